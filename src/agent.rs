@@ -0,0 +1,327 @@
+//! Distributed monitoring, where a central collector gathers the samples of
+//! benchmon agents running on several machines
+//!
+//! Agents serve their latest sample to every client which connects to them
+//! over TCP. Each new sample is sent as a message, which consists of the byte
+//! length of a JSON-encoded `AgentMessage` as a big-endian 32-bit integer,
+//! followed by the JSON itself. When sampling is slower than the collector's
+//! timeout, keep-alive messages are sent in between samples.
+//!
+//! The collector connects to every agent from a separate thread, so that a
+//! slow or disconnecting agent does not hold back the others, and merges their
+//! samples into a single CSV stream, keyed by hostname.
+
+use crate::{
+    interrupt::StopFlag,
+    output::{Sample, SampleRecord, Snapshot},
+};
+
+use serde::{Deserialize, Serialize};
+
+use slog::{debug, error, info, o, Logger};
+
+use std::{
+    collections::HashMap,
+    io::{self, Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    sync::{
+        mpsc::{self, RecvTimeoutError},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Message which an agent sends to its clients
+#[derive(Deserialize, Serialize)]
+pub enum AgentMessage {
+    /// A new sample was taken
+    Sample {
+        /// Hostname of the agent's machine
+        hostname: String,
+
+        /// Measurements
+        sample: SampleRecord,
+    },
+
+    /// No new sample was taken for a while, but the agent is still there
+    KeepAlive,
+}
+
+/// Maximal length of a message, beyond which the stream is considered corrupt
+const MAX_MESSAGE_LEN: u32 = 1 << 20;
+
+/// Time after which an agent that sends nothing, or a client that does not
+/// receive anything, is considered to be gone
+const PEER_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Time after which an agent which has no new sample to send sends a keep-alive
+/// message instead, so that its clients do not time out
+const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Delay between two attempts to connect to an unreachable agent
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+/// Send a length-prefixed message
+fn write_message(stream: &mut impl Write, message: &AgentMessage) -> io::Result<()> {
+    let json = serde_json::to_vec(message)?;
+    stream.write_all(&(json.len() as u32).to_be_bytes())?;
+    stream.write_all(&json)?;
+    stream.flush()
+}
+
+/// Receive a length-prefixed message
+fn read_message(stream: &mut impl Read) -> io::Result<AgentMessage> {
+    let mut len = [0; 4];
+    stream.read_exact(&mut len)?;
+    let len = u32::from_be_bytes(len);
+    if len > MAX_MESSAGE_LEN {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Received an overlong message ({} bytes)", len),
+        ));
+    }
+    let mut json = vec![0; len as usize];
+    stream.read_exact(&mut json)?;
+    Ok(serde_json::from_slice(&json)?)
+}
+
+/// Bind the agent's listening socket to an address or, if its port is in use
+/// and `port_retries` is nonzero, to one of the following ports
+///
+/// Failure to bind the socket is logged along with the address that could not
+/// be bound, before being reported to the caller.
+///
+pub fn bind(log: &Logger, addr: SocketAddr, port_retries: u16) -> io::Result<TcpListener> {
+    let mut addr = addr;
+    let mut retries_left = port_retries;
+    loop {
+        let err = match TcpListener::bind(addr) {
+            Ok(listener) => {
+                info!(log, "Serving samples to collectors"; "address" => %addr);
+                return Ok(listener);
+            }
+            Err(err) => err,
+        };
+        let next_port = addr.port().checked_add(1);
+        match next_port {
+            Some(next_port) if err.kind() == io::ErrorKind::AddrInUse && retries_left > 0 => {
+                info!(log, "Agent address is already in use, trying the next port";
+                      "address" => %addr);
+                addr.set_port(next_port);
+                retries_left -= 1;
+            }
+            _ => {
+                error!(log, "Failed to bind the agent's listening socket";
+                       "address" => %addr,
+                       "error" => %err);
+                return Err(err);
+            }
+        }
+    }
+}
+
+/// Serve the latest sample to every client that connects to the agent's
+/// listening socket, from background threads
+///
+/// Clients are sent every new sample, which the snapshot is polled for at
+/// twice the sampling rate (or more often, if needed to send keep-alive
+/// messages in time).
+///
+pub fn spawn_server(
+    log: Logger,
+    listener: TcpListener,
+    hostname: String,
+    snapshot: Snapshot,
+    period: Duration,
+) {
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(err) => {
+                    debug!(log, "Failed to accept a collector connection"; "error" => %err);
+                    continue;
+                }
+            };
+            let peer = stream
+                .peer_addr()
+                .map(|addr| addr.to_string())
+                .unwrap_or_else(|_| "unknown".to_owned());
+            let client_log = log.new(o!("collector" => peer));
+            let hostname = hostname.clone();
+            let snapshot = snapshot.clone();
+            thread::spawn(move || {
+                info!(client_log, "A collector connected");
+                if let Err(err) = serve_client(stream, &hostname, &snapshot, period) {
+                    info!(client_log, "A collector disconnected"; "reason" => %err);
+                }
+            });
+        }
+    });
+}
+
+/// Send every new sample to a client, until the connection fails
+fn serve_client(
+    mut stream: TcpStream,
+    hostname: &str,
+    snapshot: &Snapshot,
+    period: Duration,
+) -> io::Result<()> {
+    // A client which stops receiving samples must not block its thread forever
+    stream.set_write_timeout(Some(PEER_TIMEOUT))?;
+    let poll_interval = (period / 2).min(KEEPALIVE_INTERVAL / 2);
+    let mut last_sample: Option<Arc<Sample>> = None;
+    let mut last_send = Instant::now();
+    loop {
+        let new_sample = snapshot.latest().filter(|sample| {
+            last_sample
+                .as_ref()
+                .map_or(true, |last_sample| !Arc::ptr_eq(last_sample, sample))
+        });
+        if let Some(sample) = new_sample {
+            let message = AgentMessage::Sample {
+                hostname: hostname.to_owned(),
+                sample: SampleRecord::new(&sample),
+            };
+            write_message(&mut stream, &message)?;
+            last_sample = Some(sample);
+            last_send = Instant::now();
+        } else if last_send.elapsed() >= KEEPALIVE_INTERVAL {
+            write_message(&mut stream, &AgentMessage::KeepAlive)?;
+            last_send = Instant::now();
+        }
+        thread::sleep(poll_interval);
+    }
+}
+
+/// Receive the samples of a single agent and forward them to the collector,
+/// reconnecting whenever the connection is lost
+///
+/// Keep-alive messages are not forwarded.
+///
+fn receive_samples(log: &Logger, agent: &str, sender: &mpsc::Sender<AgentMessage>) {
+    loop {
+        let result = (|| -> io::Result<()> {
+            let addr = agent.to_socket_addrs()?.next().ok_or_else(|| {
+                io::Error::new(io::ErrorKind::NotFound, "Agent address did not resolve")
+            })?;
+            let mut stream = TcpStream::connect_timeout(&addr, PEER_TIMEOUT)?;
+            stream.set_read_timeout(Some(PEER_TIMEOUT))?;
+            info!(log, "Connected to the agent");
+            loop {
+                let message = read_message(&mut stream)?;
+                if let AgentMessage::KeepAlive = message {
+                    continue;
+                }
+                if sender.send(message).is_err() {
+                    // The collector is shutting down
+                    return Ok(());
+                }
+            }
+        })();
+        match result {
+            Ok(()) => return,
+            Err(err) => {
+                info!(log, "Lost contact with the agent, will try to reconnect";
+                      "error" => %err,
+                      "delay (s)" => RECONNECT_DELAY.as_secs());
+                thread::sleep(RECONNECT_DELAY);
+            }
+        }
+    }
+}
+
+/// Quote a CSV field if needed, doubling the quotes inside of it
+fn csv_field(value: &serde_json::Value) -> String {
+    match value {
+        serde_json::Value::Null => String::new(),
+        serde_json::Value::String(text) => format!("\"{}\"", text.replace('"', "\"\"")),
+        other => other.to_string(),
+    }
+}
+
+/// Collect the samples of several agents and print them on stdout as CSV
+/// rows, keyed by hostname, until `stop` is set
+///
+/// After a reconnection, an agent sends its latest sample again, which may
+/// have been printed already. Such duplicate samples are left out.
+///
+pub fn collect(log: &Logger, agents: &[String], stop: &StopFlag) -> io::Result<()> {
+    let (sender, receiver) = mpsc::channel();
+    for agent in agents {
+        let agent_log = log.new(o!("agent" => agent.clone()));
+        let agent = agent.clone();
+        let sender = sender.clone();
+        thread::spawn(move || receive_samples(&agent_log, &agent, &sender));
+    }
+
+    let stdout = io::stdout();
+    let mut output = stdout.lock();
+    writeln!(output, "host,{}", SampleRecord::FIELDS.join(","))?;
+    let mut last_elapsed_ns = HashMap::new();
+    while !stop.is_set() {
+        let (hostname, sample) = match receiver.recv_timeout(Duration::from_millis(100)) {
+            Ok(AgentMessage::Sample { hostname, sample }) => (hostname, sample),
+            Ok(AgentMessage::KeepAlive) | Err(RecvTimeoutError::Timeout) => continue,
+            Err(RecvTimeoutError::Disconnected) => break,
+        };
+        if last_elapsed_ns.insert(hostname.clone(), sample.elapsed_ns) == Some(sample.elapsed_ns) {
+            continue;
+        }
+        let record = serde_json::to_value(&sample)?;
+        let mut row = vec![csv_field(&serde_json::Value::String(hostname))];
+        row.extend(
+            SampleRecord::FIELDS
+                .iter()
+                .map(|field| csv_field(&record[*field])),
+        );
+        writeln!(output, "{}", row.join(","))?;
+        output.flush()?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn message_framing() {
+        let hostname = "node1".to_owned();
+        let sample = SampleRecord {
+            marker: Some("phase 2".to_owned()),
+            ..SampleRecord::default()
+        };
+        let message = AgentMessage::Sample {
+            hostname: hostname.clone(),
+            sample: sample.clone(),
+        };
+        let mut buffer = Vec::new();
+        write_message(&mut buffer, &message).unwrap();
+        let len = u32::from_be_bytes([buffer[0], buffer[1], buffer[2], buffer[3]]);
+        assert_eq!(buffer.len(), 4 + len as usize);
+
+        match read_message(&mut &buffer[..]).unwrap() {
+            AgentMessage::Sample {
+                hostname: received_hostname,
+                sample: received_sample,
+            } => {
+                assert_eq!(received_hostname, hostname);
+                assert_eq!(received_sample, sample);
+            }
+            AgentMessage::KeepAlive => panic!("Received a keep-alive instead of a sample"),
+        }
+
+        // Truncated messages are errors, not garbage
+        assert!(read_message(&mut &buffer[..buffer.len() - 1]).is_err());
+
+        // Keep-alive messages go through the same framing
+        let mut buffer = Vec::new();
+        write_message(&mut buffer, &AgentMessage::KeepAlive).unwrap();
+        match read_message(&mut &buffer[..]).unwrap() {
+            AgentMessage::KeepAlive => {}
+            AgentMessage::Sample { .. } => panic!("Received a sample instead of a keep-alive"),
+        }
+    }
+}
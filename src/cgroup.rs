@@ -0,0 +1,97 @@
+//! Resource accounting of Linux control groups (cgroups)
+//!
+//! A cgroup accounts for the resource usage of every process inside of it,
+//! including processes which were detached from their parent, so it is the
+//! most accurate way to measure a multi-process benchmark's footprint. Only
+//! the unified cgroup v2 hierarchy is supported.
+
+use heim::units::{information::byte, Information};
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+/// Mount point of the cgroup v2 hierarchy
+const CGROUP2_ROOT: &str = "/sys/fs/cgroup";
+
+/// Handle to a cgroup v2
+#[derive(Debug, Eq, PartialEq)]
+pub struct Cgroup {
+    /// Path to the cgroup's directory
+    path: PathBuf,
+}
+
+/// Resource usage of a cgroup since its creation
+pub struct CgroupUsage {
+    /// CPU time consumed by the cgroup's processes
+    pub cpu_time: Duration,
+
+    /// Current memory usage of the cgroup's processes
+    pub memory: Information,
+
+    /// Peak memory usage of the cgroup's processes, if known (needs Linux 5.19+)
+    pub peak_memory: Option<Information>,
+}
+
+impl Cgroup {
+    /// Find the cgroup that a process belongs to
+    ///
+    /// This fails if the host does not use the unified cgroup v2 hierarchy.
+    ///
+    pub fn of_process(pid: u32) -> io::Result<Self> {
+        // With cgroup v2, /proc/<pid>/cgroup has a single line "0::<path>"
+        let membership = fs::read_to_string(format!("/proc/{}/cgroup", pid))?;
+        let relative_path = membership
+            .lines()
+            .find(|line| line.starts_with("0::"))
+            .map(|line| line[3..].trim_start_matches('/'))
+            .ok_or_else(|| {
+                io::Error::new(io::ErrorKind::NotFound, "Process is not in a cgroup v2")
+            })?;
+        Self::open(Path::new(CGROUP2_ROOT).join(relative_path))
+    }
+
+    /// Open an existing cgroup given the path to its directory
+    pub fn open(path: impl Into<PathBuf>) -> io::Result<Self> {
+        let path = path.into();
+        if !path.join("cgroup.procs").is_file() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "Not a cgroup v2 directory",
+            ));
+        }
+        Ok(Self { path })
+    }
+
+    /// Path to the cgroup's directory
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Measure the resource usage of the cgroup
+    pub fn usage(&self) -> io::Result<CgroupUsage> {
+        let bad_format = || io::Error::new(io::ErrorKind::InvalidData, "Bad cgroup file format");
+        let read_bytes = |file: &str| -> io::Result<Information> {
+            fs::read_to_string(self.path.join(file))?
+                .trim()
+                .parse::<u64>()
+                .map(Information::new::<byte>)
+                .map_err(|_| bad_format())
+        };
+
+        // cpu.stat lines are of the form "usage_usec 123456"
+        let usage_usec = fs::read_to_string(self.path.join("cpu.stat"))?
+            .lines()
+            .find(|line| line.starts_with("usage_usec "))
+            .and_then(|line| line.split_whitespace().nth(1)?.parse::<u64>().ok())
+            .ok_or_else(bad_format)?;
+
+        Ok(CgroupUsage {
+            cpu_time: Duration::from_micros(usage_usec),
+            memory: read_bytes("memory.current")?,
+            peak_memory: read_bytes("memory.peak").ok(),
+        })
+    }
+}
@@ -0,0 +1,400 @@
+//! Configuration files
+//!
+//! Configuration files let teams standardize benchmon invocations across many
+//! machines. They are TOML files whose keys are the names of command-line
+//! options (with underscores instead of dashes), and whose values are:
+//!
+//! - Booleans for flags
+//! - Numbers for numerical options, including durations (in seconds)
+//! - Arrays of strings for comma-separated lists
+//! - Strings for everything else
+//!
+//! ```toml
+//! # Comments are allowed
+//! preset = "human"
+//! active_only = true
+//! period = 0.5
+//! noisy_processes = ["updatedb", "packagekitd"]
+//! ```
+//!
+//! Unknown keys are reported as errors, so that typos are not silently
+//! ignored. Options which are specified on the command line override the
+//! values from the configuration file.
+
+use crate::{format, output::SyncPolicy, summary, time, CliOpts, Preset, StallPolicy};
+
+use chrono::NaiveTime;
+
+use serde::{de::Error as _, Deserialize, Deserializer};
+
+use std::{
+    fmt::Display,
+    fs,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::Duration,
+};
+
+use structopt::clap::ArgMatches;
+
+/// Deserialize an option's value from a TOML value of type `Value`, by
+/// feeding its textual form to the option's command-line parser, so that
+/// options are validated in the same way wherever they come from
+fn parsed<'de, D, Value, T, E>(
+    deserializer: D,
+    parser: fn(&str) -> Result<T, E>,
+) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    Value: Deserialize<'de> + ToString,
+    E: Display,
+{
+    let value = Value::deserialize(deserializer)?;
+    parser(&value.to_string())
+        .map(Some)
+        .map_err(D::Error::custom)
+}
+
+/// Deserialize a string which is parsed via `FromStr`
+fn from_str<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr,
+    T::Err: Display,
+{
+    parsed::<D, String, T, T::Err>(deserializer, str::parse::<T>)
+}
+
+/// Deserialize a duration in seconds (see `time::parse_secs`)
+fn secs<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Duration>, D::Error> {
+    parsed::<D, f64, _, _>(deserializer, time::parse_secs)
+}
+
+/// Deserialize a sampling period in seconds (see `time::parse_period`)
+fn period<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Duration>, D::Error> {
+    parsed::<D, f64, _, _>(deserializer, time::parse_period)
+}
+
+/// Deserialize a timeout in seconds (see `time::parse_timeout`)
+fn timeout<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Duration>, D::Error> {
+    parsed::<D, f64, _, _>(deserializer, time::parse_timeout)
+}
+
+/// Deserialize a heartbeat interval in seconds (see `time::parse_heartbeat`)
+fn heartbeat<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Duration>, D::Error> {
+    parsed::<D, f64, _, _>(deserializer, time::parse_heartbeat)
+}
+
+/// Deserialize a time of day in HH:MM format
+fn time_of_day<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<NaiveTime>, D::Error> {
+    parsed::<D, String, _, _>(deserializer, time::parse_time_of_day)
+}
+
+/// Deserialize a maximal number of samples in memory (see
+/// `summary::parse_max_samples`)
+fn max_samples<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<usize>, D::Error> {
+    parsed::<D, usize, _, _>(deserializer, summary::parse_max_samples)
+}
+
+/// Contents of a configuration file
+///
+/// Every field stands for the command-line option of the same name, and is
+/// `None` if the configuration file does not set it. Every option can be set
+/// except for --config itself and the benchmark command of runner mode.
+///
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Config {
+    // Startup report
+    startup_report: Option<bool>,
+    report_json: Option<PathBuf>,
+    #[serde(default, deserialize_with = "timeout")]
+    disk_usage_timeout: Option<Duration>,
+    cpu_flags: Option<Vec<String>>,
+    show_virtual_fs: Option<bool>,
+    watch_disk: Option<PathBuf>,
+    dmi: Option<bool>,
+    include_serials: Option<bool>,
+    boot_params: Option<Vec<String>>,
+    noisy_processes: Option<Vec<String>>,
+    temperature_resolution: Option<f32>,
+    ignore_users: Option<Vec<String>>,
+    active_only: Option<bool>,
+    active_min_cpu: Option<f64>,
+    active_min_rss: Option<u64>,
+    require_unprivileged: Option<bool>,
+
+    // Periodic measurements
+    #[serde(default, deserialize_with = "secs")]
+    clock_jump_threshold: Option<Duration>,
+    idle: Option<bool>,
+    #[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+    iowait: Option<bool>,
+    busiest_core: Option<bool>,
+    psi: Option<bool>,
+    memory: Option<bool>,
+    numa: Option<bool>,
+    max_consecutive_errors: Option<u32>,
+    freq_spread: Option<bool>,
+    relative_freq: Option<bool>,
+    track_pid: Option<u32>,
+    net: Option<bool>,
+    ecc: Option<bool>,
+    thermal_headroom: Option<bool>,
+    jitter: Option<bool>,
+    cpu_seconds: Option<bool>,
+    totals: Option<bool>,
+    raw_counters: Option<bool>,
+    core_balance: Option<bool>,
+    summary: Option<bool>,
+    #[serde(default, deserialize_with = "max_samples")]
+    max_samples_in_memory: Option<usize>,
+    #[serde(default, deserialize_with = "from_str")]
+    decimation: Option<summary::Decimation>,
+
+    // Output
+    #[serde(default, deserialize_with = "from_str")]
+    preset: Option<Preset>,
+    time_format: Option<String>,
+    utc: Option<bool>,
+    max_col_width: Option<usize>,
+    #[serde(default, deserialize_with = "from_str")]
+    rounding: Option<format::RoundingMode>,
+    #[serde(default, deserialize_with = "from_str")]
+    small_rates: Option<format::SmallRateNotation>,
+    ascii: Option<bool>,
+    smooth: Option<usize>,
+    markers: Option<bool>,
+    csv: Option<PathBuf>,
+    csv_append: Option<bool>,
+    csv_comments: Option<bool>,
+    json: Option<PathBuf>,
+    #[cfg_attr(not(feature = "parquet"), allow(dead_code))]
+    parquet: Option<PathBuf>,
+    output: Option<PathBuf>,
+    no_stdout: Option<bool>,
+    #[serde(default, deserialize_with = "from_str")]
+    fsync: Option<SyncPolicy>,
+    marker_pipe: Option<PathBuf>,
+
+    // Sampling schedule
+    #[serde(default, deserialize_with = "period")]
+    period: Option<Duration>,
+    #[serde(default, deserialize_with = "secs")]
+    priming_interval: Option<Duration>,
+    #[serde(default, deserialize_with = "from_str")]
+    on_stall: Option<StallPolicy>,
+    #[serde(default, deserialize_with = "time_of_day")]
+    start_at: Option<NaiveTime>,
+    #[serde(default, deserialize_with = "time_of_day")]
+    stop_at: Option<NaiveTime>,
+    process_diff: Option<bool>,
+    fail_on_warning: Option<bool>,
+    #[serde(default, deserialize_with = "heartbeat")]
+    heartbeat: Option<Duration>,
+    once: Option<bool>,
+
+    // Runner mode
+    run: Option<bool>,
+    abort_on_critical: Option<bool>,
+    critical_margin: Option<f32>,
+    baseline_samples: Option<u64>,
+
+    // Distributed monitoring
+    agent: Option<SocketAddr>,
+    port_retry: Option<u16>,
+    collect: Option<Vec<String>>,
+
+    // Listings
+    list_sensors: Option<bool>,
+    list_interfaces: Option<bool>,
+    list_mounts: Option<bool>,
+}
+
+impl Config {
+    /// Load a configuration file
+    fn load(path: &Path) -> Result<Self, String> {
+        let contents = fs::read_to_string(path).map_err(|err| err.to_string())?;
+        toml::from_str(&contents).map_err(|err| err.to_string())
+    }
+
+    /// Set the options which were not specified on the command line (as told
+    /// by `matches`) to their value from the configuration file, if any
+    fn merge_into(self, cli_opts: &mut CliOpts, matches: &ArgMatches) {
+        // Truth that an option was specified on the command line, given the
+        // name of the matching CliOpts field
+        let on_command_line = |field: &str| matches.occurrences_of(field.replace('_', "-")) > 0;
+
+        // Options which have a value, or a default value, on the command line
+        macro_rules! merge {
+            ($($field:ident),*) => {
+                $(
+                    if let Some(value) = self.$field {
+                        if !on_command_line(stringify!($field)) {
+                            cli_opts.$field = value;
+                        }
+                    }
+                )*
+            };
+        }
+
+        // Options which are unset unless specified on the command line
+        macro_rules! merge_optional {
+            ($($field:ident),*) => {
+                $(
+                    if let Some(value) = self.$field {
+                        if !on_command_line(stringify!($field)) {
+                            cli_opts.$field = Some(value);
+                        }
+                    }
+                )*
+            };
+        }
+
+        merge!(
+            startup_report,
+            disk_usage_timeout,
+            cpu_flags,
+            show_virtual_fs,
+            dmi,
+            include_serials,
+            time_format,
+            utc,
+            boot_params,
+            noisy_processes,
+            temperature_resolution,
+            ignore_users,
+            active_only,
+            active_min_cpu,
+            active_min_rss,
+            require_unprivileged,
+            clock_jump_threshold,
+            idle,
+            busiest_core,
+            psi,
+            memory,
+            numa,
+            max_consecutive_errors,
+            freq_spread,
+            relative_freq,
+            net,
+            ecc,
+            thermal_headroom,
+            jitter,
+            cpu_seconds,
+            totals,
+            raw_counters,
+            core_balance,
+            summary,
+            max_samples_in_memory,
+            decimation,
+            rounding,
+            small_rates,
+            ascii,
+            smooth,
+            markers,
+            csv_append,
+            csv_comments,
+            no_stdout,
+            fsync,
+            period,
+            priming_interval,
+            on_stall,
+            process_diff,
+            fail_on_warning,
+            once,
+            run,
+            abort_on_critical,
+            critical_margin,
+            baseline_samples,
+            port_retry,
+            collect,
+            list_sensors,
+            list_interfaces,
+            list_mounts
+        );
+        merge_optional!(
+            preset,
+            report_json,
+            watch_disk,
+            track_pid,
+            max_col_width,
+            csv,
+            json,
+            output,
+            marker_pipe,
+            start_at,
+            stop_at,
+            heartbeat,
+            agent
+        );
+
+        // I/O wait is only measured on Linux, but configuration files may be
+        // shared with other OSes
+        #[cfg(target_os = "linux")]
+        merge!(iowait);
+
+        // Likewise, configuration files may be shared with benchmon builds
+        // which do not support Parquet output
+        #[cfg(feature = "parquet")]
+        merge_optional!(parquet);
+    }
+}
+
+impl CliOpts {
+    /// Load the configuration file designated by --config, if any, and use it
+    /// to fill in the options which were not specified on the command line
+    /// (as told by the `matches` that these options were parsed from)
+    pub fn apply_config(&mut self, matches: &ArgMatches) -> Result<(), String> {
+        let path = match &self.config {
+            Some(path) => path.clone(),
+            None => return Ok(()),
+        };
+        let config = Config::load(&path)
+            .map_err(|err| format!("Invalid configuration file {}: {}", path.display(), err))?;
+        config.merge_into(self, matches);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_config() {
+        let config = toml::from_str::<Config>(
+            r#"
+            # Comments are allowed, including after values
+            preset = "csv"  # like this
+            active_only = true
+            period = 2
+            priming_interval = 0.5
+            start_at = "22:30"
+            noisy_processes = [
+                "updatedb",
+                "a, b",
+            ]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.preset, Some(Preset::Csv));
+        assert_eq!(config.active_only, Some(true));
+        assert_eq!(config.period, Some(Duration::from_secs(2)));
+        assert_eq!(config.priming_interval, Some(Duration::from_millis(500)));
+        assert_eq!(config.start_at, NaiveTime::from_hms_opt(22, 30, 0));
+        assert_eq!(
+            config.noisy_processes,
+            Some(vec!["updatedb".to_owned(), "a, b".to_owned()])
+        );
+        assert_eq!(config.idle, None);
+
+        // Typos and invalid values are reported
+        assert!(toml::from_str::<Config>("actve_only = true").is_err());
+        assert!(toml::from_str::<Config>("config = \"other.toml\"").is_err());
+        assert!(toml::from_str::<Config>("period = 0").is_err());
+        assert!(toml::from_str::<Config>("preset = \"fancy\"").is_err());
+        assert!(toml::from_str::<Config>("idle = \"yes\"").is_err());
+    }
+}
@@ -0,0 +1,108 @@
+//! Check how evenly a parallel workload was spread across CPU cores
+//!
+//! The utilization of each logical core is accumulated over the whole run,
+//! then reported at the end of it as a bar chart, so that cores which were
+//! left idle or overloaded by a supposedly balanced benchmark stand out.
+
+use super::DurationBreakdown;
+
+use crate::format::{self, TableStyle};
+
+use slog::{info, Logger};
+
+/// Utilization of the busiest logical CPU core, between 0.0 and 1.0, given the
+/// per-core CPU time breakdowns of a sample (as produced by
+/// `Monitor::per_core_time_change()`), or None if there is no core
+pub fn busiest_core(per_core: &[DurationBreakdown]) -> Option<f32> {
+    per_core
+        .iter()
+        .map(DurationBreakdown::busy_frac)
+        .fold(None, |max, busy_frac| {
+            Some(max.map_or(busy_frac, |max: f32| max.max(busy_frac)))
+        })
+}
+
+/// Accumulator of the utilization of each logical CPU core
+pub struct CoreBalance {
+    /// Busy time and elapsed time of each logical core, in seconds
+    cores: Box<[(f64, f64)]>,
+}
+
+impl CoreBalance {
+    /// Deviation from the mean utilization across cores beyond which a core
+    /// is flagged as under- or over-utilized
+    const IMBALANCE_THRESHOLD: f64 = 0.1;
+
+    /// Width of the utilization bars, in characters
+    const BAR_WIDTH: usize = 20;
+
+    /// Prepare to track the utilization of `core_count` logical CPU cores
+    pub fn new(core_count: u64) -> Self {
+        Self {
+            cores: vec![(0.0, 0.0); core_count as usize].into_boxed_slice(),
+        }
+    }
+
+    /// Account for the per-core CPU time breakdowns of a new sample, as
+    /// produced by `Monitor::per_core_time_change()`
+    pub fn record(&mut self, per_core: &[DurationBreakdown]) {
+        debug_assert_eq!(per_core.len(), self.cores.len());
+        for ((busy, elapsed), breakdown) in self.cores.iter_mut().zip(per_core) {
+            let busy_frac = f64::from(breakdown.busy_frac());
+            let overall_secs = breakdown.overall.as_secs_f64();
+            *busy += busy_frac * overall_secs;
+            *elapsed += overall_secs;
+        }
+    }
+
+    /// Average utilization of each core over the samples recorded so far,
+    /// between 0.0 and 1.0, or None if no time was recorded for that core
+    pub fn utilizations(&self) -> Vec<Option<f64>> {
+        self.cores
+            .iter()
+            .map(|&(busy, elapsed)| {
+                if elapsed > 0.0 {
+                    Some((busy / elapsed).min(1.0))
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
+    /// Report the utilization of each core as a bar chart, along with the mean
+    /// and standard deviation across cores, flagging cores whose utilization
+    /// is far from the mean
+    pub fn report(&self, log: &Logger, style: TableStyle) {
+        let utilizations = self.utilizations();
+        let known = utilizations.iter().flatten().copied().collect::<Vec<_>>();
+        if known.is_empty() {
+            info!(log, "No CPU time was recorded, core balance is unknown");
+            return;
+        }
+        let mean = known.iter().sum::<f64>() / known.len() as f64;
+        let variance = known.iter().map(|u| (u - mean).powi(2)).sum::<f64>() / known.len() as f64;
+        let std_dev = variance.sqrt();
+
+        info!(log, "Per-core CPU utilization over the run";
+              "mean (%)" => format!("{:.1}", mean * 100.0),
+              "standard deviation (%)" => format!("{:.1}", std_dev * 100.0));
+        for (core, utilization) in utilizations.into_iter().enumerate() {
+            let utilization = match utilization {
+                Some(utilization) => utilization,
+                None => continue,
+            };
+            let balance = if utilization < mean - Self::IMBALANCE_THRESHOLD {
+                "under-utilized"
+            } else if utilization > mean + Self::IMBALANCE_THRESHOLD {
+                "over-utilized"
+            } else {
+                "balanced"
+            };
+            info!(log, "[{}]", format::display_bar(utilization, Self::BAR_WIDTH, style);
+                  "core" => core,
+                  "utilization (%)" => format!("{:.1}", utilization * 100.0),
+                  "balance" => balance);
+        }
+    }
+}
@@ -0,0 +1,29 @@
+//! Query and display CPU feature flags
+
+use crate::platform::{Host, Platform};
+
+use slog::{debug, info, Logger};
+
+/// Report which of the requested CPU feature flags the host's CPU supports
+///
+/// Flag names follow the conventions of the Linux /proc/cpuinfo file (e.g.
+/// "avx512f" or "amx_tile" on x86, "sve" or "asimd" on ARM).
+///
+pub fn startup_report(log: &Logger, requested_flags: &[String]) {
+    let flags = match Host::cpu_flags() {
+        Ok(flags) => flags,
+        Err(err) => {
+            debug!(log, "CPU feature flags are unavailable"; "error" => %err);
+            return;
+        }
+    };
+    debug!(log, "Received CPU feature flags"; "flags" => ?flags);
+
+    let (supported, unsupported): (Vec<&str>, Vec<&str>) = requested_flags
+        .iter()
+        .map(String::as_str)
+        .partition(|&flag| flags.contains(flag));
+    info!(log, "Checked CPU feature flags";
+          "supported" => supported.join(" "),
+          "unsupported" => unsupported.join(" "));
+}
@@ -1,14 +1,55 @@
 //! Query and display CPU frequency information
 
-use heim::units::Frequency;
+use crate::{cpu::FrequencyRange, format};
+
+use heim::units::{frequency::megahertz, Frequency};
+
+use std::fmt::Display;
 
 /// CPU clock frequency column formatting
 pub struct Formatter {
-    /// Minimal CPU frequency, if known
-    min: Option<Frequency>,
+    /// Title of the column in tabular output
+    title: &'static str,
 
-    /// Maximal CPU frequency, if known
-    max: Option<Frequency>,
+    /// Width of the output column
+    output_width: usize,
 }
 
-// TODO: Implement this
+impl Formatter {
+    /// Number of digits which is reserved for frequencies in MHz if the
+    /// maximal CPU frequency is unknown (enough for up to 99.999 GHz)
+    const DEFAULT_DIGITS: usize = 5;
+
+    /// Construct a CPU frequency column formatter, with a certain title, for a
+    /// certain frequency range and an optional maximal column width (see
+    /// `format::cap_col_width()`)
+    pub fn new(title: &'static str, range: &FrequencyRange, max_width: Option<usize>) -> Self {
+        let max_digits = range
+            .max
+            .map(|max| format::decimal_digits(max.get::<megahertz>()))
+            .unwrap_or(Self::DEFAULT_DIGITS);
+        let natural_width = max_digits.max(format::str_width(title));
+        Self {
+            title,
+            output_width: format::cap_col_width(natural_width, max_width),
+        }
+    }
+
+    /// Display the title of a column of results, surrounded by a fill character
+    pub fn display_title(&self, fill: char) -> impl Display + '_ {
+        format::display_col_header(self.title, self.output_width, fill)
+    }
+
+    /// Display a CPU frequency within a column of results, in MHz
+    pub fn display_data(&self, freq: Frequency) -> impl Display {
+        let mhz = freq.get::<megahertz>();
+        let output_width = self.output_width;
+        format::display_col_data(format!("{:>1$}", mhz, output_width), output_width)
+    }
+
+    /// Display a placeholder within the column, for data which the platform
+    /// does not provide
+    pub fn display_unavailable(&self) -> impl Display {
+        format::display_col_placeholder("--", self.output_width)
+    }
+}
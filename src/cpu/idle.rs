@@ -0,0 +1,89 @@
+//! Query and display CPU idle state (aka C-state) information
+
+use crate::{
+    platform::{Host, Platform},
+    warning::BenchmonWarning,
+};
+
+use slog::{debug, info, warn, Logger};
+
+use std::{collections::BTreeMap, time::Duration};
+
+/// Idle state (aka C-state) of a CPU core
+pub struct IdleState {
+    /// Name of the idle state (e.g. "C1E")
+    pub name: String,
+
+    /// Time that it takes for a core to exit this idle state
+    pub exit_latency: Duration,
+
+    /// Time spent by the core in this idle state since boot
+    pub residency: Duration,
+
+    /// Truth that the core is not allowed to enter this idle state
+    pub disabled: bool,
+}
+
+/// Exit latency above which an idle state is considered to be deep enough to
+/// hurt the latency of bursty workloads
+const DEEP_STATE_LATENCY: Duration = Duration::from_micros(50);
+
+/// Report on the idle states of the host's CPU cores
+// TODO: Optionally monitor the time spent in each idle state during sampling
+pub fn startup_report(log: &Logger) {
+    // Query the per-core idle states, if available
+    let per_cpu_states = match Host::cpu_idle_states() {
+        Ok(per_cpu_states) if !per_cpu_states.is_empty() => per_cpu_states,
+        Ok(_) => {
+            debug!(log, "No CPU idle state information is available");
+            return;
+        }
+        Err(err) => {
+            debug!(log, "CPU idle state information is unavailable"; "error" => %err);
+            return;
+        }
+    };
+
+    // Report per-core residencies at the debug level, and aggregate them across
+    // cores (in state order) for the higher-level report
+    let mut aggregated = BTreeMap::<(Duration, String), (Duration, usize)>::new();
+    for (cpu, states) in per_cpu_states.iter().enumerate() {
+        for state in states {
+            debug!(log, "Found a CPU idle state";
+                   "cpu" => cpu,
+                   "name" => &state.name,
+                   "exit latency (µs)" => state.exit_latency.as_micros() as u64,
+                   "residency (s)" => state.residency.as_secs_f64(),
+                   "disabled" => state.disabled);
+            let entry = aggregated
+                .entry((state.exit_latency, state.name.clone()))
+                .or_default();
+            entry.0 += state.residency;
+            if !state.disabled {
+                entry.1 += 1;
+            }
+        }
+    }
+    for ((exit_latency, name), (residency, enabled_cpus)) in &aggregated {
+        info!(log, "Received CPU idle state information";
+              "name" => name,
+              "exit latency (µs)" => exit_latency.as_micros() as u64,
+              "total residency since boot (s)" => residency.as_secs_f64(),
+              "enabled on CPUs" => enabled_cpus);
+    }
+
+    // Warn about deep idle states, which hurt latency-sensitive work
+    let deep_states = aggregated
+        .iter()
+        .filter(|((exit_latency, _), (_, enabled_cpus))| {
+            *exit_latency >= DEEP_STATE_LATENCY && *enabled_cpus > 0
+        })
+        .map(|((_, name), _)| name.as_str())
+        .collect::<Vec<_>>();
+    if !deep_states.is_empty() {
+        warn!(log, "Deep CPU idle states are enabled, they may hurt the \
+                    performance of latency-sensitive benchmarks";
+              "deep states" => deep_states.join(", "),
+              "warning" => BenchmonWarning::DeepIdleStates);
+    }
+}
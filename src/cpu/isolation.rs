@@ -0,0 +1,61 @@
+//! Query and display the isolation of CPU cores from system activity
+//!
+//! On Linux, benchmarks can be shielded from OS noise by removing some CPU
+//! cores from the scheduler's load balancing (isolcpus) and stopping the
+//! scheduler tick on them (nohz_full). Since these settings are easy to get
+//! wrong, we report which cores they actually apply to.
+
+use crate::platform::{Host, Platform};
+
+use slog::{debug, info, Logger};
+
+use std::{collections::BTreeSet, io};
+
+// TODO: Once the set of monitored CPU cores can be selected, warn when some of
+//       the monitored cores are not isolated while others are, as that
+//       usually means that the wrong cores were selected.
+
+/// Display a set of CPU cores in the compact sysfs list format ("0-3,8")
+fn display_cpu_list(cpus: &BTreeSet<usize>) -> String {
+    let mut ranges = Vec::<(usize, usize)>::new();
+    for &cpu in cpus {
+        match ranges.last_mut() {
+            Some((_, end)) if *end + 1 == cpu => *end = cpu,
+            _ => ranges.push((cpu, cpu)),
+        }
+    }
+    let ranges = ranges
+        .into_iter()
+        .map(|(start, end)| {
+            if start == end {
+                format!("{}", start)
+            } else {
+                format!("{}-{}", start, end)
+            }
+        })
+        .collect::<Vec<_>>();
+    if ranges.is_empty() {
+        "none".to_owned()
+    } else {
+        ranges.join(",")
+    }
+}
+
+/// Report which CPU cores are isolated and tickless
+pub fn startup_report(log: &Logger) {
+    let display_result = |result: io::Result<BTreeSet<usize>>, what: &str| match result {
+        Ok(cpus) => Some(display_cpu_list(&cpus)),
+        Err(err) => {
+            debug!(log, "Information about {} CPU cores is unavailable", what;
+                   "error" => %err);
+            None
+        }
+    };
+    let isolated = display_result(Host::isolated_cpus(), "isolated");
+    let tickless = display_result(Host::tickless_cpus(), "tickless");
+    if isolated.is_some() || tickless.is_some() {
+        info!(log, "Received CPU isolation information";
+              "isolated cores" => isolated.as_ref().map(String::as_str).unwrap_or("unknown"),
+              "tickless cores" => tickless.as_ref().map(String::as_str).unwrap_or("unknown"));
+    }
+}
@@ -1,6 +1,15 @@
 //! Query and display CPU information
 
+pub mod balance;
+pub mod flags;
 pub mod freq;
+pub mod idle;
+pub mod isolation;
+
+use crate::{
+    platform::{Host, Platform},
+    warning::BenchmonWarning,
+};
 
 use futures_util::{
     future::{FutureExt, TryFutureExt},
@@ -15,10 +24,15 @@ use heim::{
 
 use slog::{debug, info, warn, Logger};
 
-use std::time::{Duration, Instant};
+use std::{
+    borrow::Cow,
+    error::Error,
+    fmt, io,
+    time::{Duration, Instant},
+};
 
 /// Range of possible CPU frequencies
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Default)]
 pub struct FrequencyRange {
     /// Minimal CPU frequency (if known)
     pub min: Option<Frequency>,
@@ -27,17 +41,86 @@ pub struct FrequencyRange {
     pub max: Option<Frequency>,
 }
 
+/// Raw cumulative CPU counters, as reported by the OS
+///
+/// Unlike the other CPU measurements, these are not differences between two
+/// measurements, but monotonic counts since boot, which is useful for users
+/// who want to perform their own differencing.
+///
+#[derive(Clone)]
+pub struct RawCounters {
+    /// Time spent in user mode processes since boot (including guests)
+    pub user: Time,
+
+    /// Time spent in kernel mode processes since boot
+    pub system: Time,
+
+    /// Time spent doing nothing since boot
+    pub idle: Time,
+
+    /// Time spent in niced user mode processes since boot
+    #[cfg(target_os = "linux")]
+    pub nice: Time,
+
+    /// Time spent waiting for I/O since boot
+    #[cfg(target_os = "linux")]
+    pub io_wait: Time,
+
+    /// Time stolen by the hypervisor since boot
+    #[cfg(target_os = "linux")]
+    pub steal: Time,
+
+    /// Context switches since boot
+    pub ctx_switches: u64,
+
+    /// Interrupts since boot
+    pub interrupts: u64,
+}
+
+/// Spread of the current CPU frequencies across logical cores
+#[derive(Clone, Copy)]
+pub struct FrequencySpread {
+    /// Frequency of the slowest core
+    pub min: Frequency,
+
+    /// Frequency of the fastest core
+    pub max: Frequency,
+}
+
+/// Context switches, broken down by cause
+#[derive(Clone, Copy, Default)]
+pub struct ContextSwitches {
+    /// Switches where a thread gave up the CPU itself (e.g. to wait for I/O)
+    pub voluntary: u64,
+
+    /// Switches where a thread was preempted by the OS scheduler, which is a
+    /// sign that other activity is competing with it for CPU time
+    pub involuntary: u64,
+}
+
 /// CPU statistics variation between two measurements
 pub struct StatsDelta {
+    /// Time elapsed since the previous measurement
+    pub elapsed: Duration,
+
     /// New context switches (voluntary + involuntary)
     pub new_ctx_switches: u64,
 
+    /// New context switches of the tracked process, broken down by cause (if
+    /// a process is tracked and the platform exposes this information)
+    pub new_tracked_ctx_switches: Option<ContextSwitches>,
+
     /// New interrupts
     pub new_interrupts: u64,
 
     /// New software interrupts (Linux-only)
     #[cfg(target_os = "linux")]
     pub new_soft_interrupts: u64,
+
+    /// Truth that a system-wide counter went backwards since the previous
+    /// measurement (because it wrapped around or was reset), in which case
+    /// the corresponding count of new events is reported as zero
+    pub counter_reset: bool,
 }
 
 /// Breakdown of elapsed CPU time by system activity
@@ -57,6 +140,7 @@ pub struct StatsDelta {
 // TODO: Once we support benchmark runner operation, provide a good API for such
 //       "measuring the user/kernel time spent doing something" kind of use
 //       cases. Ideally something like Instant::now() and elapsed().
+#[derive(Clone)]
 pub struct DurationBreakdown {
     /// Overall CPU time elapsed since last measurement
     pub overall: Duration,
@@ -75,7 +159,83 @@ pub struct DurationBreakdown {
     pub linux_fracs: LinuxDurationFracs,
 }
 
+impl DurationBreakdown {
+    /// Sum of the mutually exclusive time fractions
+    ///
+    /// This should be 1.0, give or take rounding errors. If it is materially
+    /// above that, the measurement was affected by a glitch such as an
+    /// unhandled change in the number of CPU cores.
+    ///
+    pub fn total_frac(&self) -> f32 {
+        let total = self.user_frac + self.system_frac + self.idle_frac;
+        #[cfg(target_os = "linux")]
+        let total = {
+            // Guest time is already accounted for in user time
+            let fracs = &self.linux_fracs;
+            total
+                + fracs.nice_frac
+                + fracs.io_wait_frac
+                + fracs.irq_frac
+                + fracs.soft_irq_frac
+                + fracs.steal_frac
+        };
+        total
+    }
+
+    /// Fraction of the time during which the CPU was busy, i.e. neither idle
+    /// nor waiting for I/O
+    ///
+    /// Fractions are all zero when too little time elapsed, so this is not
+    /// computed as the complement of the idle fraction.
+    ///
+    pub fn busy_frac(&self) -> f32 {
+        let idle_frac = self.idle_frac;
+        #[cfg(target_os = "linux")]
+        let idle_frac = idle_frac + self.linux_fracs.io_wait_frac;
+        (self.total_frac() - idle_frac).max(0.0)
+    }
+
+    /// Sum of the mutually exclusive time fractions above which a breakdown is
+    /// considered to be affected by a measurement glitch (e.g. clock skew)
+    const MAX_PLAUSIBLE_TOTAL_FRAC: f32 = 1.05;
+
+    /// Renormalize the time fractions if they sum up implausibly far above
+    /// 1.0, in which case their original sum is returned
+    pub fn fix_implausible_total(&mut self) -> Option<f32> {
+        let total = self.total_frac();
+        if total > Self::MAX_PLAUSIBLE_TOTAL_FRAC {
+            self.renormalize();
+            Some(total)
+        } else {
+            None
+        }
+    }
+
+    /// Scale all time fractions so that they sum up to 1.0
+    pub fn renormalize(&mut self) {
+        let total = self.total_frac();
+        if total <= 0.0 {
+            return;
+        }
+        self.user_frac /= total;
+        self.system_frac /= total;
+        self.idle_frac /= total;
+        #[cfg(target_os = "linux")]
+        {
+            let fracs = &mut self.linux_fracs;
+            fracs.nice_frac /= total;
+            fracs.io_wait_frac /= total;
+            fracs.irq_frac /= total;
+            fracs.soft_irq_frac /= total;
+            fracs.steal_frac /= total;
+            fracs.guest_frac = fracs.guest_frac.map(|frac| frac / total);
+            fracs.guest_nice_frac = fracs.guest_nice_frac.map(|frac| frac / total);
+        }
+    }
+}
+
 /// Linux-specific complement to CPUDuration
+#[derive(Clone)]
 pub struct LinuxDurationFracs {
     /// Fraction of time spent in niced user mode processes (including guests)
     pub nice_frac: f32,
@@ -99,13 +259,180 @@ pub struct LinuxDurationFracs {
     pub guest_nice_frac: Option<f32>,
 }
 
+/// Cumulative CPU time counters, as provided by `heim::cpu::CpuTime`
+///
+/// This abstraction allows `duration_breakdown` to be tested with synthetic
+/// counters, which heim does not let us build.
+///
+trait CpuTimeCounters {
+    /// Time spent in user mode processes (including guests)
+    fn user(&self) -> Time;
+
+    /// Time spent in kernel mode processes
+    fn system(&self) -> Time;
+
+    /// Time spent doing nothing
+    fn idle(&self) -> Time;
+
+    /// Time spent in niced user mode processes (including guests)
+    #[cfg(target_os = "linux")]
+    fn nice(&self) -> Time;
+
+    /// Time spent waiting for I/O to complete
+    #[cfg(target_os = "linux")]
+    fn io_wait(&self) -> Time;
+
+    /// Time spent servicing hardware interrupts
+    #[cfg(target_os = "linux")]
+    fn irq(&self) -> Time;
+
+    /// Time spent servicing software interrupts
+    #[cfg(target_os = "linux")]
+    fn soft_irq(&self) -> Time;
+
+    /// Time spent by other OSes running in a virtualized environment
+    #[cfg(target_os = "linux")]
+    fn steal(&self) -> Time;
+
+    /// Time spent running a vCPU for Linux-controlled guests (if known)
+    #[cfg(target_os = "linux")]
+    fn guest(&self) -> Option<Time>;
+
+    /// Time spent running a vCPU for niced Linux-controlled guests (if known)
+    #[cfg(target_os = "linux")]
+    fn guest_nice(&self) -> Option<Time>;
+}
+
+impl CpuTimeCounters for CpuTime {
+    fn user(&self) -> Time {
+        CpuTime::user(self)
+    }
+
+    fn system(&self) -> Time {
+        CpuTime::system(self)
+    }
+
+    fn idle(&self) -> Time {
+        CpuTime::idle(self)
+    }
+
+    #[cfg(target_os = "linux")]
+    fn nice(&self) -> Time {
+        heim::cpu::os::linux::CpuTimeExt::nice(self)
+    }
+
+    #[cfg(target_os = "linux")]
+    fn io_wait(&self) -> Time {
+        heim::cpu::os::linux::CpuTimeExt::io_wait(self)
+    }
+
+    #[cfg(target_os = "linux")]
+    fn irq(&self) -> Time {
+        heim::cpu::os::linux::CpuTimeExt::irq(self)
+    }
+
+    #[cfg(target_os = "linux")]
+    fn soft_irq(&self) -> Time {
+        heim::cpu::os::linux::CpuTimeExt::soft_irq(self)
+    }
+
+    #[cfg(target_os = "linux")]
+    fn steal(&self) -> Time {
+        heim::cpu::os::linux::CpuTimeExt::steal(self)
+    }
+
+    #[cfg(target_os = "linux")]
+    fn guest(&self) -> Option<Time> {
+        heim::cpu::os::linux::CpuTimeExt::guest(self)
+    }
+
+    #[cfg(target_os = "linux")]
+    fn guest_nice(&self) -> Option<Time> {
+        heim::cpu::os::linux::CpuTimeExt::guest_nice(self)
+    }
+}
+
+/// Compute the breakdown of the CPU time that elapsed between two timings of
+/// `core_count` logical CPU cores
+fn duration_breakdown<Counters: CpuTimeCounters>(
+    (old_time, old_timestamp): &(Counters, Instant),
+    (time, timestamp): (&Counters, Instant),
+    core_count: u64,
+) -> DurationBreakdown {
+    let overall = (timestamp - *old_timestamp) * core_count as u32;
+    let overall_secs = overall.as_secs_f64();
+    // Time fractions are not meaningful if almost no time elapsed
+    const MIN_OVERALL_SECS: f64 = 1e-6;
+    let to_frac = |time: Time| -> f32 {
+        if overall_secs < MIN_OVERALL_SECS {
+            return 0.0;
+        }
+        let time_secs = time.get::<second>();
+        (time_secs / overall_secs) as f32
+    };
+    let guest_sub = |t1: Option<Time>, t2: Option<Time>| -> Option<Time> {
+        match (t1, t2) {
+            (Some(t1), Some(t2)) => Some(t1 - t2),
+            (None, None) => None,
+            _ => unreachable!(),
+        }
+    };
+
+    DurationBreakdown {
+        overall,
+        user_frac: to_frac(time.user() - old_time.user()),
+        system_frac: to_frac(time.system() - old_time.system()),
+        idle_frac: to_frac(time.idle() - old_time.idle()),
+        #[cfg(target_os = "linux")]
+        linux_fracs: LinuxDurationFracs {
+            nice_frac: to_frac(time.nice() - old_time.nice()),
+            io_wait_frac: to_frac(time.io_wait() - old_time.io_wait()),
+            irq_frac: to_frac(time.irq() - old_time.irq()),
+            soft_irq_frac: to_frac(time.soft_irq() - old_time.soft_irq()),
+            steal_frac: to_frac(time.steal() - old_time.steal()),
+            guest_frac: guest_sub(time.guest(), old_time.guest()).map(to_frac),
+            guest_nice_frac: guest_sub(time.guest_nice(), old_time.guest_nice()).map(to_frac),
+        },
+    }
+}
+
+/// Error which is reported when the number of logical CPU cores changed since
+/// monitoring started (e.g. because vCPUs were hot-plugged into a VM), which
+/// the CPU monitor does not support
+#[derive(Debug)]
+pub struct CoreCountChanged {
+    /// Number of logical CPU cores when monitoring started
+    pub initial: u64,
+
+    /// Current number of logical CPU cores
+    pub current: u64,
+}
+
+impl fmt::Display for CoreCountChanged {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "The number of logical CPU cores changed from {} to {} during monitoring, \
+             this is not supported",
+            self.initial, self.current
+        )
+    }
+}
+
+impl Error for CoreCountChanged {}
+
 /// CPU monitoring mechanism
 pub struct Monitor {
     /// Global frequency range
     frequency_range: FrequencyRange,
 
-    /// Last measured statistics (context switches, interrupts, etc)
-    stats: CpuStats,
+    /// Last measured statistics (context switches, interrupts, etc) +
+    /// associated timestamp
+    stats: (CpuStats, Instant),
+
+    /// Process whose context switches are tracked, and its last measured
+    /// context switch counts (if any)
+    tracked_process: Option<(u32, ContextSwitches)>,
 
     /// Last measured aggregated timings + associated timestamp
     time: (CpuTime, Instant),
@@ -123,6 +450,9 @@ pub struct Monitor {
 
     /// Number of physical cores (if known)
     physical_count: Option<u64>,
+
+    /// Number of CPU sockets (if known)
+    socket_count: Option<u64>,
 }
 
 impl Monitor {
@@ -138,17 +468,12 @@ impl Monitor {
         // Request long-lasting CPU properties and initial CPU state
         // TODO: Do some type length profiling here
         let frequency_range = heim::cpu::frequency().map_ok(extract_range).boxed();
-        let stats = heim::cpu::stats();
+        let stats = heim::cpu::stats().map_ok(|stats| (stats, Instant::now()));
         let time = heim::cpu::time().map_ok(add_timestamp);
         let logical_count = heim::cpu::logical_count();
-        #[cfg(target_os = "linux")]
-        let frequency_ranges = heim::cpu::os::linux::frequencies()
-            .map_ok(extract_range)
-            .try_collect::<Vec<_>>()
-            .map_ok(|vec| Some(vec.into_boxed_slice()))
-            .boxed();
-        #[cfg(not(target_os = "linux"))]
-        let frequency_ranges = futures_util::future::ok(None);
+        let frequency_ranges = Host::per_cpu_frequencies().map_ok(|freqs| {
+            freqs.map(|freqs| freqs.into_iter().map(extract_range).collect::<Box<[_]>>())
+        });
         let times = heim::cpu::times()
             .map_ok(add_timestamp)
             .try_collect::<Vec<_>>()
@@ -169,11 +494,13 @@ impl Monitor {
         Ok(Self {
             frequency_range,
             stats,
+            tracked_process: None,
             time,
             logical_count,
             frequency_ranges,
             times,
             physical_count,
+            socket_count: socket_count(),
         })
     }
 
@@ -197,72 +524,180 @@ impl Monitor {
         use heim::cpu::os::linux::CpuStatsExt;
 
         let stats = heim::cpu::stats().await?;
+        let timestamp = Instant::now();
+
+        // If the tracked process is gone, stop tracking it
+        let new_tracked_ctx_switches = match self.tracked_process {
+            Some((pid, old_switches)) => match Host::process_context_switches(pid) {
+                Ok(switches) => {
+                    self.tracked_process = Some((pid, switches));
+                    Some(ContextSwitches {
+                        voluntary: switches.voluntary.saturating_sub(old_switches.voluntary),
+                        involuntary: switches
+                            .involuntary
+                            .saturating_sub(old_switches.involuntary),
+                    })
+                }
+                Err(_) => {
+                    self.tracked_process = None;
+                    None
+                }
+            },
+            None => None,
+        };
+
+        // Kernel counters may go backwards if they wrap around or are reset
+        // (e.g. across suspend/resume, or in some containers)
+        let (old_stats, old_timestamp) = &self.stats;
+        let mut counter_reset = false;
+        let mut new_events = |new: u64, old: u64| {
+            counter_reset |= new < old;
+            new.saturating_sub(old)
+        };
+        let new_ctx_switches = new_events(stats.ctx_switches(), old_stats.ctx_switches());
+        let new_interrupts = new_events(stats.interrupts(), old_stats.interrupts());
+        #[cfg(target_os = "linux")]
+        let new_soft_interrupts = new_events(stats.soft_interrupts(), old_stats.soft_interrupts());
         let result = StatsDelta {
-            new_ctx_switches: stats.ctx_switches() - self.stats.ctx_switches(),
-            new_interrupts: stats.interrupts() - self.stats.interrupts(),
+            elapsed: timestamp - *old_timestamp,
+            new_ctx_switches,
+            new_tracked_ctx_switches,
+            new_interrupts,
             #[cfg(target_os = "linux")]
-            new_soft_interrupts: stats.soft_interrupts() - self.stats.soft_interrupts(),
+            new_soft_interrupts,
+            counter_reset,
         };
-        self.stats = stats;
+        self.stats = (stats, timestamp);
         Ok(result)
     }
 
+    /// Start tracking the context switches of a process (e.g. a benchmark)
+    ///
+    /// System-wide context switch counts are not broken down by cause, but
+    /// per-process ones are, and involuntary context switches of a benchmark
+    /// are a strong indicator of interference from other system activity.
+    /// Tracking stops if the process exits.
+    ///
+    pub fn track_process(&mut self, pid: u32) -> io::Result<()> {
+        self.tracked_process = Some((pid, Host::process_context_switches(pid)?));
+        Ok(())
+    }
+
+    /// PID of the process whose context switches are being tracked, if any
+    pub fn tracked_pid(&self) -> Option<u32> {
+        self.tracked_process.as_ref().map(|(pid, _switches)| *pid)
+    }
+
     /// Report the change in aggregated CPU timings since the last measurement
     ///
     /// If you want the CPU timings since boot, it is better to call
     /// `heim::cpu::time()` directly.
     ///
-    pub async fn time_change(&mut self) -> heim::Result<DurationBreakdown> {
-        #[cfg(target_os = "linux")]
-        use heim::cpu::os::linux::CpuTimeExt;
-
-        let time = heim::cpu::time().await?;
+    /// If almost no time elapsed since the last measurement, time fractions
+    /// cannot be meaningfully computed, and are all reported as zero.
+    ///
+    /// Time fractions are computed with respect to the number of logical CPU
+    /// cores that was observed when monitoring started, so if that number
+    /// changed, the measurement is meaningless and a `CoreCountChanged` error
+    /// is reported. Unlike a failed query, this error is not transient, and
+    /// monitoring should not go on after it.
+    ///
+    /// This is the only measurement which queries the number of logical CPU
+    /// cores, so it should be performed on every sample.
+    ///
+    pub async fn time_change(
+        &mut self,
+    ) -> heim::Result<Result<DurationBreakdown, CoreCountChanged>> {
+        let (time, logical_count) = try_join!(heim::cpu::time(), heim::cpu::logical_count())?;
         let timestamp = Instant::now();
-        let (old_time, old_timestamp) = &self.time;
-
-        // NOTE: This will be wrong if logical_count changes, but that event is
-        //       so uncommon (it requires a complex VM setup) that we can afford
-        //       not to handle it in this particular measurement.
-        let overall = (timestamp - *old_timestamp) * self.logical_count as u32;
-        let overall_secs = overall.as_secs_f64();
-        let to_frac = |time: Time| -> f32 {
-            let time_secs = time.get::<second>();
-            (time_secs / overall_secs) as f32
-        };
-        let guest_sub = |t1: Option<Time>, t2: Option<Time>| -> Option<Time> {
-            match (t1, t2) {
-                (Some(t1), Some(t2)) => Some(t1 - t2),
-                (None, None) => None,
-                _ => unreachable!(),
-            }
-        };
+        if logical_count != self.logical_count {
+            return Ok(Err(CoreCountChanged {
+                initial: self.logical_count,
+                current: logical_count,
+            }));
+        }
 
-        let result = DurationBreakdown {
-            overall,
-            user_frac: to_frac(time.user() - old_time.user()),
-            system_frac: to_frac(time.system() - old_time.system()),
-            idle_frac: to_frac(time.idle() - old_time.idle()),
-            #[cfg(target_os = "linux")]
-            linux_fracs: LinuxDurationFracs {
-                nice_frac: to_frac(time.nice() - old_time.nice()),
-                io_wait_frac: to_frac(time.io_wait() - old_time.io_wait()),
-                irq_frac: to_frac(time.irq() - old_time.irq()),
-                soft_irq_frac: to_frac(time.soft_irq() - old_time.soft_irq()),
-                steal_frac: to_frac(time.steal() - old_time.steal()),
-                guest_frac: guest_sub(time.guest(), old_time.guest()).map(to_frac),
-                guest_nice_frac: guest_sub(time.guest_nice(), old_time.guest_nice()).map(to_frac),
-            },
-        };
+        let result = duration_breakdown(&self.time, (&time, timestamp), self.logical_count);
 
         self.time = (time, timestamp);
+        Ok(Ok(result))
+    }
+
+    /// Report the change in the CPU timings of each logical core since the
+    /// last measurement
+    ///
+    /// Each breakdown is expressed as fractions of the time elapsed on that
+    /// core, which makes e.g. a single-threaded bottleneck stand out as one
+    /// core with near-zero idle time.
+    ///
+    /// A `CoreCountChanged` error is reported if the number of logical CPU
+    /// cores changed since monitoring started. This is only a sanity check,
+    /// such changes are meant to be caught by `time_change()`.
+    ///
+    pub async fn per_core_time_change(&mut self) -> heim::Result<Vec<DurationBreakdown>> {
+        let times = heim::cpu::times().try_collect::<Vec<_>>().await?;
+        let timestamp = Instant::now();
+        self.check_logical_count(times.len() as u64)?;
+
+        let result = self
+            .times
+            .iter()
+            .zip(&times)
+            .map(|(old_time, time)| duration_breakdown(old_time, (time, timestamp), 1))
+            .collect();
+
+        self.times = times
+            .into_iter()
+            .map(|time| (time, timestamp))
+            .collect::<Box<[_]>>();
         Ok(result)
     }
 
+    /// Report the raw cumulative CPU counters of the last measurements
+    ///
+    /// CPU times come from the last `time_change()` call, and statistics come
+    /// from the last `stats_change()` call (or from monitor creation).
+    ///
+    pub fn raw_counters(&self) -> RawCounters {
+        #[cfg(target_os = "linux")]
+        use heim::cpu::os::linux::CpuTimeExt;
+
+        let (time, _timestamp) = &self.time;
+        let (stats, _timestamp) = &self.stats;
+        RawCounters {
+            user: time.user(),
+            system: time.system(),
+            idle: time.idle(),
+            #[cfg(target_os = "linux")]
+            nice: time.nice(),
+            #[cfg(target_os = "linux")]
+            io_wait: time.io_wait(),
+            #[cfg(target_os = "linux")]
+            steal: time.steal(),
+            ctx_switches: stats.ctx_switches(),
+            interrupts: stats.interrupts(),
+        }
+    }
+
     /// Number of logical CPU cores (including e.g. hyperthreads)
     pub fn logical_count(&self) -> u64 {
         self.logical_count
     }
 
+    /// Check that the number of logical CPU cores did not change since
+    /// monitoring started, given its current value
+    fn check_logical_count(&self, current: u64) -> heim::Result<()> {
+        if current == self.logical_count {
+            Ok(())
+        } else {
+            let err = CoreCountChanged {
+                initial: self.logical_count,
+                current,
+            };
+            Err(io::Error::new(io::ErrorKind::Other, err).into())
+        }
+    }
+
     /// Report what we know about per-CPU frequency ranges
     ///
     /// If available, will report one entry per logical CPU cores.
@@ -273,18 +708,107 @@ impl Monitor {
         Some(&frequency_ranges[..])
     }
 
-    // TODO: CPU frequencies
-    //       (Must detect change in CPU core count & panic w/ clear error
-    //        should also assert that frequency range remains the same)
-    // TODO: Relative CPU frequencies, if available, 0 is min and 1 is max
-    //       (Based on frequency_ranges + frequencies)
-    // TODO: Elapsed per-CPU times (reuse time_change logic!)
-    //       (Must detect change in CPU core count & panic w/ clear error)
+    /// Measure the current frequency of each logical CPU core, if possible
+    ///
+    /// If per-core frequencies cannot be measured on this platform, None is
+    /// returned, and the static `frequency_range()` should be used instead.
+    ///
+    /// A `CoreCountChanged` error is reported if the number of logical CPU
+    /// cores changed since monitoring started. This is only a sanity check,
+    /// such changes are meant to be caught by `time_change()`.
+    ///
+    // TODO: Should also check that the frequency range remains the same
+    pub async fn current_frequencies(&self) -> heim::Result<Option<Box<[Frequency]>>> {
+        let frequencies = Host::current_frequencies().await?;
+        // Some hosts (e.g. VMs) do not expose any per-core frequency, which
+        // does not mean that they have no CPU cores
+        if let Some(frequencies) = frequencies.as_ref().filter(|freqs| !freqs.is_empty()) {
+            self.check_logical_count(frequencies.len() as u64)?;
+        }
+        Ok(frequencies.map(Vec::into_boxed_slice))
+    }
+
+    /// Report the lowest and highest current frequency across all logical
+    /// cores, if per-core frequencies are known
+    ///
+    /// A large spread reveals uneven boosting, or a single throttled core.
+    ///
+    pub async fn current_frequency_spread(&self) -> heim::Result<Option<FrequencySpread>> {
+        let frequencies = self.current_frequencies().await?;
+        Ok(frequencies.and_then(|frequencies| {
+            let min = frequencies.iter().min()?;
+            let max = frequencies.iter().max()?;
+            Some(FrequencySpread {
+                min: *min,
+                max: *max,
+            })
+        }))
+    }
+
+    /// Measure the current frequency of each logical CPU core relative to its
+    /// frequency range, where 0.0 is the minimal frequency and 1.0 is the
+    /// maximal frequency, if per-core frequencies can be measured
+    ///
+    /// The relative frequency of a core is unknown if its frequency range is
+    /// unknown or degenerate (minimum >= maximum). The global frequency range
+    /// is used for cores whose own frequency range is not known.
+    ///
+    pub async fn relative_frequencies(&self) -> heim::Result<Option<Box<[Option<f32>]>>> {
+        let frequencies = match self.current_frequencies().await? {
+            Some(frequencies) => frequencies,
+            None => return Ok(None),
+        };
+        let relative_frequency = |(core, freq): (usize, &Frequency)| {
+            let range = self
+                .frequency_ranges()
+                .and_then(|ranges| ranges.get(core))
+                .unwrap_or(&self.frequency_range);
+            let min = range.min?.get::<megahertz>() as f32;
+            let max = range.max?.get::<megahertz>() as f32;
+            if min >= max {
+                return None;
+            }
+            Some((freq.get::<megahertz>() as f32 - min) / (max - min))
+        };
+        Ok(Some(
+            frequencies
+                .iter()
+                .enumerate()
+                .map(relative_frequency)
+                .collect(),
+        ))
+    }
+
+    /// Measure the lowest current frequency of a logical CPU core relative to
+    /// its frequency range (see `relative_frequencies()`), if it is known for
+    /// at least one core
+    ///
+    /// This tells at a glance whether some cores are throttling, whatever the
+    /// absolute frequencies of the host's CPU are.
+    ///
+    pub async fn lowest_relative_frequency(&self) -> heim::Result<Option<f32>> {
+        let frequencies = self.relative_frequencies().await?;
+        Ok(frequencies.and_then(|frequencies| {
+            frequencies.iter().flatten().fold(None, |lowest, &freq| {
+                Some(lowest.map_or(freq, |lowest: f32| lowest.min(freq)))
+            })
+        }))
+    }
 
     /// Number of physical CPU cores, if known
     pub fn physical_count(&self) -> Option<u64> {
         self.physical_count
     }
+
+    /// Number of CPU sockets (aka packages), if known
+    pub fn socket_count(&self) -> Option<u64> {
+        self.socket_count
+    }
+}
+
+/// Count the host's CPU sockets (aka packages), if possible
+pub fn socket_count() -> Option<u64> {
+    Host::socket_count()
 }
 
 /// Report on the host's CPU configuration
@@ -295,12 +819,34 @@ pub fn startup_report(
     log: &Logger,
     logical_cpus: u64,
     physical_cpus: Option<u64>,
+    cpu_sockets: Option<u64>,
     global_cpu_freq: CpuFrequency,
     per_cpu_freqs: Option<Vec<CpuFrequency>>,
 ) {
+    let display_count = |count: Option<u64>| -> Cow<str> {
+        match count {
+            Some(count) => count.to_string().into(),
+            None => "unknown".into(),
+        }
+    };
+    let physical_cpus_per_socket = match (physical_cpus, cpu_sockets) {
+        (Some(physical_cpus), Some(cpu_sockets)) => Some(physical_cpus / cpu_sockets),
+        _ => None,
+    };
     info!(log, "Received CPU configuration information";
           "logical CPU count" => logical_cpus,
-          "physical CPU count" => physical_cpus);
+          "physical CPU count" => physical_cpus,
+          "CPU socket count" => %display_count(cpu_sockets),
+          "physical CPUs per socket" => %display_count(physical_cpus_per_socket));
+
+    // Multi-socket systems have non-uniform memory and cache access costs, so
+    // benchmarks which span multiple sockets may behave unexpectedly.
+    if cpu_sockets.unwrap_or(1) > 1 {
+        warn!(log, "Found multiple CPU sockets, make sure that your benchmarks \
+                    only span multiple sockets on purpose (e.g. by pinning \
+                    their threads)!";
+              "warning" => BenchmonWarning::MultipleSockets);
+    }
 
     let log_freq_range = |freq: &CpuFrequency, cpu_name: &str| {
         if let (Some(min), Some(max)) = (freq.min(), freq.max()) {
@@ -312,7 +858,8 @@ pub fn startup_report(
             warn!(log, "Some CPU frequency range data is missing";
                   "min frequency" => ?freq.min(),
                   "max frequency" => ?freq.max(),
-                  "cpu" => cpu_name);
+                  "cpu" => cpu_name,
+                  "warning" => BenchmonWarning::CpuFrequencyUnknown);
         }
     };
 
@@ -355,3 +902,138 @@ pub fn startup_report(
         log_freq_range(&global_cpu_freq, "all");
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Synthetic CPU time counters, in seconds
+    #[derive(Clone, Copy, Default)]
+    struct FakeCpuTime {
+        user: f64,
+        system: f64,
+        idle: f64,
+    }
+
+    impl CpuTimeCounters for FakeCpuTime {
+        fn user(&self) -> Time {
+            Time::new::<second>(self.user)
+        }
+
+        fn system(&self) -> Time {
+            Time::new::<second>(self.system)
+        }
+
+        fn idle(&self) -> Time {
+            Time::new::<second>(self.idle)
+        }
+
+        #[cfg(target_os = "linux")]
+        fn nice(&self) -> Time {
+            Time::new::<second>(0.0)
+        }
+
+        #[cfg(target_os = "linux")]
+        fn io_wait(&self) -> Time {
+            Time::new::<second>(0.0)
+        }
+
+        #[cfg(target_os = "linux")]
+        fn irq(&self) -> Time {
+            Time::new::<second>(0.0)
+        }
+
+        #[cfg(target_os = "linux")]
+        fn soft_irq(&self) -> Time {
+            Time::new::<second>(0.0)
+        }
+
+        #[cfg(target_os = "linux")]
+        fn steal(&self) -> Time {
+            Time::new::<second>(0.0)
+        }
+
+        #[cfg(target_os = "linux")]
+        fn guest(&self) -> Option<Time> {
+            None
+        }
+
+        #[cfg(target_os = "linux")]
+        fn guest_nice(&self) -> Option<Time> {
+            None
+        }
+    }
+
+    #[test]
+    fn implausible_cpu_time() {
+        // Two cores, and counters which claim that 3s of CPU time were spent
+        // within 1s of wall-clock time, as if the core count had doubled
+        let start = Instant::now();
+        let old_time = FakeCpuTime::default();
+        let time = FakeCpuTime {
+            user: 2.0,
+            system: 0.5,
+            idle: 0.5,
+        };
+        let mut breakdown = duration_breakdown(
+            &(old_time, start),
+            (&time, start + Duration::from_secs(1)),
+            2,
+        );
+        assert!((breakdown.total_frac() - 1.5).abs() < 1e-6);
+
+        let total = breakdown.fix_implausible_total();
+        assert!((total.unwrap() - 1.5).abs() < 1e-6);
+        assert!((breakdown.total_frac() - 1.0).abs() < 1e-6);
+        assert!((breakdown.user_frac - 2.0 / 3.0).abs() < 1e-6);
+
+        // Plausible breakdowns are left alone
+        assert_eq!(breakdown.fix_implausible_total(), None);
+    }
+
+    #[test]
+    fn back_to_back_measurements() {
+        // Two measurements taken at the same instant, or almost, during which
+        // the counters still advanced a little
+        let start = Instant::now();
+        let old_time = FakeCpuTime::default();
+        let time = FakeCpuTime {
+            user: 1e-9,
+            system: 0.0,
+            idle: 1e-9,
+        };
+        for &elapsed in &[Duration::default(), Duration::from_nanos(100)] {
+            let breakdown = duration_breakdown(&(old_time, start), (&time, start + elapsed), 4);
+            assert_eq!(breakdown.overall, elapsed * 4);
+            assert_eq!(breakdown.user_frac, 0.0);
+            assert_eq!(breakdown.system_frac, 0.0);
+            assert_eq!(breakdown.idle_frac, 0.0);
+            #[cfg(target_os = "linux")]
+            {
+                let linux_fracs = &breakdown.linux_fracs;
+                assert_eq!(linux_fracs.nice_frac, 0.0);
+                assert_eq!(linux_fracs.io_wait_frac, 0.0);
+                assert_eq!(linux_fracs.steal_frac, 0.0);
+            }
+            assert_eq!(breakdown.total_frac(), 0.0);
+        }
+    }
+
+    #[test]
+    fn busiest_core() {
+        let start = Instant::now();
+        let end = start + Duration::from_secs(1);
+        let core = |user, idle| {
+            let time = FakeCpuTime {
+                user,
+                system: 0.0,
+                idle,
+            };
+            duration_breakdown(&(FakeCpuTime::default(), start), (&time, end), 1)
+        };
+        let per_core = [core(0.25, 0.75), core(0.9, 0.1), core(0.0, 1.0)];
+        assert!((per_core[1].busy_frac() - 0.9).abs() < 1e-6);
+        assert!((balance::busiest_core(&per_core).unwrap() - 0.9).abs() < 1e-6);
+        assert_eq!(balance::busiest_core(&[]), None);
+    }
+}
@@ -0,0 +1,76 @@
+//! Disk I/O monitoring
+
+use crate::{
+    platform::{Host, Platform},
+    totals::{ByteCounters, ByteTotals},
+};
+
+use futures_util::stream::TryStreamExt;
+
+use heim::{
+    disk::IoCounters,
+    units::{information::byte, Information},
+};
+
+use std::collections::HashMap;
+
+/// Disk I/O monitoring mechanism
+///
+/// Only the I/O of physical disks is accounted for, as the I/O of partitions
+/// and virtual block devices (e.g. loop or device-mapper devices) is also
+/// accounted for by the disks which back them. On OSes where physical disks
+/// cannot be told apart, every block device is accounted for.
+///
+pub struct Monitor {
+    /// Bytes read and written by physical disks since monitoring started
+    traffic: ByteTotals,
+}
+
+impl Monitor {
+    /// Set up disk I/O monitoring
+    pub async fn new() -> heim::Result<Self> {
+        Ok(Self {
+            traffic: ByteTotals::new(Self::read_counters().await?),
+        })
+    }
+
+    /// Measure the disk I/O counters, updating the totals
+    pub async fn update(&mut self) -> heim::Result<()> {
+        self.traffic.update(Self::read_counters().await?);
+        Ok(())
+    }
+
+    /// Measure the total number of bytes read and written by physical disks
+    /// since monitoring started, as of the last measurement
+    ///
+    /// I/O that was counted before a counter reset, or before a disk was
+    /// removed, is kept (see the `totals` module).
+    ///
+    pub fn totals(&self) -> (Information, Information) {
+        let (read, written) = self.traffic.totals();
+        (
+            Information::new::<byte>(read),
+            Information::new::<byte>(written),
+        )
+    }
+
+    /// Read the bytes read and written by each physical disk
+    async fn read_counters() -> heim::Result<HashMap<String, ByteCounters>> {
+        heim::disk::io_counters()
+            .map_ok(|counters: IoCounters| {
+                (
+                    counters.device_name().to_string_lossy().into_owned(),
+                    (
+                        counters.read_bytes().get::<byte>(),
+                        counters.write_bytes().get::<byte>(),
+                    ),
+                )
+            })
+            .try_filter(|(device, _counters)| {
+                let is_physical = Host::is_physical_disk(device).unwrap_or(true);
+                futures_util::future::ready(is_physical)
+            })
+            .try_collect()
+            .await
+    }
+}
@@ -1,26 +1,223 @@
-use crate::format;
+use crate::{
+    format,
+    platform::{Host, Platform},
+    warning::BenchmonWarning,
+};
 
 use heim::{
     disk::{Partition, Usage},
-    units::information::byte,
+    units::{information::byte, Information},
 };
 
-use slog::{debug, info, Logger};
+use slog::{debug, info, warn, Logger};
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::Path,
+};
+
+/// Request queue settings of a physical block device
+pub struct BlockQueue {
+    /// Kernel name of the block device (e.g. "sda" or "nvme0n1")
+    pub name: String,
+
+    /// Active I/O scheduler (e.g. "mq-deadline", "bfq" or "none"), if known
+    pub scheduler: Option<String>,
+
+    /// Maximal number of queued requests, if known
+    pub nr_requests: Option<u64>,
+
+    /// Truth that the device is rotational (i.e. a HDD), if known
+    pub rotational: Option<bool>,
+
+    /// Amount of data which is read ahead of sequential reads, if known
+    pub read_ahead: Option<Information>,
+}
 
-use std::collections::{BTreeMap, BTreeSet};
+/// Pseudo/virtual filesystem types, which are not backed by a storage device
+const VIRTUAL_FILESYSTEMS: &[&str] = &[
+    "autofs",
+    "binfmt_misc",
+    "bpf",
+    "cgroup",
+    "cgroup2",
+    "configfs",
+    "debugfs",
+    "devfs",
+    "devpts",
+    "devtmpfs",
+    "efivarfs",
+    "fusectl",
+    "hugetlbfs",
+    "mqueue",
+    "nsfs",
+    "overlay",
+    "proc",
+    "pstore",
+    "ramfs",
+    "rpc_pipefs",
+    "securityfs",
+    "sysfs",
+    "tmpfs",
+    "tracefs",
+];
+
+/// Copy-on-write filesystem types, which never overwrite data in place
+const COPY_ON_WRITE_FILESYSTEMS: &[&str] = &["apfs", "bcachefs", "btrfs", "zfs"];
+
+/// Journaling filesystem types, which write metadata (and possibly data) twice
+const JOURNALING_FILESYSTEMS: &[&str] = &["ext3", "ext4", "jfs", "ntfs", "reiserfs", "xfs"];
+
+/// Mount options which affect storage benchmark results
+const RELEVANT_MOUNT_OPTIONS: &[&str] = &[
+    "async",
+    "barrier",
+    "nobarrier",
+    "commit",
+    "compress",
+    "data",
+    "dax",
+    "discard",
+    "nodiscard",
+    "noatime",
+    "relatime",
+    "strictatime",
+    "nodatacow",
+    "ssd",
+    "sync",
+];
+
+/// Truth that a filesystem mount is not backed by a storage device
+fn is_virtual(partition: &Partition) -> bool {
+    // Check the known list of pseudo/virtual filesystems first
+    if VIRTUAL_FILESYSTEMS.contains(&partition.file_system().as_str()) {
+        return true;
+    }
+
+    // Otherwise, consider that a mount is backed by a storage device if its
+    // device name is a device path or designates a network share (e.g.
+    // "server:/export" for NFS or "//server/share" for SMB)
+    match partition.device() {
+        Some(device) => {
+            let device = device.to_string_lossy();
+            !(device.starts_with("/dev/") || device.contains(':') || device.starts_with("//"))
+        }
+        None => true,
+    }
+}
+
+/// Report on the configuration of the filesystem which backs a path that a
+/// storage benchmark is going to access
+fn watched_path_report(
+    log: &Logger,
+    path: &Path,
+    disk_partitions_and_usage: &[(Partition, heim::Result<Usage>)],
+) {
+    // Resolve symlinks, then find the innermost mount which contains the path
+    let path = match path.canonicalize() {
+        Ok(path) => path,
+        Err(err) => {
+            warn!(log, "Failed to resolve the watched path";
+                  "path" => %path.display(),
+                  "error" => %err,
+                  "warning" => BenchmonWarning::ProbeFailed);
+            return;
+        }
+    };
+    let partition = disk_partitions_and_usage
+        .iter()
+        .map(|(partition, _usage)| partition)
+        .filter(|partition| path.starts_with(partition.mount_point()))
+        .max_by_key(|partition| partition.mount_point().components().count());
+    let partition = match partition {
+        Some(partition) => partition,
+        None => {
+            warn!(log, "Failed to find the filesystem of the watched path";
+                  "path" => %path.display(),
+                  "warning" => BenchmonWarning::ProbeFailed);
+            return;
+        }
+    };
+
+    // Report the filesystem type and the mount options that matter
+    let file_system = partition.file_system().as_str();
+    let copy_on_write = COPY_ON_WRITE_FILESYSTEMS.contains(&file_system);
+    let journaled = JOURNALING_FILESYSTEMS.contains(&file_system);
+    let mount_options = Host::mount_options(partition.mount_point());
+    let relevant_options = mount_options.as_ref().ok().map(|options| {
+        options
+            .iter()
+            .filter(|option| {
+                let name = option.split('=').next().unwrap_or_default();
+                RELEVANT_MOUNT_OPTIONS.contains(&name)
+            })
+            .cloned()
+            .collect::<Vec<_>>()
+    });
+    info!(log, "Found the filesystem of the watched path";
+          "path" => %path.display(),
+          "mount point" => %partition.mount_point().display(),
+          "file system" => file_system,
+          "copy-on-write" => copy_on_write,
+          "journaled" => journaled,
+          "mount options" => ?relevant_options);
+
+    // Reading files updates their access time, unless told otherwise, which
+    // turns read-heavy benchmarks into partially write-heavy ones
+    match mount_options {
+        Ok(options) => {
+            if !options.iter().any(|option| option == "noatime") {
+                let atime_mode = if options.iter().any(|option| option == "relatime") {
+                    "relatime"
+                } else {
+                    "strictatime"
+                };
+                warn!(log, "Access time updates are enabled on the watched path, \
+                            reads may cause metadata writes (consider noatime)";
+                      "path" => %path.display(),
+                      "atime mode" => atime_mode,
+                      "warning" => BenchmonWarning::AtimeUpdates);
+            }
+        }
+        Err(err) => {
+            debug!(log, "Mount options are unavailable";
+                   "mount point" => %partition.mount_point().display(),
+                   "error" => %err);
+        }
+    }
+}
 
 /// Report on the host's file system configuration
+///
+/// Pseudo/virtual filesystems (procfs, tmpfs...) are only reported in detail
+/// if `show_virtual` is true, otherwise they are only counted. If a
+/// `watched_path` is specified, the configuration of the filesystem that
+/// backs it is reported in more detail.
+///
 pub fn startup_report(
     log: &Logger,
     disk_partitions_and_usage: Vec<(Partition, heim::Result<Usage>)>,
+    show_virtual: bool,
+    watched_path: Option<&Path>,
 ) {
+    if let Some(watched_path) = watched_path {
+        watched_path_report(log, watched_path, &disk_partitions_and_usage);
+    }
+
     // The OS APIs give us a list of filesystem mounts (at least on Unix), but
     // as performance engineers what we're really interested in are the physical
     // devices that back these mount points. Let's try to reverse-engineer that
     // information from mount properties...
     debug!(log, "Processing filesystem mount list...");
     let mut dev_to_mounts = BTreeMap::<_, BTreeSet<_>>::new();
+    let mut num_hidden_mounts = 0;
     for (partition, usage) in disk_partitions_and_usage {
+        // Hide virtual filesystems unless asked to show them
+        if !show_virtual && is_virtual(&partition) {
+            num_hidden_mounts += 1;
+            continue;
+        }
+
         // Disk capacity and disk usage will be used (if available) as a
         // last-resort disambiguation key for mounts with identical device name
         // and size (e.g. unrelated tmpfs mounts on Linux).
@@ -58,12 +255,70 @@ pub fn startup_report(
         assert!(insert_result, "Observed the same mount point twice!");
     }
 
-    // Display the deduplicated filesystem-backing devices, with their mounts
+    // Display the deduplicated filesystem-backing devices, with their mounts,
+    // while looking up the physical block devices behind them
+    let mut physical_devices = BTreeMap::new();
     for ((device, capacity, file_system, _used_bytes), mount_list) in dev_to_mounts {
+        if device.starts_with("/dev/") {
+            match Host::block_queue(Path::new(&device)) {
+                Ok(queue) => {
+                    physical_devices.entry(queue.name.clone()).or_insert(queue);
+                }
+                Err(err) => {
+                    debug!(log, "Block device information is unavailable";
+                           "device name" => &device,
+                           "error" => %err);
+                }
+            }
+        }
         info!(log, "Found a mounted device";
               "device name" => device,
               "capacity" => capacity,
               "file system" => file_system,
               "mount point(s)" => ?mount_list);
     }
+
+    // Display the settings of the physical block devices, which affect the
+    // performance of storage benchmarks, once per device
+    for (name, queue) in physical_devices {
+        let read_ahead = queue
+            .read_ahead
+            .map(|read_ahead| format!("{}", format::display_information(read_ahead)));
+        info!(log, "Found a physical storage device";
+              "device name" => &name,
+              "I/O scheduler" => &queue.scheduler,
+              "queue depth" => queue.nr_requests,
+              "read-ahead" => read_ahead,
+              "rotational" => queue.rotational);
+        if queue.rotational == Some(true) {
+            warn!(log, "A filesystem is stored on a rotational device (HDD), \
+                        expect slow and highly variable I/O performance";
+                  "device name" => name,
+                  "warning" => BenchmonWarning::RotationalStorage);
+        }
+    }
+    if num_hidden_mounts > 0 {
+        info!(log, "Skipped virtual filesystem mounts (use --show-virtual-fs \
+                    to display them)";
+              "mount count" => num_hidden_mounts);
+    }
+}
+
+/// List the host's filesystem mounts, as tab-separated device names and mount
+/// points, sorted by device name
+pub fn list(disk_partitions: Vec<Partition>) {
+    let dev_and_mounts = disk_partitions
+        .into_iter()
+        .map(|partition| {
+            let device = if let Some(device) = partition.device() {
+                device.to_string_lossy().into_owned()
+            } else {
+                "none".to_owned()
+            };
+            (device, partition.mount_point().to_owned())
+        })
+        .collect::<BTreeSet<_>>();
+    for (device, mount_point) in dev_and_mounts {
+        println!("{}\t{}", device, mount_point.display());
+    }
 }
@@ -0,0 +1,110 @@
+//! Fingerprint of the host's hardware and OS configuration
+//!
+//! To quickly tell whether two benchmark runs happened on identically
+//! configured machines, benchmon computes a short hash of the machine's key
+//! characteristics, which only changes when one of them changes.
+
+use heim::units::{frequency::megahertz, information::byte, Frequency, Information};
+
+use slog::{debug, info, Logger};
+
+use std::fmt::Display;
+
+/// Version of the fingerprinted field set and of its normalization
+///
+/// Fingerprints are reproducible across benchmon versions as long as this
+/// version does not change. It must be bumped whenever `MachineSpec`'s fields
+/// or their normalization (see `MachineSpec::normalized()`) change.
+///
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Key characteristics of the host's hardware and OS configuration
+pub struct MachineSpec {
+    /// CPU model name, if known
+    pub cpu_model: Option<String>,
+
+    /// Number of logical CPU cores
+    pub logical_cpus: u64,
+
+    /// Number of physical CPU cores, if known
+    pub physical_cpus: Option<u64>,
+
+    /// Number of CPU sockets, if known
+    pub cpu_sockets: Option<u64>,
+
+    /// Minimal CPU frequency, if known
+    pub min_cpu_freq: Option<Frequency>,
+
+    /// Maximal CPU frequency, if known
+    pub max_cpu_freq: Option<Frequency>,
+
+    /// Total amount of RAM
+    pub ram_size: Information,
+
+    /// OS kernel release, if known
+    pub kernel_release: Option<String>,
+}
+
+impl MachineSpec {
+    /// Normalized textual description of the machine characteristics
+    ///
+    /// This is one "key=value" line per field, in declaration order, where
+    /// unknown values are spelled "unknown", frequencies are in integral MHz
+    /// and the RAM size is in bytes.
+    ///
+    fn normalized(&self) -> String {
+        fn or_unknown(value: Option<impl Display>) -> String {
+            match value {
+                Some(value) => value.to_string(),
+                None => "unknown".to_owned(),
+            }
+        }
+        let mhz = |freq: Option<Frequency>| freq.map(|freq| freq.get::<megahertz>());
+
+        format!(
+            "cpu_model={}\n\
+             logical_cpus={}\n\
+             physical_cpus={}\n\
+             cpu_sockets={}\n\
+             min_cpu_freq_mhz={}\n\
+             max_cpu_freq_mhz={}\n\
+             ram_bytes={}\n\
+             kernel_release={}\n",
+            or_unknown(self.cpu_model.as_ref().map(|model| model.trim())),
+            self.logical_cpus,
+            or_unknown(self.physical_cpus),
+            or_unknown(self.cpu_sockets),
+            or_unknown(mhz(self.min_cpu_freq)),
+            or_unknown(mhz(self.max_cpu_freq)),
+            self.ram_size.get::<byte>(),
+            or_unknown(self.kernel_release.as_ref()),
+        )
+    }
+
+    /// Compute the fingerprint of these machine characteristics
+    ///
+    /// This is the 64-bit FNV-1a hash of the normalized description, in
+    /// hexadecimal. It is not cryptographically secure, but it is stable and
+    /// only meant to detect accidental configuration changes.
+    ///
+    pub fn fingerprint(&self) -> String {
+        const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+        const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+        let hash = self
+            .normalized()
+            .bytes()
+            .fold(FNV_OFFSET_BASIS, |hash, byte| {
+                (hash ^ u64::from(byte)).wrapping_mul(FNV_PRIME)
+            });
+        format!("{:016x}", hash)
+    }
+}
+
+/// Report the fingerprint of the host's configuration
+pub fn startup_report(log: &Logger, spec: &MachineSpec) {
+    debug!(log, "Fingerprinting machine configuration";
+           "normalized description" => spec.normalized());
+    info!(log, "Computed machine configuration fingerprint";
+          "fingerprint" => spec.fingerprint(),
+          "schema version" => SCHEMA_VERSION);
+}
@@ -1,6 +1,6 @@
 use heim::units::{information::byte, Information};
 
-use std::fmt;
+use std::{borrow::Cow, fmt, str::FromStr};
 
 use unicode_segmentation::UnicodeSegmentation;
 
@@ -20,19 +20,480 @@ where
     }
 }
 
-/// Display the header of a column of measurements
-pub fn display_col_header(text: &str, width: usize) -> impl fmt::Display + '_ {
-    DelayedDisplay(move |dest| write!(dest, "{0:─^1$}", text, width))
+/// Apply the user-specified maximal column width, if any, to the natural width
+/// of a column of measurements
+pub fn cap_col_width(width: usize, max_width: Option<usize>) -> usize {
+    match max_width {
+        // A column must be at least wide enough for a truncation ellipsis
+        Some(max_width) => width.min(max_width.max(1)),
+        None => width,
+    }
+}
+
+/// Characters which are used to draw the borders of tabular output
+#[derive(Clone, Copy)]
+pub struct TableStyle {
+    /// Character which surrounds column titles in the table header
+    pub header_fill: char,
+
+    /// Separator between two columns of the table header
+    pub header_separator: char,
+
+    /// Separator between two columns of measurements
+    pub data_separator: char,
+
+    /// Characters which are used to draw horizontal bar charts, from an empty
+    /// character cell to a full one, with partially filled cells in between
+    pub bar_blocks: &'static [char],
+
+    /// Characters which are used to draw histograms, from an empty column to
+    /// a full one, with partially filled columns in between
+    pub histogram_blocks: &'static [char],
+}
+
+impl TableStyle {
+    /// Unicode box-drawing characters, which look best on modern terminals
+    pub const UNICODE: Self = Self {
+        header_fill: '─',
+        header_separator: '┼',
+        data_separator: '│',
+        bar_blocks: &[' ', '▏', '▎', '▍', '▌', '▋', '▊', '▉', '█'],
+        histogram_blocks: &[' ', '▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'],
+    };
+
+    /// ASCII equivalents, for terminals and log viewers that cannot render
+    /// box-drawing characters
+    pub const ASCII: Self = Self {
+        header_fill: '-',
+        header_separator: '+',
+        data_separator: '|',
+        bar_blocks: &[' ', '#'],
+        histogram_blocks: &[' ', '.', ':', '#'],
+    };
 }
 
-pub const COL_HEADER_SEPARATOR: char = '┼';
+impl Default for TableStyle {
+    fn default() -> Self {
+        Self::UNICODE
+    }
+}
+
+/// Display the header of a column of measurements, centered and surrounded
+/// by a fill character
+pub fn display_col_header(text: &str, width: usize, fill: char) -> impl fmt::Display + '_ {
+    DelayedDisplay(move |dest| {
+        // Like display_col_data, we must measure grapheme clusters ourselves
+        let text = truncate(text, width);
+        let padding = width.saturating_sub(str_width(&text));
+        let left_padding = padding / 2;
+        for _ in 0..left_padding {
+            write!(dest, "{}", fill)?;
+        }
+        write!(dest, "{}", text)?;
+        for _ in left_padding..padding {
+            write!(dest, "{}", fill)?;
+        }
+        Ok(())
+    })
+}
 
 /// Display a measurement within a column
 pub fn display_col_data(data: impl fmt::Display, width: usize) -> impl fmt::Display {
-    DelayedDisplay(move |dest| write!(dest, "{0:1$}", data, width))
+    DelayedDisplay(move |dest| {
+        // The padding of std::fmt counts chars, but our column widths are
+        // measured in grapheme clusters, so we must pad the data ourselves.
+        let data = data.to_string();
+        let data = truncate(&data, width);
+        let padding = width.saturating_sub(str_width(&data));
+        write!(dest, "{}{:2$}", data, "", padding)
+    })
+}
+
+/// Display a horizontal bar which is `width` characters wide when full, and
+/// filled in proportion to `fraction` (which is clamped between 0.0 and 1.0)
+pub fn display_bar(fraction: f64, width: usize, style: TableStyle) -> impl fmt::Display {
+    DelayedDisplay(move |dest| {
+        let blocks = style.bar_blocks;
+        let levels = blocks.len() - 1;
+        let fraction = if fraction.is_nan() {
+            0.0
+        } else {
+            fraction.max(0.0).min(1.0)
+        };
+        let filled = (fraction * (width * levels) as f64).round() as usize;
+        for cell in 0..width {
+            let cell_level = filled.saturating_sub(cell * levels).min(levels);
+            write!(dest, "{}", blocks[cell_level])?;
+        }
+        Ok(())
+    })
+}
+
+/// Display a histogram as one column of characters per bin, whose height is
+/// proportional to the bin's count (nonempty bins are always visible)
+pub fn display_histogram(counts: &[u64], style: TableStyle) -> impl fmt::Display + '_ {
+    DelayedDisplay(move |dest| {
+        let blocks = style.histogram_blocks;
+        let levels = blocks.len() - 1;
+        let max_count = counts.iter().copied().max().unwrap_or(0).max(1);
+        for &count in counts {
+            let level = (count as f64 / max_count as f64 * levels as f64).round() as usize;
+            let level = if count > 0 { level.max(1) } else { 0 };
+            write!(dest, "{}", blocks[level])?;
+        }
+        Ok(())
+    })
+}
+
+/// Display a sequence of levels between 0.0 and 1.0 as columns of characters
+/// of proportional height, with blank columns for unknown levels
+pub fn display_levels(levels: &[Option<f64>], style: TableStyle) -> impl fmt::Display + '_ {
+    DelayedDisplay(move |dest| {
+        let blocks = style.histogram_blocks;
+        let max_level = blocks.len() - 1;
+        for level in levels {
+            // Known levels are always visible, even if they are zero
+            let block = match level {
+                Some(level) => {
+                    let level = level.max(0.0).min(1.0);
+                    ((level * max_level as f64).round() as usize).max(1)
+                }
+                None => 0,
+            };
+            write!(dest, "{}", blocks[block])?;
+        }
+        Ok(())
+    })
+}
+
+/// Display a right-aligned placeholder within a column
+pub fn display_col_placeholder(placeholder: &'static str, width: usize) -> impl fmt::Display {
+    let placeholder = DelayedDisplay(move |dest| write!(dest, "{:>1$}", placeholder, width));
+    display_col_data(placeholder, width)
+}
+
+/// Placeholder for measurements which failed in a given sample
+const ERROR_PLACEHOLDER: &str = "ERR";
+
+/// Rounding mode of displayed decimal numbers
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum RoundingMode {
+    /// Discard the extra digits, like most system monitors do
+    Truncate,
+
+    /// Round to the nearest number, with ties going to the even last digit
+    HalfEven,
+}
+
+impl Default for RoundingMode {
+    fn default() -> Self {
+        RoundingMode::HalfEven
+    }
+}
+
+impl FromStr for RoundingMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "truncate" => Ok(RoundingMode::Truncate),
+            "round" => Ok(RoundingMode::HalfEven),
+            _ => Err(format!("Unknown rounding mode \"{}\"", s)),
+        }
+    }
+}
+
+/// Round a number to a certain amount of decimal places
+///
+/// The result should be displayed with the same amount of decimal places, so
+/// that std::fmt's own rounding does not come into play.
+///
+/// Ties are detected on the exact binary value of the input, so a number like
+/// 49.95, which is not exactly representable as a float, is not a tie.
+///
+pub fn round_decimals(value: f32, decimals: i32, mode: RoundingMode) -> f32 {
+    let scale = 10f64.powi(decimals);
+    let scaled = f64::from(value) * scale;
+    let rounded = match mode {
+        RoundingMode::Truncate => scaled.trunc(),
+        RoundingMode::HalfEven => {
+            let nearest = scaled.round();
+            if (scaled - scaled.trunc()).abs() == 0.5 && nearest % 2.0 != 0.0 {
+                nearest - scaled.signum()
+            } else {
+                nearest
+            }
+        }
+    };
+    (rounded / scale) as f32
+}
+
+/// Percentage column formatting
+pub struct PercentageFormatter {
+    /// Title of the column in tabular output
+    title: &'static str,
+
+    /// Width of the column in grapheme clusters
+    width: usize,
+
+    /// Rounding mode of the displayed percentages
+    rounding: RoundingMode,
+}
+
+impl PercentageFormatter {
+    /// Maximal width of a percentage in the usual 0-100% range ("100.0%")
+    const MAX_DATA_WIDTH: usize = 6;
+
+    /// Set up a percentage column with a certain title, optional maximal
+    /// width (see `cap_col_width()`) and rounding mode
+    pub fn new(title: &'static str, max_width: Option<usize>, rounding: RoundingMode) -> Self {
+        Self {
+            title,
+            width: cap_col_width(str_width(title).max(Self::MAX_DATA_WIDTH), max_width),
+            rounding,
+        }
+    }
+
+    /// Display the title of the column, surrounded by a fill character
+    pub fn display_title(&self, fill: char) -> impl fmt::Display + '_ {
+        display_col_header(self.title, self.width, fill)
+    }
+
+    /// Display a fraction within the column, as a percentage
+    pub fn display_data(&self, fraction: f32) -> impl fmt::Display {
+        let number_width = self.width - 1;
+        let percentage = round_decimals(fraction * 100.0, 1, self.rounding);
+        let percentage =
+            DelayedDisplay(move |dest| write!(dest, "{:>1$.1}%", percentage, number_width));
+        display_col_data(percentage, self.width)
+    }
+
+    /// Display a placeholder within the column, for data which is unknown
+    pub fn display_unavailable(&self) -> impl fmt::Display {
+        display_col_placeholder("--", self.width)
+    }
+
+    /// Display a placeholder within the column, for a failed measurement
+    pub fn display_error(&self) -> impl fmt::Display {
+        display_col_placeholder(ERROR_PLACEHOLDER, self.width)
+    }
+}
+
+/// Integer column formatting
+pub struct IntegerFormatter {
+    /// Title of the column in tabular output
+    title: &'static str,
+
+    /// Width of the column in grapheme clusters
+    width: usize,
+}
+
+impl IntegerFormatter {
+    /// Width which is reserved for integers even if the title is shorter
+    const MIN_DATA_WIDTH: usize = 6;
+
+    /// Set up an integer column with a certain title and optional maximal
+    /// width (see `cap_col_width()`)
+    pub fn new(title: &'static str, max_width: Option<usize>) -> Self {
+        Self {
+            title,
+            width: cap_col_width(str_width(title).max(Self::MIN_DATA_WIDTH), max_width),
+        }
+    }
+
+    /// Display the title of the column, surrounded by a fill character
+    pub fn display_title(&self, fill: char) -> impl fmt::Display + '_ {
+        display_col_header(self.title, self.width, fill)
+    }
+
+    /// Display an integer within the column, or an empty cell if unknown
+    pub fn display_data(&self, value: Option<u64>) -> impl fmt::Display {
+        let width = self.width;
+        let integer = DelayedDisplay(move |dest| match value {
+            Some(value) => write!(dest, "{:>1$}", value, width),
+            None => Ok(()),
+        });
+        display_col_data(integer, width)
+    }
+
+    /// Display a placeholder within the column, for data which the platform
+    /// does not provide
+    pub fn display_unavailable(&self) -> impl fmt::Display {
+        display_col_placeholder("--", self.width)
+    }
+
+    /// Display a placeholder within the column, for a failed measurement
+    pub fn display_error(&self) -> impl fmt::Display {
+        display_col_placeholder(ERROR_PLACEHOLDER, self.width)
+    }
+}
+
+/// Formatter for columns of quantities of information (e.g. the bytes that were
+/// transferred since monitoring started), which are displayed with an SI unit
+pub struct InformationFormatter {
+    /// Title of the column in tabular output
+    title: &'static str,
+
+    /// Width of the column in grapheme clusters
+    width: usize,
 }
 
-pub const COL_DATA_SEPARATOR: char = '│';
+impl InformationFormatter {
+    /// Width which is reserved for quantities of information even if the title
+    /// is shorter (see `display_information()`, e.g. "999.999 kB")
+    const MIN_DATA_WIDTH: usize = 10;
+
+    /// Set up a quantity of information column with a certain title and
+    /// optional maximal width (see `cap_col_width()`)
+    pub fn new(title: &'static str, max_width: Option<usize>) -> Self {
+        Self {
+            title,
+            width: cap_col_width(str_width(title).max(Self::MIN_DATA_WIDTH), max_width),
+        }
+    }
+
+    /// Display the title of the column, surrounded by a fill character
+    pub fn display_title(&self, fill: char) -> impl fmt::Display + '_ {
+        display_col_header(self.title, self.width, fill)
+    }
+
+    /// Display a quantity of information within the column
+    pub fn display_data(&self, quantity: Information) -> impl fmt::Display {
+        let width = self.width;
+        let quantity = display_information(quantity).to_string();
+        let quantity = DelayedDisplay(move |dest| write!(dest, "{:>1$}", quantity, width));
+        display_col_data(quantity, width)
+    }
+
+    /// Display a placeholder within the column, for a failed measurement
+    pub fn display_error(&self) -> impl fmt::Display {
+        display_col_placeholder(ERROR_PLACEHOLDER, self.width)
+    }
+}
+
+/// Notation of rates which are too small to be displayed as a nonzero integer
+/// in their column's unit
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SmallRateNotation {
+    /// Round them to an integer in the column's unit like other rates, which
+    /// displays a trickle of activity as 0
+    Scaled,
+
+    /// Display them as an integer in the base unit, with a unit suffix (e.g.
+    /// "12B" in a KiB/s column)
+    Unscaled,
+
+    /// Display them in the column's unit, in scientific notation (e.g. "1.2e-2"
+    /// in a KiB/s column)
+    Scientific,
+}
+
+impl Default for SmallRateNotation {
+    fn default() -> Self {
+        SmallRateNotation::Scaled
+    }
+}
+
+impl FromStr for SmallRateNotation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "scaled" => Ok(SmallRateNotation::Scaled),
+            "unscaled" => Ok(SmallRateNotation::Unscaled),
+            "scientific" => Ok(SmallRateNotation::Scientific),
+            _ => Err(format!("Unknown small rate notation \"{}\"", s)),
+        }
+    }
+}
+
+/// Rate column formatting
+///
+/// Rates are measured in a base unit per second (e.g. bytes/s), but displayed
+/// as integers in a larger unit (e.g. KiB/s), except for rates which would be
+/// rounded down to zero in that unit (see `SmallRateNotation`).
+///
+pub struct RateFormatter {
+    /// Formatter for integral rates in the column's unit
+    integers: IntegerFormatter,
+
+    /// Number of base units in the column's unit
+    unit_scale: f64,
+
+    /// Suffix of the base unit, used when small rates are unscaled
+    base_unit_suffix: &'static str,
+
+    /// Notation of small rates
+    small_rates: SmallRateNotation,
+}
+
+impl RateFormatter {
+    /// Set up a rate column with a certain title, unit (given as a multiple of
+    /// a base unit, along with that unit's suffix), optional maximal width (see
+    /// `cap_col_width()`) and small rate notation
+    pub fn new(
+        title: &'static str,
+        unit_scale: f64,
+        base_unit_suffix: &'static str,
+        max_width: Option<usize>,
+        small_rates: SmallRateNotation,
+    ) -> Self {
+        Self {
+            integers: IntegerFormatter::new(title, max_width),
+            unit_scale,
+            base_unit_suffix,
+            small_rates,
+        }
+    }
+
+    /// Display the title of the column, surrounded by a fill character
+    pub fn display_title(&self, fill: char) -> impl fmt::Display + '_ {
+        self.integers.display_title(fill)
+    }
+
+    /// Display a rate, in base units per second, within the column
+    pub fn display_data(&self, rate: f64) -> impl fmt::Display {
+        let width = self.integers.width;
+        let scaled = rate / self.unit_scale;
+        let is_small = rate > 0.0 && scaled.round() == 0.0;
+        let (small_rates, suffix) = (self.small_rates, self.base_unit_suffix);
+        let rate = DelayedDisplay(move |dest| match small_rates {
+            SmallRateNotation::Unscaled if is_small => {
+                let unscaled = format!("{:.0}{}", rate, suffix);
+                write!(dest, "{:>1$}", unscaled, width)
+            }
+            SmallRateNotation::Scientific if is_small => write!(dest, "{:>1$.1e}", scaled, width),
+            _ => write!(dest, "{:>1$}", scaled.round() as u64, width),
+        });
+        display_col_data(rate, width)
+    }
+
+    /// Display a placeholder within the column, for a failed measurement
+    pub fn display_error(&self) -> impl fmt::Display {
+        self.integers.display_error()
+    }
+}
+
+/// Number of decimal digits that are needed to print an unsigned integer
+///
+/// Unlike a floating-point logarithm, this is exact for every input, including
+/// 0 (which is printed as one digit) and integers close to a power of 10.
+///
+pub fn decimal_digits(number: u64) -> usize {
+    let mut digits = 1;
+    let mut rest = number / 10;
+    while rest > 0 {
+        digits += 1;
+        rest /= 10;
+    }
+    digits
+}
+
+/// Decimal order of magnitude of an unsigned integer, i.e. floor(log10(n)),
+/// which is considered to be 0 for both 0 and 1
+pub fn magnitude(number: u64) -> u32 {
+    decimal_digits(number) as u32 - 1
+}
 
 /// Display a quantity of information from heim
 pub fn display_information(quantity: Information) -> impl fmt::Display {
@@ -41,27 +502,32 @@ pub fn display_information(quantity: Information) -> impl fmt::Display {
         let bytes = quantity.get::<byte>();
 
         // Check that quantity's order of magnitude
-        let magnitude = if bytes > 0 {
-            (bytes as f64).log10().trunc() as u8
-        } else {
-            0
-        };
-
-        // General recipe for printing fractional SI information quantities
-        let write_si = |dest: &mut fmt::Formatter<'_>, unit_magnitude, unit| {
-            let base = 10_u64.pow(unit_magnitude);
-            let integral_part = bytes / base;
-            let fractional_part = (bytes / (base / 1000)) % 1000;
-            write!(dest, "{}.{:03} {}", integral_part, fractional_part, unit)
-        };
+        let magnitude = magnitude(bytes);
+        if magnitude <= 2 {
+            return write!(dest, "{} B", bytes);
+        }
 
-        // Select the right recipe depending on the order of magnitude
-        match magnitude {
-            0..=2 => write!(dest, "{} B", bytes),
-            3..=5 => write_si(dest, 3, "kB"),
-            6..=8 => write_si(dest, 6, "MB"),
-            9..=11 => write_si(dest, 9, "GB"),
-            _ => write_si(dest, 12, "TB"),
+        // Select the SI unit which matches that order of magnitude, then round
+        // the quantity to the nearest thousandth of that unit. Rounding may
+        // carry over to the next unit (e.g. 999.9996 kB rounds to 1.000 MB).
+        const UNITS: [(u32, &str); 4] = [(3, "kB"), (6, "MB"), (9, "GB"), (12, "TB")];
+        let mut unit_idx = (magnitude as usize / 3 - 1).min(UNITS.len() - 1);
+        loop {
+            let (unit_magnitude, unit) = UNITS[unit_idx];
+            let thousandth = 10_u64.pow(unit_magnitude - 3);
+            let round_up = bytes % thousandth >= (thousandth + 1) / 2;
+            let thousandths = bytes / thousandth + u64::from(round_up);
+            if thousandths >= 1_000_000 && unit_idx + 1 < UNITS.len() {
+                unit_idx += 1;
+                continue;
+            }
+            return write!(
+                dest,
+                "{}.{:03} {}",
+                thousandths / 1000,
+                thousandths % 1000,
+                unit
+            );
         }
     })
 }
@@ -74,3 +540,158 @@ pub fn display_information(quantity: Information) -> impl fmt::Display {
 pub fn str_width(s: &str) -> usize {
     s.graphemes(true).count()
 }
+
+/// Number of terminal lines which are taken by some text, accounting for the
+/// wrapping of lines which are wider than the terminal (if its width is known)
+pub fn line_count(text: &str, term_width: Option<usize>) -> u64 {
+    text.lines()
+        .map(|line| match term_width {
+            Some(term_width) if term_width > 0 => {
+                let width = str_width(line);
+                ((width + term_width - 1) / term_width).max(1) as u64
+            }
+            _ => 1,
+        })
+        .sum()
+}
+
+/// Truncate a string to a certain width in grapheme clusters, replacing the end
+/// of overlong strings with an ellipsis
+pub fn truncate(s: &str, max_width: usize) -> Cow<str> {
+    if str_width(s) <= max_width {
+        return s.into();
+    }
+    let mut truncated = s
+        .graphemes(true)
+        .take(max_width.saturating_sub(1))
+        .collect::<String>();
+    if max_width > 0 {
+        truncated.push('…');
+    }
+    truncated.into()
+}
+
+/// Query the width of the terminal that stdout is connected to, in columns
+///
+/// If stdout is not connected to a terminal, fall back to the COLUMNS
+/// environment variable, like many other command-line tools do.
+///
+pub fn terminal_width() -> Option<usize> {
+    terminal_dimension(|(width, _height)| width, "COLUMNS")
+}
+
+/// Query the height of the terminal that stdout is connected to, in lines
+///
+/// If stdout is not connected to a terminal, fall back to the LINES
+/// environment variable, like many other command-line tools do.
+///
+pub fn terminal_height() -> Option<usize> {
+    terminal_dimension(|(_width, height)| height, "LINES")
+}
+
+/// Query a dimension of the terminal that stdout is connected to, falling back
+/// to an environment variable if stdout is not connected to a terminal
+fn terminal_dimension(
+    select_dimension: impl FnOnce((usize, usize)) -> usize,
+    env_var: &str,
+) -> Option<usize> {
+    termize::dimensions_stdout()
+        .map(select_dimension)
+        .or_else(|| std::env::var(env_var).ok()?.parse::<usize>().ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn small_rate_notations() {
+        let display = |rate: f64, small_rates| {
+            let formatter = RateFormatter::new("net_KiB/s", 1024.0, "B", None, small_rates);
+            formatter.display_data(rate).to_string().trim().to_owned()
+        };
+        for &small_rates in &[
+            SmallRateNotation::Scaled,
+            SmallRateNotation::Unscaled,
+            SmallRateNotation::Scientific,
+        ] {
+            // Rates which are visible in the column's unit are not affected
+            assert_eq!(display(0.0, small_rates), "0");
+            assert_eq!(display(2048.0, small_rates), "2");
+        }
+        assert_eq!(display(12.0, SmallRateNotation::Scaled), "0");
+        assert_eq!(display(12.0, SmallRateNotation::Unscaled), "12B");
+        assert_eq!(display(12.0, SmallRateNotation::Scientific), "1.2e-2");
+    }
+
+    #[test]
+    fn percentage_rounding() {
+        use RoundingMode::*;
+
+        // 49.95 is slightly above the tie once converted to binary, so it is
+        // rounded up, whereas truncation ignores the extra digits
+        assert_eq!(round_decimals(49.94, 1, HalfEven), 49.9);
+        assert_eq!(round_decimals(49.95, 1, HalfEven), 50.0);
+        assert_eq!(round_decimals(49.96, 1, HalfEven), 50.0);
+        assert_eq!(round_decimals(49.94, 1, Truncate), 49.9);
+        assert_eq!(round_decimals(49.95, 1, Truncate), 49.9);
+        assert_eq!(round_decimals(49.96, 1, Truncate), 49.9);
+
+        // Exact ties go to the even last digit
+        assert_eq!(round_decimals(0.25, 1, HalfEven), 0.2);
+        assert_eq!(round_decimals(0.75, 1, HalfEven), 0.8);
+        assert_eq!(round_decimals(-0.25, 1, HalfEven), -0.2);
+
+        // Displayed percentages go through the same rounding
+        let display = |fraction: f32, rounding| {
+            let formatter = PercentageFormatter::new("user%", None, rounding);
+            formatter
+                .display_data(fraction)
+                .to_string()
+                .trim()
+                .to_owned()
+        };
+        assert_eq!(display(0.4994, HalfEven), "49.9%");
+        assert_eq!(display(0.4995, HalfEven), "50.0%");
+        assert_eq!(display(0.4996, HalfEven), "50.0%");
+        assert_eq!(display(0.4995, Truncate), "49.9%");
+        assert_eq!(display(0.4996, Truncate), "49.9%");
+    }
+
+    #[test]
+    fn digits_and_magnitudes() {
+        for &(number, digits, order) in &[
+            (0, 1, 0),
+            (1, 1, 0),
+            (9, 1, 0),
+            (10, 2, 1),
+            (99, 2, 1),
+            (100, 3, 2),
+            (u64::MAX, 20, 19),
+        ] {
+            assert_eq!(decimal_digits(number), digits, "digits of {}", number);
+            assert_eq!(magnitude(number), order, "magnitude of {}", number);
+        }
+    }
+
+    #[test]
+    fn information_display() {
+        let display = |bytes| display_information(Information::new::<byte>(bytes)).to_string();
+        for &(bytes, expected) in &[
+            (0, "0 B"),
+            (999, "999 B"),
+            (1_000, "1.000 kB"),
+            // Boundary between the kB and MB tiers
+            (999_999, "999.999 kB"),
+            (1_000_000, "1.000 MB"),
+            // Rounding to the nearest thousandth, rather than truncating
+            (1_999_499_999, "1.999 GB"),
+            (1_999_500_000, "2.000 GB"),
+            // Rounding which carries over into the next unit
+            (999_999_499, "999.999 MB"),
+            (999_999_500, "1.000 GB"),
+        ] {
+            assert_eq!(display(bytes), expected, "display of {} bytes", bytes);
+        }
+    }
+}
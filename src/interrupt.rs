@@ -0,0 +1,92 @@
+//! Handling of user interruptions (Ctrl-C)
+//!
+//! The first Ctrl-C asks benchmon to stop cleanly, which it does by setting a
+//! flag that long waits are performed on, so that they end early. The second
+//! Ctrl-C exits immediately.
+
+use std::{
+    sync::{Arc, Condvar, Mutex},
+    time::{Duration, Instant},
+};
+
+/// Exit code used when benchmon is interrupted a second time, following the
+/// shell convention of 128 + signal number (SIGINT = 2)
+const FORCED_INTERRUPT_EXIT_CODE: i32 = 130;
+
+/// Flag which is set when the user asks benchmon to stop, and can be waited on
+#[derive(Clone, Default)]
+pub struct StopFlag(Arc<(Mutex<bool>, Condvar)>);
+
+impl StopFlag {
+    /// Set the flag, waking up everyone waiting on it, and tell whether it was
+    /// already set
+    fn set(&self) -> bool {
+        let (stopped, condvar) = &*self.0;
+        let mut stopped = stopped.lock().unwrap();
+        let was_set = *stopped;
+        *stopped = true;
+        condvar.notify_all();
+        was_set
+    }
+
+    /// Truth that the user asked benchmon to stop
+    pub fn is_set(&self) -> bool {
+        *(self.0).0.lock().unwrap()
+    }
+
+    /// Wait for the flag to be set, for at most `timeout`, and tell whether it
+    /// was set (in which case the wait may have been cut short)
+    pub fn wait_timeout(&self, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        let (stopped, condvar) = &*self.0;
+        let mut stopped = stopped.lock().unwrap();
+        while !*stopped {
+            let now = Instant::now();
+            if now >= deadline {
+                break;
+            }
+            stopped = condvar.wait_timeout(stopped, deadline - now).unwrap().0;
+        }
+        *stopped
+    }
+}
+
+/// Handle Ctrl-C by setting the returned flag, or by exiting immediately if
+/// the flag was already set (i.e. on the second Ctrl-C)
+pub fn install_handler() -> Result<StopFlag, ctrlc::Error> {
+    let stop = StopFlag::default();
+    let handler_stop = stop.clone();
+    ctrlc::set_handler(move || {
+        if handler_stop.set() {
+            std::process::exit(FORCED_INTERRUPT_EXIT_CODE);
+        }
+    })?;
+    Ok(stop)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::thread;
+
+    #[test]
+    fn stop_flag() {
+        let stop = StopFlag::default();
+        assert!(!stop.is_set());
+        assert!(!stop.wait_timeout(Duration::from_millis(10)));
+
+        // Setting the flag from another thread cuts a long wait short
+        let setter_stop = stop.clone();
+        let setter = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(10));
+            setter_stop.set()
+        });
+        let start = Instant::now();
+        assert!(stop.wait_timeout(Duration::from_secs(60)));
+        assert!(start.elapsed() < Duration::from_secs(30));
+        assert!(!setter.join().unwrap());
+        assert!(stop.is_set());
+        assert!(stop.set());
+    }
+}
@@ -1,204 +1,1712 @@
+mod agent;
+mod cgroup;
+mod config;
 mod cpu;
+mod disk;
 mod filesystem;
+mod fingerprint;
 mod format;
+mod interrupt;
+mod marker;
 mod memory;
 mod network;
+mod numa;
 mod os;
+mod output;
+mod platform;
+mod privileges;
 mod process;
+mod psi;
+mod report;
+mod runner;
 mod sensors;
+mod summary;
 mod time;
+mod totals;
 mod users;
+mod warning;
 
-use chrono::Local as LocalTime;
-
-use futures_util::{
-    future::{FutureExt, TryFutureExt},
-    stream::{StreamExt, TryStreamExt},
-    try_join,
+use crate::{
+    marker::{MarkerReader, MarkerSource},
+    output::{Sample, SampleSink},
+    warning::BenchmonWarning,
 };
 
-use slog::{info, o, Drain, Logger};
+use async_std::future;
+
+use chrono::{Local as LocalTime, NaiveTime};
+
+use futures_util::stream::TryStreamExt;
+
+use heim::units::{information::mebibyte, Information};
+
+use slog::{crit, debug, error, info, o, warn, Drain, Logger};
 
-use std::{sync::Mutex, thread, time::Duration};
+use std::{
+    collections::HashMap,
+    fmt,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
 
-use structopt::StructOpt;
+use structopt::{clap, StructOpt};
 
 // Command-line options
 #[derive(Debug, StructOpt)]
 #[structopt(rename_all = "kebab-case")]
 /// A benchmarking-oriented system monitor
 struct CliOpts {
+    /// Load default options from this configuration file (a TOML file whose
+    /// keys are option names), which the command line overrides
+    #[structopt(long)]
+    config: Option<PathBuf>,
+
+    /// Bundle of output options for a common use case, which can be
+    /// complemented with other output options: "human" (table on stdout with
+    /// the idle% and iowait% columns), "csv" (the same columns as CSV on
+    /// stdout, instead of the table, with warnings as comments) or "json"
+    /// (every measurement as JSON Lines on stdout, instead of the table)
+    #[structopt(long, possible_values = &["human", "csv", "json"])]
+    preset: Option<Preset>,
+
     /// Report the host system's characteristics on startup
     #[structopt(long)]
     startup_report: bool,
 
+    /// Also save the startup report as JSON into this file (implies
+    /// --startup-report), so that it can be compared across machines
+    #[structopt(long, parse(from_os_str))]
+    report_json: Option<PathBuf>,
+
+    /// Time after which a filesystem is considered unresponsive during the
+    /// startup report, in seconds
+    #[structopt(long, default_value = "5.0", parse(try_from_str = time::parse_timeout))]
+    disk_usage_timeout: Duration,
+
+    /// Comma-separated CPU feature flags (named as in Linux's /proc/cpuinfo)
+    /// whose availability is checked in the startup report
+    #[structopt(
+        long,
+        use_delimiter = true,
+        default_value = "sse4_2,avx,avx2,fma,avx512f,avx512bw,avx512vl,amx_tile,asimd,sve,sve2"
+    )]
+    cpu_flags: Vec<String>,
+
+    /// Report pseudo/virtual filesystems (procfs, tmpfs...) in detail in the
+    /// startup report
+    #[structopt(long)]
+    show_virtual_fs: bool,
+
+    /// Report the type and mount options of the filesystem which backs this
+    /// path, which a storage benchmark is going to access
+    #[structopt(long, parse(from_os_str))]
+    watch_disk: Option<PathBuf>,
+
+    /// Report the installed memory modules on startup (this requires reading
+    /// the DMI tables, which usually needs superuser privileges)
+    #[structopt(long)]
+    dmi: bool,
+
+    /// Include the machine's serial number in the startup report (it is
+    /// omitted by default, as it uniquely identifies the machine)
+    #[structopt(long)]
+    include_serials: bool,
+
     /// Desired date/time format, in strftime notation
     #[structopt(long, default_value = "%a %H:%M:%S")]
     time_format: String,
+
+    /// Display the time column and process creation times in UTC, instead of
+    /// the local timezone
+    #[structopt(long)]
+    utc: bool,
+
+    /// Comma-separated names of kernel boot parameters which are highlighted
+    /// in the startup report
+    #[structopt(
+        long,
+        use_delimiter = true,
+        default_value = "isolcpus,nohz_full,rcu_nocbs,mitigations,intel_pstate,amd_pstate,\
+                         processor.max_cstate,intel_idle.max_cstate,idle,transparent_hugepage"
+    )]
+    boot_params: Vec<String>,
+
+    /// Comma-separated names of processes which are known to cause background
+    /// system activity, and should be warned about in the startup report
+    #[structopt(
+        long,
+        use_delimiter = true,
+        default_value = "unattended-upgrade,updatedb,mandb,packagekitd"
+    )]
+    noisy_processes: Vec<String>,
+
+    /// Resolution to which temperatures are rounded for display, in degrees
+    /// Celsius
+    #[structopt(long, default_value = "0.1")]
+    temperature_resolution: f32,
+
+    /// Comma-separated usernames of service accounts (e.g. CI runners), which
+    /// are reported but do not count towards the multiple users warning
+    #[structopt(long, use_delimiter = true)]
+    ignore_users: Vec<String>,
+
+    /// Only report processes whose resource usage exceeds one of the
+    /// --active-min-cpu and --active-min-rss thresholds in the startup report
+    /// (children of pruned processes are attached to their nearest ancestor)
+    #[structopt(long)]
+    active_only: bool,
+
+    /// Average CPU usage since creation above which a process is considered
+    /// active, in percent of a CPU core
+    #[structopt(long, default_value = "1.0")]
+    active_min_cpu: f64,
+
+    /// Resident memory above which a process is considered active, in MiB
+    #[structopt(long, default_value = "100")]
+    active_min_rss: u64,
+
+    /// Refuse to run with superuser privileges
+    #[structopt(long)]
+    require_unprivileged: bool,
+
+    /// Wall-clock vs monotonic clock discrepancy above which a clock jump is
+    /// reported, in seconds
+    #[structopt(long, default_value = "1.0", parse(try_from_str = time::parse_secs))]
+    clock_jump_threshold: Duration,
+
+    /// Display the fraction of CPU time spent idle
+    #[structopt(long)]
+    idle: bool,
+
+    /// Display the fraction of CPU time spent waiting for I/O
+    #[cfg(target_os = "linux")]
+    #[structopt(long)]
+    iowait: bool,
+
+    /// Display the utilization of the busiest logical CPU core, which makes
+    /// single-threaded bottlenecks stand out
+    #[structopt(long)]
+    busiest_core: bool,
+
+    /// Display the fraction of time during which some tasks were stalled on
+    /// CPU, memory and I/O (requires Linux Pressure Stall Information)
+    #[structopt(long)]
+    psi: bool,
+
+    /// Display the amount of RAM and swap in use, in MiB
+    #[structopt(long)]
+    memory: bool,
+
+    /// Display the fraction of memory in use on the most loaded NUMA node, and
+    /// warn if memory allocations are skewed towards one node (Linux only)
+    #[structopt(long)]
+    numa: bool,
+
+    /// Number of consecutive samples in which a measurement may fail before
+    /// benchmon gives up (isolated failures are only marked in the output)
+    #[structopt(long, default_value = "3")]
+    max_consecutive_errors: u32,
+
+    /// Display the lowest and highest current frequency across all logical CPU
+    /// cores (in MHz), which reveals uneven boosting or throttled cores
+    #[structopt(long)]
+    freq_spread: bool,
+
+    /// Display the lowest current frequency across all logical CPU cores,
+    /// relative to the core's frequency range (0% at its minimal frequency,
+    /// 100% at its maximal frequency), which reveals throttled cores whatever
+    /// the CPU model
+    #[structopt(long)]
+    relative_freq: bool,
+
+    /// Display the rate of involuntary context switches (preemptions) of this
+    /// process, e.g. a running benchmark (Linux-only)
+    #[structopt(long)]
+    track_pid: Option<u32>,
+
+    /// Display the total network throughput (received + sent) of all
+    /// non-loopback network interfaces
+    #[structopt(long)]
+    net: bool,
+
+    /// Display the number of corrected memory errors since startup (requires
+    /// ECC memory and Linux EDAC support)
+    #[structopt(long)]
+    ecc: bool,
+
+    /// Display the highest thermal headroom used by a temperature sensor, i.e.
+    /// how far its temperature went between its high and critical trip points
+    /// (0% at or below the high trip point, 100% at the critical one)
+    #[structopt(long)]
+    thermal_headroom: bool,
+
+    /// Display the sampling jitter, i.e. the delay between the intended and
+    /// actual time of each sample
+    #[structopt(long)]
+    jitter: bool,
+
+    /// Display the total CPU time consumed system-wide since benchmon
+    /// started, in seconds
+    #[structopt(long)]
+    cpu_seconds: bool,
+
+    /// Display the total amount of data read and written by physical disks,
+    /// and received and sent over the network (with --net), since benchmon
+    /// started, and report the disk totals at the end of the run
+    #[structopt(long)]
+    totals: bool,
+
+    /// Record the raw CPU times and statistics, and the raw network traffic
+    /// counters (with --net), in the CSV and JSON outputs, for analyses that
+    /// perform their own differencing (these are cumulative since boot, and
+    /// are not displayed on stdout)
+    #[structopt(long)]
+    raw_counters: bool,
+
+    /// Report the average utilization of each logical CPU core at the end of
+    /// the run, flagging cores that were under- or over-utilized, in order to
+    /// check that a parallel benchmark was well balanced
+    #[structopt(long)]
+    core_balance: bool,
+
+    /// Report the minimum, mean and maximum of every measurement at the end of
+    /// the run, along with a histogram of its values between these bounds
+    #[structopt(long)]
+    summary: bool,
+
+    /// Maximal number of samples which are kept in memory for drawing the
+    /// timelines of the --summary, beyond which they are decimated (the
+    /// minimum, mean and maximum are still computed from every sample)
+    #[structopt(long, default_value = "10000", parse(try_from_str = summary::parse_max_samples))]
+    max_samples_in_memory: usize,
+
+    /// How samples are decimated once --max-samples-in-memory is reached:
+    /// keep "every-kth" sample (k doubling every time the cap is reached), a
+    /// random subset of the samples ("reservoir"), or merge pairs of
+    /// neighboring samples into their "min-max" range (which preserves spikes)
+    #[structopt(
+        long,
+        default_value = "every-kth",
+        possible_values = &["every-kth", "reservoir", "min-max"]
+    )]
+    decimation: summary::Decimation,
+
+    /// Maximal width of an output column, beyond which its contents are
+    /// truncated
+    #[structopt(long)]
+    max_col_width: Option<usize>,
+
+    /// How percentages are rounded for display: "round" to the nearest value
+    /// (ties to even) or "truncate" extra digits like most system monitors
+    #[structopt(long, default_value = "round", possible_values = &["round", "truncate"])]
+    rounding: format::RoundingMode,
+
+    /// How rates which are too small for their column's unit (e.g. a few bytes
+    /// per second in the net_KiB/s column) are displayed: "scaled" like other
+    /// rates (usually as 0), "unscaled" in base units (e.g. "12B"), or in
+    /// "scientific" notation (e.g. "1.2e-2")
+    #[structopt(
+        long,
+        default_value = "scaled",
+        possible_values = &["scaled", "unscaled", "scientific"]
+    )]
+    small_rates: format::SmallRateNotation,
+
+    /// Draw the table borders with ASCII characters instead of Unicode
+    /// box-drawing characters, for terminals that cannot render the latter
+    #[structopt(long)]
+    ascii: bool,
+
+    /// Display a moving average of numerical columns over this many samples
+    #[structopt(long, default_value = "1")]
+    smooth: usize,
+
+    /// Read markers from stdin, one per line, and attach each of them to the
+    /// next sample (e.g. to record when a benchmark phase starts)
+    #[structopt(long)]
+    markers: bool,
+
+    /// Record the measurements into this CSV file, in addition to displaying
+    /// them on stdout ("-" records them on stdout, which requires --no-stdout)
+    #[structopt(long, parse(from_os_str))]
+    csv: Option<PathBuf>,
+
+    /// Append to the CSV file if it already exists (by default, benchmon
+    /// refuses to overwrite an existing file)
+    #[structopt(long)]
+    csv_append: bool,
+
+    /// Record benchmon's warnings as "#"-prefixed comment lines of the CSV
+    /// file, with their code and timestamp, where they occurred in the stream
+    /// of measurements (not every CSV reader supports comments)
+    #[structopt(long)]
+    csv_comments: bool,
+
+    /// Record every measurement into this JSON Lines file, with one JSON
+    /// object per sample ("-" records them on stdout, which requires
+    /// --no-stdout)
+    #[structopt(long, parse(from_os_str))]
+    json: Option<PathBuf>,
+
+    /// Record the measurements into this Parquet file, which is only complete
+    /// once monitoring is over (benchmon refuses to overwrite an existing file)
+    #[cfg(feature = "parquet")]
+    #[structopt(long, parse(from_os_str))]
+    parquet: Option<PathBuf>,
+
+    /// Also write the table of measurements into this text file (benchmon
+    /// refuses to overwrite an existing file)
+    #[structopt(long, parse(from_os_str))]
+    output: Option<PathBuf>,
+
+    /// Do not display the table of measurements on stdout (requires --output,
+    /// --csv, --json or --parquet)
+    #[structopt(long)]
+    no_stdout: bool,
+
+    /// When output files are synced to storage: "never" (rely on the OS),
+    /// "per-sample", or "interval[=<seconds>]" (at most every 10s by default)
+    #[structopt(long, default_value = "never")]
+    fsync: output::SyncPolicy,
+
+    /// Read markers from this named pipe instead of stdin
+    #[structopt(long, parse(from_os_str))]
+    marker_pipe: Option<PathBuf>,
+
+    /// Time between two samples, in seconds (can be fractional, e.g. 0.1)
+    #[structopt(long, default_value = "1", parse(try_from_str = time::parse_period))]
+    period: Duration,
+
+    /// Time between the setup of the system monitors and the first sample, in
+    /// seconds, which the first sample's CPU utilization is measured over
+    /// (later samples are taken one period apart from the first one)
+    #[structopt(long, default_value = "0.1", parse(try_from_str = time::parse_secs))]
+    priming_interval: Duration,
+
+    /// What to do when a sample takes more than 3 periods to be collected:
+    /// "skip" it (and mark the next sample accordingly) or "abort"
+    #[structopt(long, default_value = "skip", possible_values = &["skip", "abort"])]
+    on_stall: StallPolicy,
+
+    /// Wait until this local time of day (in HH:MM format) before sampling,
+    /// unless it is already within the --start-at/--stop-at window
+    #[structopt(long, parse(try_from_str = time::parse_time_of_day))]
+    start_at: Option<NaiveTime>,
+
+    /// Stop sampling at the next occurence of this local time of day (in HH:MM
+    /// format), which may be on the next day
+    #[structopt(long, parse(try_from_str = time::parse_time_of_day))]
+    stop_at: Option<NaiveTime>,
+
+    /// Snapshot the running processes when monitoring starts and stops, then
+    /// report which processes appeared, vanished or significantly changed
+    /// their resource consumption in between
+    #[structopt(long)]
+    process_diff: bool,
+
+    /// Exit with code 3 if any benchmon warning was emitted during the run,
+    /// so that CI can tell an unclean environment apart from errors (which
+    /// yield exit code 1, or 2 for invalid command-line options)
+    #[structopt(long)]
+    fail_on_warning: bool,
+
+    /// Periodically log a message on stderr (every this many seconds) to
+    /// tell that monitoring is still ongoing, which is reassuring during long
+    /// runs whose output is redirected to a file (at least 1s)
+    #[structopt(long, parse(try_from_str = time::parse_heartbeat))]
+    heartbeat: Option<Duration>,
+
+    /// Take a single sample after the --priming-interval, then exit (the
+    /// periodic sampling loop and the end-of-run reports are skipped)
+    #[structopt(long)]
+    once: bool,
+
+    /// Run a benchmark command (given after "--") once the baseline activity
+    /// of the system has been measured, monitor the system until it exits,
+    /// then report its execution time and resource usage (the benchmark's exit
+    /// ends monitoring, so this cannot be combined with --once or --stop-at)
+    #[structopt(long, conflicts_with_all = &["once", "stop-at"])]
+    run: bool,
+
+    /// Kill the benchmark and exit with an error if a temperature sensor gets
+    /// within --critical-margin of its critical trip point (with --run), in
+    /// order to protect the hardware during unattended runs
+    #[structopt(long, requires = "run")]
+    abort_on_critical: bool,
+
+    /// Margin below a sensor's critical trip point at which
+    /// --abort-on-critical kicks in, in degrees Celsius
+    #[structopt(long, default_value = "5.0")]
+    critical_margin: f32,
+
+    /// Number of samples that are taken before starting the benchmark, in
+    /// order to measure the system's baseline activity (with --run)
+    #[structopt(long, default_value = "3")]
+    baseline_samples: u64,
+
+    /// Benchmark command and its arguments (with --run)
+    #[structopt(last = true)]
+    command: Vec<String>,
+
+    /// Serve the latest sample over TCP on this address (e.g. 0.0.0.0:4242),
+    /// to a benchmon instance which collects samples from several machines
+    /// (see --collect)
+    #[structopt(long)]
+    agent: Option<SocketAddr>,
+
+    /// If the --agent port is already in use, try up to this many following
+    /// ports instead of exiting with an error
+    #[structopt(long, default_value = "0")]
+    port_retry: u16,
+
+    /// Instead of monitoring the local system, collect the samples of these
+    /// benchmon agents (comma-separated host:port addresses, see --agent) and
+    /// print them on stdout as CSV, keyed by hostname
+    #[structopt(long, use_delimiter = true, conflicts_with_all = &["agent", "run", "once"])]
+    collect: Vec<String>,
+
+    /// List the host's sensors (as unit:label pairs), then exit
+    #[structopt(long)]
+    list_sensors: bool,
+
+    /// List the host's network interfaces, then exit
+    #[structopt(long)]
+    list_interfaces: bool,
+
+    /// List the host's filesystem devices and mount points, then exit
+    #[structopt(long)]
+    list_mounts: bool,
+}
+
+impl CliOpts {
+    /// Timezone in which time points are displayed
+    fn timezone(&self) -> time::Timezone {
+        if self.utc {
+            time::Timezone::Utc
+        } else {
+            time::Timezone::Local
+        }
+    }
+
+    /// Truth that the measurements are recorded into a Parquet file (which
+    /// requires the "parquet" cargo feature)
+    fn has_parquet_output(&self) -> bool {
+        #[cfg(feature = "parquet")]
+        {
+            self.parquet.is_some()
+        }
+        #[cfg(not(feature = "parquet"))]
+        {
+            false
+        }
+    }
+
+    /// Expand the selected output preset, if any, into the lower-level output
+    /// options that it stands for
+    fn apply_preset(&mut self) {
+        match self.preset {
+            // Live table on stdout, with the CPU activity columns that are most
+            // useful for checking that benchmarks are running well
+            Some(Preset::Human) => {
+                self.idle = true;
+                #[cfg(target_os = "linux")]
+                {
+                    self.iowait = true;
+                }
+            }
+            // Same columns as CSV on stdout, for piping into analysis tools
+            Some(Preset::Csv) => {
+                self.idle = true;
+                self.csv_comments = true;
+                #[cfg(target_os = "linux")]
+                {
+                    self.iowait = true;
+                }
+                if self.csv.is_none() {
+                    self.csv = Some(PathBuf::from("-"));
+                    self.no_stdout = true;
+                }
+            }
+            // Every measurement as JSON Lines on stdout, which does not depend
+            // on the column options
+            Some(Preset::Json) => {
+                if self.json.is_none() {
+                    self.json = Some(PathBuf::from("-"));
+                    self.no_stdout = true;
+                }
+            }
+            None => {}
+        }
+    }
+}
+
+/// Bundle of output options for a common use case
+///
+/// Presets only enable options, so any option which is explicitly specified on
+/// the command line in addition to the preset remains in effect.
+///
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Preset {
+    /// Live table on stdout with the idle% and (on Linux) iowait% columns
+    Human,
+
+    /// CSV on stdout with the same columns as `Human`, instead of the table
+    Csv,
+
+    /// JSON Lines on stdout with every measurement, instead of the table
+    Json,
+}
+
+impl FromStr for Preset {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "human" => Ok(Preset::Human),
+            "csv" => Ok(Preset::Csv),
+            "json" => Ok(Preset::Json),
+            _ => Err(format!("Unknown output preset \"{}\"", s)),
+        }
+    }
+}
+
+/// Reaction to a sample taking too long to be collected
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum StallPolicy {
+    /// Skip the sample and attach a marker to the next one
+    Skip,
+
+    /// Exit with an error
+    Abort,
+}
+
+impl FromStr for StallPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "skip" => Ok(StallPolicy::Skip),
+            "abort" => Ok(StallPolicy::Abort),
+            _ => Err(format!("Unknown stall policy \"{}\"", s)),
+        }
+    }
 }
 
 #[async_std::main]
 async fn main() -> heim::Result<()> {
     // Parse the command-line options
-    let cli_opts = CliOpts::from_args();
+    let matches = CliOpts::clap().get_matches();
+    let mut cli_opts = CliOpts::from_clap(&matches);
+    if let Err(err) = cli_opts.apply_config(&matches) {
+        clap::Error::with_description(&err, clap::ErrorKind::InvalidValue).exit();
+    }
+    cli_opts.apply_preset();
+    if cli_opts.start_at.is_some() && cli_opts.start_at == cli_opts.stop_at {
+        clap::Error::with_description(
+            "The --start-at/--stop-at sampling window must not be empty",
+            clap::ErrorKind::ArgumentConflict,
+        )
+        .exit();
+    }
+    if cli_opts.no_stdout
+        && cli_opts.output.is_none()
+        && cli_opts.csv.is_none()
+        && cli_opts.json.is_none()
+        && !cli_opts.has_parquet_output()
+    {
+        clap::Error::with_description(
+            "--no-stdout requires another output (--output, --csv, --json or --parquet)",
+            clap::ErrorKind::MissingRequiredArgument,
+        )
+        .exit();
+    }
+    let stdout_paths = [&cli_opts.csv, &cli_opts.json]
+        .iter()
+        .filter(|path| path.as_deref() == Some(Path::new("-")))
+        .count();
+    if stdout_paths > 1 || (stdout_paths == 1 && !cli_opts.no_stdout) {
+        clap::Error::with_description(
+            "Only one output may be written to stdout (use --no-stdout)",
+            clap::ErrorKind::ArgumentConflict,
+        )
+        .exit();
+    }
+    if cli_opts.run == cli_opts.command.is_empty() {
+        clap::Error::with_description(
+            "A benchmark command must be given after \"--\" if and only if --run is used",
+            clap::ErrorKind::ArgumentConflict,
+        )
+        .exit();
+    }
 
     // Set up a logger
     let decorator = slog_term::TermDecorator::new().build();
     let drain = slog_term::CompactFormat::new(decorator).build();
     let drain = Mutex::new(drain).fuse();
+    let warning_queue = if cli_opts.csv_comments {
+        Some(warning::WarningQueue::default())
+    } else {
+        None
+    };
+    let (drain, warning_count) = warning::WarningCounter::new(drain, warning_queue.clone());
     let log = slog::Logger::root(drain, o!("benchmon version" => env!("CARGO_PKG_VERSION")));
 
+    // Check the privileges that we are running with
+    if !privileges::startup_check(&log, cli_opts.require_unprivileged) {
+        std::process::exit(1);
+    }
+
+    // List the requested system components and exit, if asked to
+    if cli_opts.list_sensors || cli_opts.list_interfaces || cli_opts.list_mounts {
+        return list_components(&cli_opts).await;
+    }
+
+    // Stop cleanly on Ctrl-C, so that the output sinks are flushed and the
+    // end-of-run reports are produced, and so that waits end early
+    let stop = interrupt::install_handler()
+        .map_err(|err| std::io::Error::new(std::io::ErrorKind::Other, err))?;
+
+    // Collect the samples of remote agents and exit, if asked to
+    if !cli_opts.collect.is_empty() {
+        agent::collect(&log, &cli_opts.collect, &stop)?;
+        return Ok(());
+    }
+
+    // Bind the agent's listening socket early, so that a busy port is
+    // reported before any time is spent monitoring
+    let agent_listener = match cli_opts.agent {
+        Some(addr) => Some(agent::bind(&log, addr, cli_opts.port_retry)?),
+        None => None,
+    };
+
     // Produce the initial system report, if asked to
-    if cli_opts.startup_report {
-        startup_report(&log).await?;
+    if cli_opts.startup_report || cli_opts.report_json.is_some() {
+        startup_report(&log, &cli_opts).await?;
     }
 
-    // Prepare to print periodical time measurements
-    //
-    // TODO: Should use different format for stdout records and file records,
-    //       once dedicated CSV file output is supported.
-    let time_formatter = time::Formatter::new(&cli_opts.time_format);
+    // Wait for the start of the sampling window, if asked to
+    if let Some(start_at) = cli_opts.start_at {
+        let now = LocalTime::now();
+        let within_window = cli_opts.stop_at.map_or(false, |stop_at| {
+            time::is_within_window(now.time(), start_at, stop_at)
+        });
+        if !within_window {
+            let start_time = time::next_occurrence(now, start_at);
+            info!(log, "Waiting for the start of the sampling window";
+                  "start time" => %start_time);
+            if stop.wait_timeout((start_time - now).to_std().unwrap_or_default()) {
+                info!(log, "Interrupted before the start of the sampling window");
+                return Ok(());
+            }
+        }
+    }
+    let stop_time = cli_opts
+        .stop_at
+        .map(|stop_at| time::next_occurrence(LocalTime::now(), stop_at));
 
-    // Perform general system monitoring
+    // Set up the destinations of periodical measurements
+    let monitor_pressure = cli_opts.psi && psi::is_supported();
+    if cli_opts.psi && !monitor_pressure {
+        warn!(log, "Resource pressure information is unavailable (needs Linux \
+                    4.20+ with PSI enabled), it will not be monitored";
+              "warning" => BenchmonWarning::ProbeFailed);
+    }
+    let mut cpu_monitor = cpu::Monitor::new().await?;
+    if let Some(pid) = cli_opts.track_pid {
+        if let Err(err) = cpu_monitor.track_process(pid) {
+            warn!(log, "Failed to query the tracked process' context switches, \
+                        they will not be monitored";
+                  "pid" => pid,
+                  "error" => %err,
+                  "warning" => BenchmonWarning::ProbeFailed);
+        }
+    }
+    let mut core_balance = if cli_opts.core_balance {
+        Some(cpu::balance::CoreBalance::new(cpu_monitor.logical_count()))
+    } else {
+        None
+    };
+    let mut net_monitor = if cli_opts.net {
+        Some(network::Monitor::new().await?)
+    } else {
+        None
+    };
+    let mut disk_monitor = if cli_opts.totals {
+        Some(disk::Monitor::new().await?)
+    } else {
+        None
+    };
+    let mut memory_monitor = if cli_opts.memory {
+        Some(memory::Monitor::new().await?)
+    } else {
+        None
+    };
+    let mut numa_monitor = if cli_opts.numa {
+        match numa::Monitor::new() {
+            Ok(Some(numa_monitor)) => Some(numa_monitor),
+            Ok(None) => {
+                numa::report_unavailable(&log, None);
+                None
+            }
+            Err(err) => {
+                numa::report_unavailable(&log, Some(err));
+                None
+            }
+        }
+    } else {
+        None
+    };
+    let ecc_monitor = if cli_opts.ecc {
+        match memory::EccMonitor::new() {
+            Ok(Some(ecc_monitor)) => Some(ecc_monitor),
+            Ok(None) => {
+                info!(
+                    log,
+                    "ECC is not enabled, memory errors will not be monitored"
+                );
+                None
+            }
+            Err(err) => {
+                warn!(log, "ECC memory information is unavailable, memory \
+                            errors will not be monitored";
+                      "error" => %err,
+                      "warning" => BenchmonWarning::ProbeFailed);
+                None
+            }
+        }
+    } else {
+        None
+    };
+    let time_formatter = time::Formatter::new(&cli_opts.time_format, cli_opts.max_col_width);
+    let columns = output::Columns {
+        idle: cli_opts.idle,
+        #[cfg(target_os = "linux")]
+        io_wait: cli_opts.iowait,
+        busiest_core: cli_opts.busiest_core,
+        pressure: monitor_pressure,
+        memory: cli_opts.memory,
+        numa: numa_monitor.is_some(),
+        involuntary_ctx_switches: cli_opts.track_pid.is_some() || cli_opts.run,
+        net: cli_opts.net,
+        ecc: ecc_monitor.is_some(),
+        thermal_headroom: cli_opts.thermal_headroom,
+        freq_spread: cli_opts.freq_spread,
+        freq_range: *cpu_monitor.frequency_range(),
+        relative_freq: cli_opts.relative_freq,
+        jitter: cli_opts.jitter,
+        cpu_seconds: cli_opts.cpu_seconds,
+        net_totals: cli_opts.totals && cli_opts.net,
+        disk_totals: cli_opts.totals,
+        raw_counters: cli_opts.raw_counters,
+        marker: cli_opts.markers || cli_opts.marker_pipe.is_some(),
+        max_width: cli_opts.max_col_width,
+        smoothing_window: cli_opts.smooth,
+        style: if cli_opts.ascii {
+            format::TableStyle::ASCII
+        } else {
+            format::TableStyle::UNICODE
+        },
+        rounding: cli_opts.rounding,
+        small_rates: cli_opts.small_rates,
+        timezone: cli_opts.timezone(),
+    };
+    let mut sinks: Vec<Box<dyn SampleSink>> = Vec::new();
+    if !cli_opts.no_stdout {
+        sinks.push(Box::new(output::StdoutTable::new(time_formatter, &columns)));
+    }
+    if let Some(output_path) = &cli_opts.output {
+        let writer = output::FileWriter::create_new(output_path, cli_opts.fsync)?;
+        sinks.push(Box::new(output::StdoutTable::for_file(
+            writer,
+            time::Formatter::new(&cli_opts.time_format, cli_opts.max_col_width),
+            &columns,
+        )));
+    }
+    if let Some(csv_path) = &cli_opts.csv {
+        sinks.push(Box::new(output::CsvFile::new(
+            csv_path,
+            cli_opts.csv_append,
+            cli_opts.fsync,
+            &columns,
+            warning_queue,
+        )?));
+    }
+    if let Some(json_path) = &cli_opts.json {
+        sinks.push(Box::new(output::JsonLinesFile::new(
+            json_path,
+            cli_opts.fsync,
+        )?));
+    }
+    #[cfg(feature = "parquet")]
+    {
+        if let Some(parquet_path) = &cli_opts.parquet {
+            sinks.push(Box::new(output::ParquetFile::new(
+                parquet_path,
+                cli_opts.fsync,
+                &columns,
+            )?));
+        }
+    }
+
+    // Set up the system monitors
+    let mut psi_monitor = if monitor_pressure {
+        Some(psi::Monitor::new()?)
+    } else {
+        None
+    };
+
+    let sensor_monitor = if cli_opts.abort_on_critical || cli_opts.thermal_headroom {
+        let sensor_monitor = sensors::Monitor::new().await?;
+        if cli_opts.abort_on_critical && !sensor_monitor.has_critical_trip_points() {
+            warn!(log, "No temperature sensor has a critical trip point, \
+                        --abort-on-critical will not protect the hardware";
+                  "warning" => BenchmonWarning::ProbeFailed);
+        }
+        Some(sensor_monitor)
+    } else {
+        None
+    };
+
+    // Describe the measurements that are going to be emitted
+    for sink in &mut sinks {
+        sink.write_header()?;
+    }
+
+    // Start reading user-provided markers, if asked to
+    let marker_reader = match (&cli_opts.marker_pipe, cli_opts.markers) {
+        (Some(path), _) => Some(MarkerReader::new(&log, MarkerSource::Pipe(path.clone()))),
+        (None, true) => Some(MarkerReader::new(&log, MarkerSource::Stdin)),
+        (None, false) => None,
+    };
+
+    // Prepare to detect wall-clock adjustments
+    let mut clock_jump_detector = time::ClockJumpDetector::new(cli_opts.clock_jump_threshold);
+
+    // Prepare to measure the sampling jitter
+    let period = cli_opts.period;
+    let mut last_instant = None;
+
+    // Prepare to accumulate the non-idle CPU time, if asked to
+    let mut cpu_seconds = Duration::default();
+
+    // Prepare to summarize the measurements at the end of the run, if asked to
+    let mut summary = if cli_opts.summary {
+        Some(summary::Summary::new(
+            cli_opts.max_samples_in_memory,
+            cli_opts.decimation,
+        ))
+    } else {
+        None
+    };
+
+    // Count the samples that were emitted, which the heartbeat and the
+    // baseline measurement of runner mode rely on
+    let samples_taken = Arc::new(AtomicU64::new(0));
+    let sample_counter = samples_taken.clone();
+    sinks.push(Box::new(output::Callback::new(move |_sample| {
+        sample_counter.fetch_add(1, Ordering::Relaxed);
+    })));
+
+    // Report that monitoring is ongoing at regular intervals, if asked to
+    // TODO: Suppress the heartbeat once a --quiet option exists
+    let snapshot = if cli_opts.heartbeat.is_some() || agent_listener.is_some() {
+        let snapshot = output::Snapshot::new();
+        sinks.push(Box::new(snapshot.clone()));
+        Some(snapshot)
+    } else {
+        None
+    };
+    if let Some(heartbeat) = cli_opts.heartbeat {
+        spawn_heartbeat(
+            log.clone(),
+            heartbeat,
+            samples_taken.clone(),
+            snapshot
+                .clone()
+                .expect("A snapshot is taken for the heartbeat"),
+        );
+    }
+
+    // Serve the latest sample to remote collectors, if asked to
+    if let Some(listener) = agent_listener {
+        let hostname = heim::host::platform().await?.hostname().to_owned();
+        agent::spawn_server(
+            log.clone(),
+            listener,
+            hostname,
+            snapshot.expect("A snapshot is taken for the agent"),
+            period,
+        );
+    }
+
+    // Prepare to detect stalled sampling
+    let stall_timeout = 3 * period;
+    let mut sampling_stalled = false;
+
+    // Prepare to detect measurement gaps, i.e. intervals between two samples
+    // that are much longer than the sampling period (because a stalled sample
+    // was skipped, or benchmon was paused)
     //
-    // TODO: Once we have a good system monitor, also allow using it to monitor
-    //       execution of some benchmark. Measure baseline before starting
-    //       benchmark execution. Also monitor child getrusage() during process
-    //       execution, and wall-clock execution time.
+    // NOTE: The monotonic clock does not advance while the system is suspended
+    //       on Linux, so system suspend is caught by the clock jump detector
+    //       instead of being reported as a measurement gap.
     //
-    let mut newlines_since_last_header = u64::MAX;
-    loop {
-        // Print a header describing the measurements in the beginning, and if
-        // we are outputting to a terminal, re-print it once per page of output.
-        const HEADER_HEIGHT: u64 = 1;
-        let term_height = termize::dimensions_stdout()
-            .map(|(_width, height)| height as u64)
-            .unwrap_or(u64::MAX);
-        if newlines_since_last_header >= term_height - HEADER_HEIGHT {
-            println!(
-                "{}{}",
-                time_formatter.display_title(),
-                format::COL_HEADER_SEPARATOR
-            );
-            newlines_since_last_header = 1;
-        }
+    let gap_threshold = 2 * period;
+
+    // Prepare to tolerate transient measurement failures
+    let mut failures = FailureTracker::new(cli_opts.max_consecutive_errors);
 
-        // Monitor the time
+    // Snapshot the running processes, if asked to (end-of-run reports are
+    // skipped when a single sample is requested).
+    // In runner mode, the snapshots are rather taken right before the
+    // benchmark is started and right after it has exited.
+    let mut initial_processes = if cli_opts.process_diff && !cli_opts.once && !cli_opts.run {
+        Some(process::ProcessSnapshot::take().await?)
+    } else {
+        None
+    };
+    let mut final_processes = None;
+
+    // Let the system monitors accumulate some activity, so that the utilization
+    // figures of the first sample are meaningful. This also matters when a
+    // single sample is requested, as CPU time is only accounted at scheduler
+    // tick granularity, which makes utilization over a few ms meaningless.
+    stop.wait_timeout(cli_opts.priming_interval);
+
+    // Sample timestamps are complemented with the time elapsed since this
+    // point, measured using the monotonic clock, which is immune to wall clock
+    // adjustments
+    let start_instant = Instant::now();
+
+    // Benchmark that is being run, if in runner mode
+    let mut benchmark = None;
+
+    // Perform general system monitoring
+    loop {
+        // Monitor the time and CPU activity
         // TODO: Monitor other quantities
         // TODO: Make the set of monitored quantities configurable
         let local_time = LocalTime::now();
+        let instant = Instant::now();
 
-        // Display the measurements
-        // TODO: Print multiple quantities in a tabular fashion
-        // TODO: In addition to stdout, support in-memory records, dump to file
-        println!(
-            "{}{}",
-            time_formatter.display_data(local_time),
-            format::COL_DATA_SEPARATOR
-        );
-        newlines_since_last_header += 1;
+        // Stop at the end of the sampling window, if any
+        if stop_time.map_or(false, |stop_time| local_time >= stop_time) {
+            break;
+        }
+
+        // Stop if the user asked us to
+        if stop.is_set() {
+            info!(
+                log,
+                "Interrupted, stopping monitoring (press Ctrl-C again to \
+                        exit immediately)"
+            );
+            break;
+        }
+
+        // Start the benchmark once the system's baseline activity has been
+        // measured, if in runner mode, and track its context switches
+        if cli_opts.run
+            && benchmark.is_none()
+            && samples_taken.load(Ordering::Relaxed) >= cli_opts.baseline_samples
+        {
+            if cli_opts.process_diff {
+                initial_processes = Some(process::ProcessSnapshot::take().await?);
+            }
+            let started = runner::Benchmark::start(&log, &cli_opts.command)?;
+            if let Err(err) = cpu_monitor.track_process(started.pid()) {
+                warn!(log, "Failed to query the benchmark's context switches, \
+                            they will not be monitored";
+                      "pid" => started.pid(),
+                      "error" => %err,
+                      "warning" => BenchmonWarning::ProbeFailed);
+            }
+            benchmark = Some(started);
+        }
+
+        // Collect the measurements, making sure that a stuck system query does
+        // not silently stop sampling
+        //
+        // NOTE: heim performs its file I/O on async-std's blocking thread pool,
+        //       so a stuck sysfs/procfs read does not prevent the timeout from
+        //       firing. And cancelling the collection midway is fine, as each
+        //       monitor only updates its state once its own query succeeded,
+        //       so it keeps measuring changes since its last successful query.
+        //
+        let mut sensor_readings = None;
+        let collection = async {
+            // CPU time is shown in every sample, so if measuring it failed, we
+            // skip this sample and try again after one period. But if the
+            // number of CPU cores changed, this will not get any better.
+            let cpu_time = cpu_monitor.time_change().await;
+            let cpu_time = match failures.check(&log, "CPU time", cpu_time)? {
+                Some(Ok(cpu_time)) => cpu_time,
+                Some(Err(core_count_changed)) => {
+                    crit!(log, "The number of logical CPU cores changed, aborting";
+                          "initial" => core_count_changed.initial,
+                          "current" => core_count_changed.current);
+                    return Err(
+                        std::io::Error::new(std::io::ErrorKind::Other, core_count_changed).into(),
+                    );
+                }
+                None => return Ok(None),
+            };
+
+            // Measure the utilization of each CPU core, if needed for the core
+            // balance report or the busiest core column
+            let per_core = if core_balance.is_some() || cli_opts.busiest_core {
+                let per_core = cpu_monitor.per_core_time_change().await;
+                failures.check(&log, "per-core CPU time", per_core)?
+            } else {
+                None
+            };
+            if let (Some(core_balance), Some(per_core)) = (&mut core_balance, &per_core) {
+                core_balance.record(per_core);
+            }
+            let busiest_core = if cli_opts.busiest_core {
+                per_core.as_deref().and_then(cpu::balance::busiest_core)
+            } else {
+                None
+            };
 
-        // Wait for a while
-        // TODO: Make period configurable
-        thread::sleep(Duration::new(1, 0));
+            // Measure CPU statistics, for the tracked process' involuntary
+            // context switch rate and the raw counters
+            let tracked_pid = cpu_monitor.tracked_pid();
+            let stats = if tracked_pid.is_some() || cli_opts.raw_counters {
+                let stats = cpu_monitor.stats_change().await;
+                failures.check(&log, "CPU statistics", stats)?
+            } else {
+                None
+            };
+            let involuntary_ctx_switch_rate = stats.as_ref().and_then(|stats| {
+                let switches = stats.new_tracked_ctx_switches?;
+                Some(switches.involuntary as f64 / stats.elapsed.as_secs_f64())
+            });
+            if let (Some(pid), None) = (tracked_pid, cpu_monitor.tracked_pid()) {
+                warn!(log, "The tracked process is gone, its context switches \
+                            will not be monitored anymore";
+                      "pid" => pid,
+                      "warning" => BenchmonWarning::ProbeFailed);
+            }
+
+            // Raw CPU counters are only reported if they were just measured
+            let raw_cpu_counters = if cli_opts.raw_counters && stats.is_some() {
+                Some(cpu_monitor.raw_counters())
+            } else {
+                None
+            };
+
+            // Measure the network throughput, along with the raw counters and
+            // the totals, which are only reported if they were just measured
+            let (net_throughput, raw_net_counters, net_totals) = match &mut net_monitor {
+                Some(net_monitor) => {
+                    let throughput = net_monitor.aggregate_throughput().await;
+                    let throughput = failures.check(&log, "network", throughput)?;
+                    let raw_counters = if cli_opts.raw_counters && throughput.is_some() {
+                        Some(net_monitor.raw_totals())
+                    } else {
+                        None
+                    };
+                    let totals = if cli_opts.totals && throughput.is_some() {
+                        Some(net_monitor.totals())
+                    } else {
+                        None
+                    };
+                    (throughput, raw_counters, totals)
+                }
+                None => (None, None, None),
+            };
+
+            // Measure the disk I/O totals
+            let disk_totals = match &mut disk_monitor {
+                Some(disk_monitor) => {
+                    let update = disk_monitor.update().await;
+                    failures
+                        .check(&log, "disk I/O", update)?
+                        .map(|()| disk_monitor.totals())
+                }
+                None => None,
+            };
+
+            // Measure temperatures, for the thermal headroom column and the
+            // protection against overheating
+            if let Some(sensor_monitor) = &sensor_monitor {
+                let readings = sensor_monitor.readings().await;
+                sensor_readings = failures.check(&log, "temperature", readings)?;
+            }
+
+            // Markers and timing irregularities are filled in by the caller,
+            // since they must not be consumed by a sample that stalled
+            Ok::<_, heim::Error>(Some(Sample {
+                local_time,
+                elapsed: instant.saturating_duration_since(start_instant),
+                cpu_time,
+                busiest_core,
+                freq_spread: if cli_opts.freq_spread {
+                    failures
+                        .check(
+                            &log,
+                            "CPU frequency",
+                            cpu_monitor.current_frequency_spread().await,
+                        )?
+                        .flatten()
+                } else {
+                    None
+                },
+                relative_freq: if cli_opts.relative_freq {
+                    failures
+                        .check(
+                            &log,
+                            "relative CPU frequency",
+                            cpu_monitor.lowest_relative_frequency().await,
+                        )?
+                        .flatten()
+                } else {
+                    None
+                },
+                jitter: None,
+                gap: None,
+                cpu_seconds: None,
+                net_totals,
+                disk_totals,
+                pressure: match &mut psi_monitor {
+                    Some(psi_monitor) => {
+                        failures.check(&log, "pressure", psi_monitor.pressure_change())?
+                    }
+                    None => None,
+                },
+                memory: match &mut memory_monitor {
+                    Some(memory_monitor) => {
+                        failures.check(&log, "memory", memory_monitor.usage_change().await)?
+                    }
+                    None => None,
+                },
+                numa: match &numa_monitor {
+                    Some(numa_monitor) => {
+                        failures.check(&log, "NUMA", numa_monitor.node_usage())?
+                    }
+                    None => None,
+                },
+                involuntary_ctx_switch_rate,
+                net_throughput,
+                ecc_errors: match &ecc_monitor {
+                    Some(ecc_monitor) => failures.check(&log, "ECC", ecc_monitor.new_errors())?,
+                    None => None,
+                },
+                thermal_headroom: if cli_opts.thermal_headroom {
+                    sensor_readings
+                        .as_deref()
+                        .and_then(sensors::max_headroom_used)
+                } else {
+                    None
+                },
+                raw_cpu_counters,
+                raw_net_counters,
+                marker: None,
+            }))
+        };
+        let mut sample = match future::timeout(stall_timeout, collection).await {
+            Ok(sample) => match sample? {
+                Some(sample) => sample,
+                None => {
+                    stop.wait_timeout(period);
+                    continue;
+                }
+            },
+            Err(_timeout) => match cli_opts.on_stall {
+                StallPolicy::Skip => {
+                    error!(log, "Sampling stalled, skipping this sample";
+                           "timeout (s)" => stall_timeout.as_secs_f64(),
+                           "warning" => BenchmonWarning::SamplingStalled);
+                    sampling_stalled = true;
+                    continue;
+                }
+                StallPolicy::Abort => {
+                    crit!(log, "Sampling stalled, aborting";
+                          "timeout (s)" => stall_timeout.as_secs_f64(),
+                          "warning" => BenchmonWarning::SamplingStalled);
+                    for sink in &mut sinks {
+                        sink.finish()?;
+                    }
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::TimedOut,
+                        "Sampling stalled",
+                    )
+                    .into());
+                }
+            },
+        };
+
+        // Record the gaps left by skipped samples in the markers
+        let mut marker = marker_reader.as_ref().and_then(MarkerReader::next_marker);
+        if std::mem::take(&mut sampling_stalled) {
+            const GAP_MARKER: &str = "sampling stalled";
+            marker = Some(match marker {
+                Some(marker) => format!("{}; {}", GAP_MARKER, marker),
+                None => GAP_MARKER.to_owned(),
+            });
+        }
+        sample.marker = marker;
+
+        // Measure the interval since the previous sample, and flag it as a
+        // measurement gap if it is much longer than expected
+        let interval = last_instant
+            .replace(instant)
+            .map(|last_instant| instant.saturating_duration_since(last_instant));
+        let gap = interval.filter(|interval| *interval > gap_threshold);
+        if let Some(gap) = gap {
+            info!(log, "Measurement gap detected, no data was collected for a while";
+                  "gap (s)" => gap.as_secs_f64());
+        }
+        sample.jitter = interval.map(|interval| interval.checked_sub(period).unwrap_or_default());
+        sample.gap = gap;
+
+        // Fix up implausible CPU time breakdowns, which sum up above 100%
+        // because of a measurement glitch (e.g. clock skew)
+        if let Some(cpu_time_total) = sample.cpu_time.fix_implausible_total() {
+            debug!(log, "CPU time breakdown sums up above 100%, renormalizing it";
+                   "total (%)" => cpu_time_total * 100.0);
+        }
+
+        // Accumulate the non-idle CPU time, if asked to
+        if cli_opts.cpu_seconds {
+            let busy_frac = (1.0 - sample.cpu_time.idle_frac).max(0.0);
+            cpu_seconds += sample.cpu_time.overall.mul_f32(busy_frac);
+            sample.cpu_seconds = Some(cpu_seconds);
+        }
+
+        // Warn if the wall clock was adjusted since the last measurement
+        if let Some(discrepancy) = clock_jump_detector.check(local_time, instant) {
+            warn!(log, "The system clock was adjusted during monitoring, \
+                        timestamps before and after this point are not \
+                        directly comparable";
+                  "clock discrepancy (s)" => discrepancy.as_secs_f64(),
+                  "warning" => BenchmonWarning::ClockJump);
+        }
+
+        // Warn if resource pressure rose sharply since monitoring started
+        if let (Some(psi_monitor), Some(pressure)) = (&mut psi_monitor, &sample.pressure) {
+            psi_monitor.check_rise(&log, pressure);
+        }
+
+        // Warn if memory allocations are skewed towards one NUMA node
+        if let (Some(numa_monitor), Some(numa)) = (&mut numa_monitor, &sample.numa) {
+            numa_monitor.check_skew(&log, numa);
+        }
+
+        // Send the measurements to every output sink
+        // TODO: In addition to stdout and files, support in-memory records
+        for sink in &mut sinks {
+            sink.write_sample(&sample)?;
+            sink.flush()?;
+        }
+        if let Some(summary) = &mut summary {
+            summary.record(&sample);
+        }
+
+        // Kill the benchmark and exit if a sensor is getting dangerously hot,
+        // if asked to
+        if cli_opts.abort_on_critical {
+            let critical_reading = sensor_readings
+                .iter()
+                .flatten()
+                .find(|reading| reading.is_near_critical(cli_opts.critical_margin));
+            if let Some(reading) = critical_reading {
+                let to_celsius = |temperature| {
+                    sensors::RoundedTemperature::new(temperature, cli_opts.temperature_resolution)
+                        .to_string()
+                };
+                let critical_trip_point = reading.critical_trip_point.map(to_celsius);
+                crit!(log, "A sensor is close to its critical temperature, aborting";
+                      "sensor" => &reading.id,
+                      "temperature (°C)" => to_celsius(reading.current),
+                      "critical trip point (°C)" => critical_trip_point);
+                if let Some(benchmark) = &mut benchmark {
+                    benchmark.kill(&log)?;
+                }
+                for sink in &mut sinks {
+                    sink.finish()?;
+                }
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::Other,
+                    "A temperature sensor got too close to its critical trip point",
+                )
+                .into());
+            }
+        }
+
+        // Stop here if a single sample was requested
+        if cli_opts.once {
+            break;
+        }
+
+        // Stop once the benchmark has exited, if in runner mode
+        if let Some(benchmark) = &mut benchmark {
+            if benchmark.try_finish(&log)? {
+                if cli_opts.process_diff {
+                    final_processes = Some(process::ProcessSnapshot::take().await?);
+                }
+                break;
+            }
+        }
+
+        // Wait until the next sample is due, accounting for the time that was
+        // spent taking and emitting this one so that sampling does not drift
+        stop.wait_timeout(period.checked_sub(instant.elapsed()).unwrap_or_default());
+    }
+
+    // Do not let the benchmark run on if monitoring stopped before it exited
+    // (e.g. because the user interrupted benchmon)
+    drop(benchmark);
+
+    // Make sure that all measurements reached their final destination
+    for sink in &mut sinks {
+        sink.finish()?;
+    }
+
+    // Report how the running processes changed during monitoring (or during
+    // the benchmark's execution, in runner mode), if asked to
+    if let Some(initial_processes) = initial_processes {
+        let final_processes = match final_processes {
+            Some(final_processes) => final_processes,
+            None => process::ProcessSnapshot::take().await?,
+        };
+        initial_processes.diff_report(&log, &final_processes);
+    }
+
+    // Produce the end-of-run reports, unless a single sample was requested
+    if !cli_opts.once {
+        // Summarize the measurements, if asked to
+        if let Some(summary) = &summary {
+            summary.report(&log, columns.style);
+        }
+
+        // Report the network traffic over the run, if it was monitored
+        if let Some(net_monitor) = &net_monitor {
+            let (received, sent) = net_monitor.totals();
+            info!(log, "Network traffic over the run";
+                  "received" => %format::display_information(received),
+                  "sent" => %format::display_information(sent));
+        }
+
+        // Report the disk I/O over the run, if it was monitored
+        if let Some(disk_monitor) = &disk_monitor {
+            let (read, written) = disk_monitor.totals();
+            info!(log, "Disk I/O over the run";
+                  "read" => %format::display_information(read),
+                  "written" => %format::display_information(written));
+        }
+
+        // Report how evenly the CPU cores were utilized, if asked to
+        if let Some(core_balance) = &core_balance {
+            core_balance.report(&log, columns.style);
+        }
+
+        // Report the monitoring overhead
+        process::self_usage_report(&log);
+    }
+
+    // Signal that warnings were emitted via the exit code, if asked to
+    if cli_opts.fail_on_warning && warning_count.load(Ordering::Relaxed) > 0 {
+        std::process::exit(warning::WARNING_EXIT_CODE);
     }
 
     // TODO: After end of benchmark execution, produce tabular data sets for
     //       manual inspection to begin with, and later implement direct
     //       support for fancy plots (with plotters? plotly?)
+    Ok(())
 }
 
-/// Describe the host system on application startup
-async fn startup_report(log: &Logger) -> heim::Result<()> {
-    // Ask heim to start fetching all the system info we need...
-    // (with a bit of future boxing here and there to reduce type complexity)
+/// List the identifiers of some system components, for use in configuration
+async fn list_components(cli_opts: &CliOpts) -> heim::Result<()> {
+    if cli_opts.list_sensors {
+        let temperatures = heim::sensors::temperatures()
+            .try_collect::<Vec<_>>()
+            .await?;
+        sensors::list(temperatures, sensors::thermal_zones()?);
+    }
+    if cli_opts.list_interfaces {
+        let network_interfaces = heim::net::nic().try_collect::<Vec<_>>().await?;
+        network::list(network_interfaces);
+    }
+    if cli_opts.list_mounts {
+        let disk_partitions = heim::disk::partitions().try_collect::<Vec<_>>().await?;
+        filesystem::list(disk_partitions);
+    }
+    Ok(())
+}
+
+/// Describe the host system on application startup, and save a
+/// machine-readable version of this description if asked to
+async fn startup_report(log: &Logger, cli_opts: &CliOpts) -> heim::Result<()> {
+    // Query the host system's configuration
     info!(log, "Probing host system characteristics...");
-    // - CPU info
-    let global_cpu_freq = heim::cpu::frequency().boxed();
-    #[cfg(target_os = "linux")]
-    let per_cpu_freqs = heim::cpu::os::linux::frequencies()
-        .try_collect::<Vec<_>>()
-        .map_ok(Some)
-        .boxed();
-    #[cfg(not(target_os = "linux"))]
-    let per_cpu_freqs = futures_util::future::ok(None);
-    let logical_cpus = heim::cpu::logical_count();
-    let physical_cpus = heim::cpu::physical_count();
-    // - Platform info (= OS info + CPU architecture)
-    let platform = heim::host::platform();
-    // - Memory info
-    let memory = heim::memory::memory();
-    let swap = heim::memory::swap();
-    // - Filesystem info
-    let disk_partitions_and_usage = heim::disk::partitions()
-        .and_then(|partition| async {
-            // NOTE: Failure to stat a partition is purposely treated as a
-            //       non-fatal event, unlike all other failures, as it happens
-            //       on random pseudo-filesystems that no one cares about.
-            let usage_result = heim::disk::usage(partition.mount_point()).await;
-            Ok((partition, usage_result))
-        })
-        .try_collect::<Vec<_>>();
-    // - Network info
-    let network_interfaces = heim::net::nic().try_collect::<Vec<_>>();
-    // - Sensor info
-    //
-    // FIXME: This stream is where 80% of the type complexity lies (crate max
-    //        type length goes from ~230000 to ~47000 upon commenting sensor
-    //        reporting out), but I cannot box it because that causes a weird
-    //        E0308 "one type is more general than the other" error.
-    //
-    //        There are multiple reports of similar confusing errors on the
-    //        rustc bugtracker, I subscribed to those for now and will try again
-    //        after they are fixed.
-    //
-    let temperatures = heim::sensors::temperatures().try_collect::<Vec<_>>();
-    // - Virtualization info
-    let virt = heim::virt::detect().map(Ok).boxed();
-    // - User connexion info
-    let user_connections = heim::host::users().try_collect::<Vec<_>>();
-    // - Initial processes info
-    let processes = heim::process::processes()
-        .then(process::get_process_info)
-        .try_collect::<Vec<_>>();
+    let options = report::ReportOptions {
+        disk_usage_timeout: cli_opts.disk_usage_timeout,
+        dmi: cli_opts.dmi,
+    };
+    let report = report::system_report(&options).await?;
+
+    // Save the machine-readable report, if asked to
+    if let Some(json_path) = &cli_opts.report_json {
+        let json_report = report::JsonReport::new(&report);
+        let json_file = std::fs::File::create(json_path)?;
+        serde_json::to_writer_pretty(json_file, &json_report).map_err(std::io::Error::from)?;
+        info!(log, "Saved the startup report as JSON";
+              "path" => %json_path.display(),
+              "schema version" => report::SCHEMA_VERSION);
+    }
 
     // Report CPU configuration
-    let (logical_cpus, physical_cpus, global_cpu_freq, per_cpu_freqs) =
-        try_join!(logical_cpus, physical_cpus, global_cpu_freq, per_cpu_freqs)?;
     cpu::startup_report(
         &log,
-        logical_cpus,
-        physical_cpus,
-        global_cpu_freq,
-        per_cpu_freqs,
+        report.logical_cpus,
+        report.physical_cpus,
+        report.cpu_sockets,
+        report.global_cpu_freq,
+        report.per_cpu_freqs,
     );
 
+    cpu::flags::startup_report(&log, &cli_opts.cpu_flags);
+    cpu::idle::startup_report(&log);
+    cpu::isolation::startup_report(&log);
+
+    // Report clock resolution
+    time::startup_report(&log);
+    psi::startup_report(&log);
+
     // Report memory configuration
-    let (memory, swap) = try_join!(memory, swap)?;
-    memory::startup_report(&log, memory, swap);
+    memory::startup_report(&log, report.memory, report.swap, report.memory_modules);
 
     // Report filesystem configuration
-    let disk_partitions_and_usage = disk_partitions_and_usage.await?;
-    filesystem::startup_report(&log, disk_partitions_and_usage);
+    report_or_warn(
+        log,
+        "filesystem",
+        report.disk_partitions_and_usage,
+        |disk_partitions_and_usage| {
+            filesystem::startup_report(
+                &log,
+                disk_partitions_and_usage,
+                cli_opts.show_virtual_fs,
+                cli_opts.watch_disk.as_deref(),
+            )
+        },
+    );
 
     // Report network configuration
-    let network_interfaces = network_interfaces.await?;
-    network::startup_report(&log, network_interfaces);
+    report_or_warn(
+        log,
+        "network",
+        report.network_interfaces,
+        |network_interfaces| network::startup_report(&log, network_interfaces),
+    );
 
     // Report sensor configuration
-    let temperatures = temperatures.await?;
-    sensors::startup_report(&log, temperatures);
+    report_or_warn(
+        log,
+        "sensor",
+        report.temperatures_and_zones,
+        |(temperatures, thermal_zones)| {
+            sensors::startup_report(
+                &log,
+                temperatures,
+                thermal_zones,
+                cli_opts.temperature_resolution,
+            )
+        },
+    );
 
     // Report operating system and use of virtualization
-    let (platform, virt) = try_join!(platform, virt)?;
-    os::startup_report(&log, platform, virt);
+    let logical_cpus = report.logical_cpus;
+    report_or_warn(
+        log,
+        "operating system",
+        report.platform_and_virt,
+        |(platform, virt)| {
+            os::startup_report(&log, platform, virt, &cli_opts.boot_params, logical_cpus)
+        },
+    );
+    os::identity_report(&log, report.system_identity, cli_opts.include_serials);
+
+    // Fingerprint the machine configuration
+    fingerprint::startup_report(&log, &report.machine_spec);
 
     // Report open user sessions
-    let user_connections = user_connections.await?;
-    users::startup_report(&log, user_connections);
+    report_or_warn(
+        log,
+        "user session",
+        report.user_connections,
+        |user_connections| users::startup_report(&log, user_connections, &cli_opts.ignore_users),
+    );
 
     // Report running processes
-    let processes = processes.await?;
-    process::startup_report(&log, processes);
+    report_or_warn(log, "process", report.processes, |processes| {
+        let activity_thresholds = if cli_opts.active_only {
+            Some(process::ActivityThresholds {
+                cpu_frac: cli_opts.active_min_cpu / 100.0,
+                rss: Information::new::<mebibyte>(cli_opts.active_min_rss),
+            })
+        } else {
+            None
+        };
+        process::startup_report(
+            &log,
+            processes,
+            &cli_opts.noisy_processes,
+            activity_thresholds,
+            cli_opts.timezone(),
+        )
+    });
     Ok(())
 }
+
+/// Tracker of consecutive measurement failures
+///
+/// During a long monitoring run, a single failure to take a measurement should
+/// not stop monitoring. So failures are only logged and the measurement is
+/// left out of the affected sample, unless the same measurement keeps failing
+/// for too many consecutive samples, which is treated as fatal.
+///
+struct FailureTracker {
+    /// Number of consecutive failures beyond which an error is fatal
+    max_consecutive: u32,
+
+    /// Number of consecutive failures of each measurement
+    consecutive: HashMap<&'static str, u32>,
+}
+
+impl FailureTracker {
+    /// Set up failure tracking
+    fn new(max_consecutive: u32) -> Self {
+        Self {
+            max_consecutive,
+            consecutive: HashMap::new(),
+        }
+    }
+
+    /// Check the result of a measurement, turning it into None if it failed
+    /// and the failure is tolerated, or into an error otherwise
+    fn check<T, E>(
+        &mut self,
+        log: &Logger,
+        measurement: &'static str,
+        result: Result<T, E>,
+    ) -> heim::Result<Option<T>>
+    where
+        E: fmt::Display + Into<heim::Error>,
+    {
+        let consecutive = self.consecutive.entry(measurement).or_insert(0);
+        match result {
+            Ok(value) => {
+                *consecutive = 0;
+                Ok(Some(value))
+            }
+            Err(err) => {
+                *consecutive += 1;
+                if *consecutive > self.max_consecutive {
+                    error!(log, "A measurement failed repeatedly, aborting";
+                           "measurement" => measurement,
+                           "consecutive failures" => *consecutive,
+                           "error" => %err);
+                    Err(err.into())
+                } else {
+                    debug!(log, "A measurement failed, leaving it out of this sample";
+                           "measurement" => measurement,
+                           "consecutive failures" => *consecutive,
+                           "error" => %err);
+                    Ok(None)
+                }
+            }
+        }
+    }
+}
+
+/// Log a message at regular intervals, from a background thread, to tell that
+/// monitoring is still ongoing, how many samples were taken so far, and what
+/// the latest sample looked like
+fn spawn_heartbeat(
+    log: Logger,
+    interval: Duration,
+    samples_taken: Arc<AtomicU64>,
+    snapshot: output::Snapshot,
+) {
+    thread::spawn(move || loop {
+        thread::sleep(interval);
+        let samples_taken = samples_taken.load(Ordering::Relaxed);
+        match snapshot.latest() {
+            Some(sample) => {
+                info!(log, "Still monitoring";
+                      "samples taken" => samples_taken,
+                      "last sample time (s)" => sample.elapsed.as_secs_f64(),
+                      "last CPU utilization (%)" => sample.cpu_time.busy_frac() * 100.0);
+            }
+            None => info!(log, "Still monitoring, no sample was taken yet"),
+        }
+    });
+}
+
+/// Report on a non-critical part of the host system's configuration, or warn
+/// that it could not be queried without aborting the whole startup report
+fn report_or_warn<T>(
+    log: &Logger,
+    section: &str,
+    query_result: heim::Result<T>,
+    report: impl FnOnce(T),
+) {
+    match query_result {
+        Ok(data) => report(data),
+        Err(err) => warn!(log, "Failed to query {} configuration, skipping it", section;
+                          "error" => %err,
+                          "warning" => BenchmonWarning::ProbeFailed),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io;
+
+    #[test]
+    fn failure_tracker() {
+        let log = Logger::root(slog::Discard, o!());
+        let failure = || Err::<u32, _>(io::Error::new(io::ErrorKind::Other, "failed"));
+        let mut failures = FailureTracker::new(2);
+
+        // Successful measurements are passed through
+        assert_eq!(
+            failures.check(&log, "a", Ok::<_, io::Error>(42)).unwrap(),
+            Some(42)
+        );
+
+        // Up to the limit, consecutive failures are tolerated
+        assert_eq!(failures.check(&log, "a", failure()).unwrap(), None);
+        assert_eq!(failures.check(&log, "a", failure()).unwrap(), None);
+
+        // Failures of each measurement are counted separately
+        assert_eq!(failures.check(&log, "b", failure()).unwrap(), None);
+
+        // A success resets the count of consecutive failures
+        assert_eq!(
+            failures.check(&log, "b", Ok::<_, io::Error>(24)).unwrap(),
+            Some(24)
+        );
+        assert_eq!(failures.check(&log, "b", failure()).unwrap(), None);
+        assert_eq!(failures.check(&log, "b", failure()).unwrap(), None);
+
+        // Beyond the limit, failures are fatal
+        assert!(failures.check(&log, "a", failure()).is_err());
+        assert!(failures.check(&log, "b", failure()).is_err());
+
+        // With no tolerance, the first failure is fatal
+        let mut failures = FailureTracker::new(0);
+        assert!(failures.check(&log, "a", failure()).is_err());
+    }
+}
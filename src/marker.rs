@@ -0,0 +1,97 @@
+//! User-provided annotations of the sample stream
+//!
+//! When manually driving a benchmark, it is useful to mark events (e.g. "phase
+//! 2 started") in the sample stream. benchmon can read such markers line by
+//! line from its standard input or from a named pipe, and attaches each of
+//! them to the next sample that it emits.
+
+use crate::warning::BenchmonWarning;
+
+use slog::{warn, Logger};
+
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader},
+    path::{Path, PathBuf},
+    sync::mpsc::{self, Receiver, Sender},
+    thread,
+};
+
+/// Source of markers
+pub enum MarkerSource {
+    /// Read markers from benchmon's standard input
+    Stdin,
+
+    /// Read markers from a named pipe, which is reopened whenever the process
+    /// writing into it closes it
+    Pipe(PathBuf),
+}
+
+/// Receiver of markers, which are read by a background thread
+pub struct MarkerReader {
+    /// Markers which were read, but not attached to a sample yet
+    receiver: Receiver<String>,
+}
+
+impl MarkerReader {
+    /// Start reading markers from a certain source
+    pub fn new(log: &Logger, source: MarkerSource) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        let log = log.clone();
+        thread::spawn(move || {
+            let result = match &source {
+                MarkerSource::Stdin => {
+                    let stdin = io::stdin();
+                    forward_lines(stdin.lock(), &sender)
+                }
+                MarkerSource::Pipe(path) => forward_pipe(path, &sender),
+            };
+            if let Err(err) = result {
+                warn!(log, "Failed to read markers, no more markers will be recorded";
+                      "error" => %err,
+                      "warning" => BenchmonWarning::MarkerReadFailed);
+            }
+        });
+        Self { receiver }
+    }
+
+    /// Fetch the markers that were received since the last call, if any
+    ///
+    /// If multiple markers were received since the last call, they are
+    /// merged into a single one, separated by semicolons.
+    ///
+    pub fn next_marker(&self) -> Option<String> {
+        let markers = self.receiver.try_iter().collect::<Vec<_>>();
+        if markers.is_empty() {
+            None
+        } else {
+            Some(markers.join("; "))
+        }
+    }
+}
+
+/// Forward the lines written into a named pipe to a marker channel, reopening
+/// the pipe whenever the process writing into it closes it
+fn forward_pipe(path: &Path, sender: &Sender<String>) -> io::Result<()> {
+    loop {
+        // Opening a named pipe blocks until a writer shows up
+        forward_lines(BufReader::new(File::open(path)?), sender)?;
+    }
+}
+
+/// Forward the non-empty lines of some input to a marker channel, until the
+/// end of the input is reached
+fn forward_lines(input: impl BufRead, sender: &Sender<String>) -> io::Result<()> {
+    for line in input.lines() {
+        let line = line?;
+        let marker = line.trim();
+        if marker.is_empty() {
+            continue;
+        }
+        // If the main thread is gone, nobody is listening for markers anymore
+        if sender.send(marker.to_owned()).is_err() {
+            break;
+        }
+    }
+    Ok(())
+}
@@ -1,21 +1,405 @@
-use crate::format;
+use crate::{
+    format,
+    platform::{Host, Platform},
+    warning::BenchmonWarning,
+};
 
-use heim::memory::{Memory, Swap};
+use futures_util::try_join;
 
-use slog::{info, warn, Logger};
+use heim::{
+    memory::{Memory, Swap},
+    units::{information::byte, Information},
+};
+
+use slog::{debug, info, warn, Logger};
+
+use std::io;
+
+/// Pool of huge pages of a given size
+pub struct HugePagePool {
+    /// Size of the huge pages from this pool
+    pub size: Information,
+
+    /// Truth that this is the default huge page size
+    pub is_default: bool,
+
+    /// Number of huge pages that were reserved in this pool
+    pub total: u64,
+
+    /// Number of huge pages from this pool which are available for use
+    pub free: u64,
+}
+
+/// Swap area (partition or file)
+pub struct SwapArea {
+    /// Path to the swap partition or file
+    pub path: String,
+
+    /// Size of the swap area
+    pub size: Information,
+
+    /// Amount of swap space that is in use
+    pub used: Information,
+
+    /// Truth that the swap area is stored on a rotational device (HDD), which
+    /// is much slower than an SSD, if known
+    pub rotational: Option<bool>,
+}
+
+/// Memory controller with error detection and correction (ECC) enabled
+pub struct MemoryController {
+    /// Name of the memory controller (e.g. "mc0")
+    pub name: String,
+
+    /// Number of corrected memory errors since boot
+    pub correctable_errors: u64,
+
+    /// Number of memory errors which could not be corrected since boot
+    pub uncorrectable_errors: u64,
+}
+
+/// Count the memory errors of every memory controller since boot, as a
+/// (correctable, uncorrectable) pair, or None if ECC is not enabled
+fn count_memory_errors(controllers: &[MemoryController]) -> Option<(u64, u64)> {
+    if controllers.is_empty() {
+        return None;
+    }
+    Some(controllers.iter().fold((0, 0), |(ce, ue), controller| {
+        (
+            ce + controller.correctable_errors,
+            ue + controller.uncorrectable_errors,
+        )
+    }))
+}
+
+/// Monitor of corrected memory errors
+pub struct EccMonitor {
+    /// Number of correctable errors when monitoring started
+    baseline: u64,
+}
+
+impl EccMonitor {
+    /// Set up memory error monitoring, or return None if ECC is not enabled
+    pub fn new() -> io::Result<Option<Self>> {
+        Ok(
+            count_memory_errors(&Host::memory_controllers()?).map(|(correctable_errors, _)| Self {
+                baseline: correctable_errors,
+            }),
+        )
+    }
+
+    /// Count the correctable memory errors since monitoring started
+    pub fn new_errors(&self) -> io::Result<u64> {
+        let (correctable_errors, _) =
+            count_memory_errors(&Host::memory_controllers()?).unwrap_or_default();
+        Ok(correctable_errors.saturating_sub(self.baseline))
+    }
+}
+
+/// RAM and swap usage, and its variation between two measurements
+#[derive(Clone)]
+#[allow(unused)]
+pub struct MemoryUsage {
+    /// RAM which is in use (i.e. not available for new allocations)
+    pub ram_used: Information,
+
+    /// RAM which is available for new allocations without swapping
+    pub ram_available: Information,
+
+    /// Swap which is in use
+    pub swap_used: Information,
+
+    /// Change in RAM usage since the last measurement, in bytes
+    pub ram_used_change: i64,
+
+    /// Change in swap usage since the last measurement, in bytes
+    pub swap_used_change: i64,
+}
+
+/// Memory monitoring mechanism
+pub struct Monitor {
+    /// Last measured RAM and swap usage
+    last_used: (Information, Information),
+}
+
+impl Monitor {
+    /// Set up memory monitoring
+    pub async fn new() -> heim::Result<Self> {
+        let (memory, swap) = try_join!(heim::memory::memory(), heim::memory::swap())?;
+        Ok(Self {
+            last_used: (ram_used(&memory), swap.used()),
+        })
+    }
+
+    /// Report the current RAM and swap usage, and its change since the last
+    /// measurement
+    pub async fn usage_change(&mut self) -> heim::Result<MemoryUsage> {
+        let (memory, swap) = try_join!(heim::memory::memory(), heim::memory::swap())?;
+        let ram_used = ram_used(&memory);
+        let swap_used = swap.used();
+        let (old_ram_used, old_swap_used) = self.last_used;
+        let change = |new: Information, old: Information| {
+            new.get::<byte>() as i64 - old.get::<byte>() as i64
+        };
+        let result = MemoryUsage {
+            ram_used,
+            ram_available: memory.available(),
+            swap_used,
+            ram_used_change: change(ram_used, old_ram_used),
+            swap_used_change: change(swap_used, old_swap_used),
+        };
+        self.last_used = (ram_used, swap_used);
+        Ok(result)
+    }
+}
+
+/// Amount of RAM which is in use, i.e. not available for new allocations
+fn ram_used(memory: &Memory) -> Information {
+    // NOTE: Available memory can exceed total memory for a short while when
+    //       memory hotplug or ballooning is in progress.
+    let total = memory.total().get::<byte>();
+    let available = memory.available().get::<byte>();
+    Information::new::<byte>(total.saturating_sub(available))
+}
+
+/// Query the system's base memory page size, if possible
+// TODO: Query page size on other OSes
+pub fn page_size() -> Option<Information> {
+    #[cfg(unix)]
+    {
+        // This is safe because sysconf has no side effect
+        let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) };
+        if page_size > 0 {
+            Some(Information::new::<byte>(page_size as u64))
+        } else {
+            None
+        }
+    }
+
+    #[cfg(not(unix))]
+    {
+        None
+    }
+}
+
+/// Installed memory module, as described by the SMBIOS/DMI tables
+pub struct MemoryModule {
+    /// Memory technology (DDR4, DDR5...)
+    memory_type: &'static str,
+
+    /// Capacity of the module, if known
+    size: Option<Information>,
+
+    /// Maximal speed of the module in MT/s, if known
+    max_speed: Option<u16>,
+
+    /// Speed at which the module is configured to run in MT/s, if known
+    configured_speed: Option<u16>,
+}
+
+impl MemoryModule {
+    /// Decode the formatted area of an SMBIOS "Memory Device" (type 17)
+    /// structure, return None if it describes an empty memory slot.
+    fn parse(formatted: &[u8]) -> Option<Self> {
+        let read_u8 = |offset: usize| formatted.get(offset).copied();
+        let read_u16 = |offset: usize| {
+            let bytes = formatted.get(offset..offset + 2)?;
+            Some(u16::from_le_bytes([bytes[0], bytes[1]]))
+        };
+        let read_u32 = |offset: usize| {
+            let bytes = formatted.get(offset..offset + 4)?;
+            Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        };
+        let read_speed =
+            |offset: usize| read_u16(offset).filter(|&speed| speed != 0 && speed != 0xFFFF);
+
+        // Decode the module size, which has a rather convoluted encoding
+        const KIB: u64 = 1024;
+        const MIB: u64 = 1024 * KIB;
+        let size_bytes = match read_u16(0x0C)? {
+            0 => return None,
+            0xFFFF => None,
+            0x7FFF => read_u32(0x1C).map(|size_mib| (size_mib & 0x7FFF_FFFF) as u64 * MIB),
+            size_kib if size_kib & 0x8000 != 0 => Some((size_kib & 0x7FFF) as u64 * KIB),
+            size_mib => Some(size_mib as u64 * MIB),
+        };
+
+        // Decode the memory type
+        let memory_type = match read_u8(0x12) {
+            Some(0x12) => "DDR",
+            Some(0x13) => "DDR2",
+            Some(0x18) => "DDR3",
+            Some(0x1A) => "DDR4",
+            Some(0x1B) => "LPDDR",
+            Some(0x1C) => "LPDDR2",
+            Some(0x1D) => "LPDDR3",
+            Some(0x1E) => "LPDDR4",
+            Some(0x22) => "DDR5",
+            Some(0x23) => "LPDDR5",
+            _ => "Other",
+        };
+
+        Some(Self {
+            memory_type,
+            size: size_bytes.map(Information::new::<byte>),
+            max_speed: read_speed(0x15),
+            configured_speed: read_speed(0x20),
+        })
+    }
+}
+
+/// Enumerate the installed memory modules using the SMBIOS/DMI tables
+///
+/// This is only supported on Linux, and usually requires superuser privileges.
+///
+pub fn memory_modules() -> io::Result<Vec<MemoryModule>> {
+    let table = Host::dmi_table()?;
+    let mut modules = Vec::new();
+    let mut offset = 0;
+    while offset + 4 <= table.len() {
+        // Every structure starts with a type and a formatted area length,
+        // and the table ends with an "End-of-Table" (type 127) structure.
+        let struct_type = table[offset];
+        let length = table[offset + 1] as usize;
+        if struct_type == 127 || length < 4 || offset + length > table.len() {
+            break;
+        }
+        if struct_type == 17 {
+            modules.extend(MemoryModule::parse(&table[offset..offset + length]));
+        }
+
+        // The formatted area is followed by a set of strings, which ends
+        // with a double NUL terminator
+        let mut strings_end = offset + length;
+        while strings_end + 1 < table.len()
+            && (table[strings_end], table[strings_end + 1]) != (0, 0)
+        {
+            strings_end += 1;
+        }
+        offset = strings_end + 2;
+    }
+    Ok(modules)
+}
 
 /// Report on the host's memory configuration
-pub fn startup_report(log: &Logger, memory: Memory, swap: Swap) {
+///
+/// Memory modules are only reported if they were queried (as this requires
+/// special privileges, it only happens at the user's request).
+///
+pub fn startup_report(
+    log: &Logger,
+    memory: Memory,
+    swap: Swap,
+    memory_modules: Option<io::Result<Vec<MemoryModule>>>,
+) {
+    let page_size = match page_size() {
+        Some(page_size) => format!("{}", format::display_information(page_size)),
+        None => "unknown".to_owned(),
+    };
     info!(log, "Received memory configuration information";
           "RAM size" => %format::display_information(memory.total()),
+          "page size" => page_size,
           "swap size" => %format::display_information(swap.total()));
 
+    match Host::huge_page_pools() {
+        Ok(pools) => {
+            for pool in pools {
+                info!(log, "Found a huge page pool";
+                      "page size" => %format::display_information(pool.size),
+                      "default" => pool.is_default,
+                      "reserved pages" => pool.total,
+                      "free pages" => pool.free);
+            }
+        }
+        Err(err) => {
+            info!(log, "Huge page information is unavailable";
+                  "error" => %err);
+        }
+    }
+
+    let swap_areas = match Host::swap_areas() {
+        Ok(swap_areas) => swap_areas,
+        Err(err) => {
+            debug!(log, "Swap area information is unavailable"; "error" => %err);
+            Vec::new()
+        }
+    };
+    for area in &swap_areas {
+        let storage = match area.rotational {
+            Some(true) => "HDD",
+            Some(false) => "SSD",
+            None => "unknown",
+        };
+        info!(log, "Found a swap area";
+              "path" => &area.path,
+              "size" => %format::display_information(area.size),
+              "usage" => %format::display_information(area.used),
+              "storage" => storage);
+    }
+
     if swap.used() > swap.total() / 10 {
+        // Swapping to a rotational device is much worse than to an SSD
+        let on_hdd = swap_areas
+            .iter()
+            .any(|area| area.rotational == Some(true) && area.used.get::<byte>() > 0);
+        let location = if on_hdd {
+            ", partly on a slow rotational device"
+        } else {
+            ""
+        };
         warn!(
             log,
-            "Non-negligible use of swap detected, make sure that it doesn't
-             bias your benchmark!";
-            "swap usage" => %format::display_information(swap.used())
+            "Non-negligible use of swap detected{}, make sure that it doesn't \
+             bias your benchmark!",
+            location;
+            "swap usage" => %format::display_information(swap.used()),
+            "swapping to HDD" => on_hdd,
+            "warning" => BenchmonWarning::SwapInUse
         );
     }
+
+    match Host::memory_controllers() {
+        Ok(controllers) => match count_memory_errors(&controllers) {
+            Some((correctable_errors, uncorrectable_errors)) => {
+                info!(log, "Received ECC memory information";
+                      "memory controllers" => controllers.len(),
+                      "corrected errors" => correctable_errors,
+                      "uncorrected errors" => uncorrectable_errors);
+                if uncorrectable_errors > 0 {
+                    warn!(log, "Uncorrectable memory errors occured since boot, \
+                                this machine's RAM may be faulty";
+                          "uncorrected errors" => uncorrectable_errors,
+                          "warning" => BenchmonWarning::MemoryErrors);
+                }
+            }
+            None => debug!(log, "No ECC memory controller was found"),
+        },
+        Err(err) => {
+            debug!(log, "ECC memory information is unavailable"; "error" => %err);
+        }
+    }
+
+    match memory_modules {
+        Some(Ok(modules)) => {
+            info!(log, "Received memory module information";
+                  "module count" => modules.len());
+            for module in modules {
+                let size = match module.size {
+                    Some(size) => format!("{}", format::display_information(size)),
+                    None => "unknown".to_owned(),
+                };
+                info!(log, "Found a memory module";
+                      "type" => module.memory_type,
+                      "size" => size,
+                      "max speed (MT/s)" => module.max_speed,
+                      "configured speed (MT/s)" => module.configured_speed);
+            }
+        }
+        Some(Err(err)) => {
+            info!(log, "Memory module information is unavailable (needs root \
+                        privileges and DMI tables)";
+                  "error" => %err);
+        }
+        None => {}
+    }
 }
@@ -1,12 +1,20 @@
-use heim::net::{Address, MacAddr, Nic};
+use crate::totals::{ByteCounters, ByteTotals};
+
+use futures_util::stream::TryStreamExt;
+
+use heim::{
+    net::{Address, IoCounters, MacAddr, Nic},
+    units::{information::byte, Information},
+};
 
 use slog::{debug, info, o, Logger};
 
 use std::{
     borrow::Cow,
-    collections::{btree_map::Entry, BTreeMap},
+    collections::{btree_map::Entry, BTreeMap, BTreeSet, HashMap, HashSet},
     fmt::Debug,
     net::{Ipv4Addr, Ipv6Addr, SocketAddr},
+    time::Instant,
 };
 
 /// Unpack a heim `Address` which is assumed to be a link-layer address
@@ -42,6 +50,45 @@ fn unwrap_ipv6_address(address: Address) -> Ipv6Addr {
     }
 }
 
+/// Tell which scope an IPv4 address belongs to
+fn ipv4_scope(address: Ipv4Addr) -> &'static str {
+    if address.is_loopback() {
+        "loopback"
+    } else if address.is_link_local() {
+        "link-local"
+    } else if address.is_private() {
+        "private"
+    } else if address.is_multicast() {
+        "multicast"
+    } else if address.is_broadcast() {
+        "broadcast"
+    } else if address.is_unspecified() {
+        "unspecified"
+    } else {
+        "global"
+    }
+}
+
+/// Tell which scope an IPv6 address belongs to
+fn ipv6_scope(address: Ipv6Addr) -> &'static str {
+    // NOTE: The standard library does not provide stable link-local and unique
+    //       local address checks for IPv6 yet, so we check prefixes ourselves.
+    let first_segment = address.segments()[0];
+    if address.is_loopback() {
+        "loopback"
+    } else if first_segment & 0xffc0 == 0xfe80 {
+        "link-local"
+    } else if first_segment & 0xfe00 == 0xfc00 {
+        "private"
+    } else if address.is_multicast() {
+        "multicast"
+    } else if address.is_unspecified() {
+        "unspecified"
+    } else {
+        "global"
+    }
+}
+
 /// Global properties of a network interface card (according to ifconfig)
 #[derive(Debug, Default)]
 struct InterfaceProperties {
@@ -334,6 +381,7 @@ pub fn startup_report(log: &Logger, network_interfaces: Vec<Nic>) {
                 .expect("IP addresses should have a subnet mask");
             info!(nic_log, "Got an IPv4 address";
                   "address" => ?ipv4_address_props.address,
+                  "scope" => ipv4_scope(ipv4_address_props.address),
                   "netmask" => ?netmask,
                   "bcast/dest" => %print_ip_target(ipv4_address_props.target));
         }
@@ -345,8 +393,225 @@ pub fn startup_report(log: &Logger, network_interfaces: Vec<Nic>) {
                 .expect("IP addresses should have a subnet mask");
             info!(nic_log, "Got an IPv6 address";
                   "address" => ?ipv6_address_props.address,
+                  "scope" => ipv6_scope(ipv6_address_props.address),
                   "netmask" => ?netmask,
                   "bcast/dest" => %print_ip_target(ipv6_address_props.target));
         }
     }
 }
+
+/// List the host's network interface names
+pub fn list(network_interfaces: Vec<Nic>) {
+    let names = network_interfaces
+        .iter()
+        .map(|interface| interface.name().to_owned())
+        .collect::<BTreeSet<_>>();
+    for name in names {
+        println!("{}", name);
+    }
+}
+
+/// Raw traffic counters of a network interface (these are monotonic since
+/// boot, or since the interface appeared)
+#[derive(Clone, Copy)]
+pub struct InterfaceCounters {
+    /// Number of bytes received
+    pub bytes_recv: u64,
+
+    /// Number of bytes sent
+    pub bytes_sent: u64,
+
+    /// Number of packets received
+    pub packets_recv: u64,
+
+    /// Number of packets sent
+    pub packets_sent: u64,
+}
+
+/// Traffic counters of an interface which has not transferred anything
+const ZERO_COUNTERS: InterfaceCounters = InterfaceCounters {
+    bytes_recv: 0,
+    bytes_sent: 0,
+    packets_recv: 0,
+    packets_sent: 0,
+};
+
+/// Traffic rates of a network interface, per second
+#[derive(Clone, Copy)]
+pub struct InterfaceRates {
+    /// Bytes received per second
+    pub bytes_recv: f64,
+
+    /// Bytes sent per second
+    pub bytes_sent: f64,
+
+    /// Packets received per second
+    #[allow(unused)]
+    pub packets_recv: f64,
+
+    /// Packets sent per second
+    #[allow(unused)]
+    pub packets_sent: f64,
+}
+
+/// Bytes received and sent by each non-loopback interface, for `ByteTotals`
+fn byte_counters(
+    counters: &HashMap<String, InterfaceCounters>,
+    loopback_interfaces: &HashSet<String>,
+) -> HashMap<String, ByteCounters> {
+    counters
+        .iter()
+        .filter(|(interface, _counters)| !loopback_interfaces.contains(*interface))
+        .map(|(interface, counters)| {
+            let bytes = (counters.bytes_recv, counters.bytes_sent);
+            (interface.clone(), bytes)
+        })
+        .collect()
+}
+
+/// Network monitoring mechanism
+pub struct Monitor {
+    /// Names of the loopback interfaces, whose traffic never leaves the host
+    loopback_interfaces: HashSet<String>,
+
+    /// Last measured traffic counters of each interface, and the associated
+    /// timestamp
+    counters: (HashMap<String, InterfaceCounters>, Instant),
+
+    /// Bytes received and sent by non-loopback interfaces since monitoring
+    /// started
+    traffic: ByteTotals,
+}
+
+impl Monitor {
+    /// Set up network monitoring
+    pub async fn new() -> heim::Result<Self> {
+        let loopback_interfaces = heim::net::nic()
+            .try_filter(|nic| futures_util::future::ready(nic.is_loopback()))
+            .map_ok(|nic| nic.name().to_owned())
+            .try_collect::<HashSet<_>>()
+            .await?;
+        let counters = Self::read_counters().await?;
+        Ok(Self {
+            traffic: ByteTotals::new(byte_counters(&counters, &loopback_interfaces)),
+            loopback_interfaces,
+            counters: (counters, Instant::now()),
+        })
+    }
+
+    /// Measure the traffic rates of each network interface since the last
+    /// measurement, keyed by interface name
+    ///
+    /// Interfaces which appeared since the last measurement only start to be
+    /// reported on the next one, and interfaces which disappeared or whose
+    /// counters were reset are not reported, so no traffic is counted twice.
+    ///
+    pub async fn interface_rates(&mut self) -> heim::Result<HashMap<String, InterfaceRates>> {
+        let counters = Self::read_counters().await?;
+        let timestamp = Instant::now();
+        let (old_counters, old_timestamp) = &self.counters;
+        let elapsed_secs = (timestamp - *old_timestamp).as_secs_f64();
+
+        let mut rates = HashMap::with_capacity(counters.len());
+        for (interface, new) in &counters {
+            let old = match old_counters.get(interface) {
+                Some(old) => old,
+                None => continue,
+            };
+            if new.bytes_recv < old.bytes_recv
+                || new.bytes_sent < old.bytes_sent
+                || new.packets_recv < old.packets_recv
+                || new.packets_sent < old.packets_sent
+            {
+                continue;
+            }
+            let rate = |new_count: u64, old_count: u64| {
+                if elapsed_secs > 0.0 {
+                    (new_count - old_count) as f64 / elapsed_secs
+                } else {
+                    0.0
+                }
+            };
+            rates.insert(
+                interface.clone(),
+                InterfaceRates {
+                    bytes_recv: rate(new.bytes_recv, old.bytes_recv),
+                    bytes_sent: rate(new.bytes_sent, old.bytes_sent),
+                    packets_recv: rate(new.packets_recv, old.packets_recv),
+                    packets_sent: rate(new.packets_sent, old.packets_sent),
+                },
+            );
+        }
+
+        self.traffic
+            .update(byte_counters(&counters, &self.loopback_interfaces));
+        self.counters = (counters, timestamp);
+        Ok(rates)
+    }
+
+    /// Measure the total network throughput (received + sent) of all
+    /// non-loopback interfaces since the last measurement, in bytes/second
+    ///
+    /// See `interface_rates()` for how interfaces which appeared, disappeared
+    /// or were reset since the last measurement are handled.
+    ///
+    pub async fn aggregate_throughput(&mut self) -> heim::Result<f64> {
+        let rates = self.interface_rates().await?;
+        Ok(rates
+            .iter()
+            .filter(|(interface, _rates)| !self.loopback_interfaces.contains(*interface))
+            .map(|(_interface, rates)| rates.bytes_recv + rates.bytes_sent)
+            .sum())
+    }
+
+    /// Measure the total number of bytes received and sent by non-loopback
+    /// interfaces since monitoring started, as of the last measurement
+    ///
+    /// Traffic that was counted before a counter reset, or before an interface
+    /// disappeared, is kept (see the `totals` module).
+    ///
+    pub fn totals(&self) -> (Information, Information) {
+        let (received, sent) = self.traffic.totals();
+        (
+            Information::new::<byte>(received),
+            Information::new::<byte>(sent),
+        )
+    }
+
+    /// Report the raw traffic counters of non-loopback interfaces, summed over
+    /// interfaces, as of the last measurement
+    ///
+    /// Unlike `totals()`, this does not account for counter resets.
+    ///
+    pub fn raw_totals(&self) -> InterfaceCounters {
+        let mut totals = ZERO_COUNTERS;
+        for (interface, counters) in &self.counters.0 {
+            if self.loopback_interfaces.contains(interface) {
+                continue;
+            }
+            totals.bytes_recv += counters.bytes_recv;
+            totals.bytes_sent += counters.bytes_sent;
+            totals.packets_recv += counters.packets_recv;
+            totals.packets_sent += counters.packets_sent;
+        }
+        totals
+    }
+
+    /// Read the traffic counters of every network interface
+    async fn read_counters() -> heim::Result<HashMap<String, InterfaceCounters>> {
+        heim::net::io_counters()
+            .map_ok(|counters: IoCounters| {
+                (
+                    counters.interface().to_owned(),
+                    InterfaceCounters {
+                        bytes_recv: counters.bytes_recv().get::<byte>(),
+                        bytes_sent: counters.bytes_sent().get::<byte>(),
+                        packets_recv: counters.packets_recv(),
+                        packets_sent: counters.packets_sent(),
+                    },
+                )
+            })
+            .try_collect()
+            .await
+    }
+}
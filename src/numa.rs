@@ -0,0 +1,167 @@
+//! Per-NUMA-node memory monitoring
+//!
+//! On systems with several NUMA nodes, memory-bound benchmarks are sensitive to
+//! where their data is allocated. With the default "first touch" policy, data
+//! which is initialized by a single thread ends up on that thread's node, and
+//! the other nodes must then access it remotely.
+
+use crate::{
+    format,
+    platform::{Host, Platform},
+    warning::BenchmonWarning,
+};
+
+use heim::units::{information::byte, Information};
+
+use slog::{info, warn, Logger};
+
+use std::io;
+
+/// Memory of a NUMA node
+#[derive(Clone)]
+pub struct NodeMemory {
+    /// Identifier of the NUMA node
+    pub node: u32,
+
+    /// Amount of memory which is attached to the node
+    pub total: Information,
+
+    /// Amount of memory from the node which is in use
+    pub used: Information,
+}
+
+impl NodeMemory {
+    /// Fraction of the node's memory which is in use
+    fn usage_frac(&self) -> f32 {
+        let total = self.total.get::<byte>();
+        if total > 0 {
+            self.used.get::<byte>() as f32 / total as f32
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Memory usage of every NUMA node
+#[derive(Clone)]
+pub struct NumaUsage {
+    /// Memory of each NUMA node
+    pub nodes: Vec<NodeMemory>,
+}
+
+impl NumaUsage {
+    /// Fraction of memory which is in use on the most heavily loaded node
+    pub fn max_usage_frac(&self) -> f32 {
+        self.nodes
+            .iter()
+            .map(NodeMemory::usage_frac)
+            .fold(0.0, f32::max)
+    }
+}
+
+/// Per-NUMA-node memory monitor
+pub struct Monitor {
+    /// Memory usage of each node when monitoring started
+    baseline: NumaUsage,
+
+    /// Truth that a skewed allocation pattern was already reported
+    reported_skew: bool,
+}
+
+impl Monitor {
+    /// Amount of newly allocated memory below which skew is not reported, as
+    /// it is unlikely to matter and easily caused by background activity
+    const MIN_SKEW_ALLOCATION: u64 = 256 * 1024 * 1024;
+
+    /// Fraction of newly allocated memory coming from a single node, beyond
+    /// which allocation is considered skewed
+    const SKEW_THRESHOLD: f32 = 0.9;
+
+    /// Set up NUMA memory monitoring, or return None on non-NUMA systems
+    pub fn new() -> io::Result<Option<Self>> {
+        let nodes = Host::numa_node_memory()?;
+        if nodes.len() < 2 {
+            return Ok(None);
+        }
+        Ok(Some(Self {
+            baseline: NumaUsage { nodes },
+            reported_skew: false,
+        }))
+    }
+
+    /// Report the current memory usage of each NUMA node
+    pub fn node_usage(&self) -> io::Result<NumaUsage> {
+        Ok(NumaUsage {
+            nodes: Host::numa_node_memory()?,
+        })
+    }
+
+    /// Warn if the memory that was allocated since monitoring started mostly
+    /// comes from a single NUMA node
+    ///
+    /// This is only reported once, to avoid flooding the logs.
+    ///
+    pub fn check_skew(&mut self, log: &Logger, usage: &NumaUsage) {
+        if self.reported_skew {
+            return;
+        }
+
+        // Compute how much memory was allocated on each node (freed memory is
+        // not counted, as it does not tell where the benchmark's data lives)
+        let allocated = usage
+            .nodes
+            .iter()
+            .map(|node| {
+                let baseline = self
+                    .baseline
+                    .nodes
+                    .iter()
+                    .find(|baseline| baseline.node == node.node)
+                    .map(|baseline| baseline.used.get::<byte>())
+                    .unwrap_or(0);
+                (node.node, node.used.get::<byte>().saturating_sub(baseline))
+            })
+            .collect::<Vec<_>>();
+        let total_allocated = allocated.iter().map(|&(_, bytes)| bytes).sum::<u64>();
+        if total_allocated < Self::MIN_SKEW_ALLOCATION {
+            return;
+        }
+
+        // Check if a single node received most of those allocations
+        let (node, node_allocated) = allocated
+            .iter()
+            .copied()
+            .max_by_key(|&(_, bytes)| bytes)
+            .expect("There should be multiple NUMA nodes");
+        let node_share = node_allocated as f32 / total_allocated as f32;
+        if node_share > Self::SKEW_THRESHOLD {
+            self.reported_skew = true;
+            let total_allocated = Information::new::<byte>(total_allocated);
+            warn!(log, "Memory allocations are heavily skewed towards one NUMA \
+                        node, this often indicates a first-touch placement \
+                        problem";
+                  "node" => node,
+                  "allocated memory" => %format::display_information(total_allocated),
+                  "node share (%)" => node_share * 100.0,
+                  "warning" => BenchmonWarning::NumaImbalance);
+        }
+    }
+}
+
+/// Tell why NUMA memory monitoring will not be performed, if it was requested
+pub fn report_unavailable(log: &Logger, error: Option<io::Error>) {
+    match error {
+        Some(err) => {
+            warn!(log, "NUMA memory information is unavailable, it will not \
+                        be monitored";
+                  "error" => %err,
+                  "warning" => BenchmonWarning::ProbeFailed);
+        }
+        None => {
+            info!(
+                log,
+                "This is not a NUMA system, per-node memory usage will not be monitored"
+            );
+        }
+    }
+}
@@ -1,9 +1,60 @@
+use crate::{
+    platform::{Host, Platform as _},
+    warning::BenchmonWarning,
+};
+
 use heim::{host::Platform, virt::Virtualization};
 
-use slog::{info, warn, Logger};
+use slog::{debug, info, warn, Logger};
+
+use std::io;
+
+/// System load averages, i.e. the average number of runnable (and, on Linux,
+/// uninterruptible) tasks over various time windows
+#[derive(Clone, Copy)]
+pub struct LoadAverage {
+    /// Load average over the last minute
+    pub one: f64,
+
+    /// Load average over the last 5 minutes
+    pub five: f64,
+
+    /// Load average over the last 15 minutes
+    pub fifteen: f64,
+}
+
+/// Hardware identity of the machine, as reported by the firmware (SMBIOS/DMI)
+pub struct SystemIdentity {
+    /// Manufacturer of the machine, if known
+    pub vendor: Option<String>,
+
+    /// Product name of the machine, if known
+    pub product: Option<String>,
+
+    /// Serial number of the machine, if known (usually requires superuser
+    /// privileges)
+    pub serial: Option<String>,
+
+    /// Version of the BIOS/UEFI firmware, if known
+    pub bios_version: Option<String>,
+
+    /// Release date of the BIOS/UEFI firmware, if known
+    pub bios_date: Option<String>,
+}
 
 /// Report on the host's operating system and use of virtualization
-pub fn startup_report(log: &Logger, platform: Platform, virt: Option<Virtualization>) {
+///
+/// The kernel boot parameters whose name is listed in `boot_params` are
+/// highlighted, as they are relevant to benchmarking (CPU isolation, security
+/// mitigations, frequency scaling...).
+///
+pub fn startup_report(
+    log: &Logger,
+    platform: Platform,
+    virt: Option<Virtualization>,
+    boot_params: &[String],
+    logical_cpus: u64,
+) {
     info!(
         log,
         "Received host OS information";
@@ -13,12 +64,93 @@ pub fn startup_report(log: &Logger, platform: Platform, virt: Option<Virtualizat
         "OS version" => platform.version()
     );
 
+    // NOTE: heim does not support load averages yet, so we query them using
+    //       platform-specific code.
+    match Host::load_average() {
+        Ok(load) => {
+            info!(log, "Received system load averages";
+                  "1min" => load.one,
+                  "5min" => load.five,
+                  "15min" => load.fifteen);
+            if load.one > logical_cpus as f64 {
+                warn!(log, "The system is already busy before monitoring \
+                            starts, benchmarks will compete with other tasks";
+                      "1min load average" => load.one,
+                      "logical CPU cores" => logical_cpus,
+                      "warning" => BenchmonWarning::HighLoad);
+            }
+        }
+        Err(err) => {
+            debug!(log, "System load averages are unavailable"; "error" => %err);
+        }
+    }
+
+    match Host::kernel_command_line() {
+        Ok(cmdline) => {
+            debug!(log, "Received kernel command line"; "command line" => &cmdline);
+
+            // Parameters are either flags ("nohz_full") or key-value pairs
+            // ("isolcpus=2-3"), and later occurences take precedence.
+            //
+            // NOTE: This does not handle quoted parameter values containing
+            //       spaces, which are very rare in practice.
+            //
+            for param in boot_params {
+                let value = cmdline
+                    .split_whitespace()
+                    .filter_map(|arg| {
+                        let mut key_value = arg.splitn(2, '=');
+                        if key_value.next()? == param {
+                            Some(key_value.next().unwrap_or("present"))
+                        } else {
+                            None
+                        }
+                    })
+                    .last()
+                    .unwrap_or("absent");
+                info!(log, "Checked a kernel boot parameter";
+                      "parameter" => param,
+                      "value" => value);
+            }
+        }
+        Err(err) => {
+            debug!(log, "Kernel command line is unavailable"; "error" => %err);
+        }
+    }
+
     if let Some(virt) = virt {
         warn!(
             log,
             "Found underlying virtualization layers, make sure that they don't \
              bias your benchmarks!";
-            "detected virtualization scheme" => ?virt
+            "detected virtualization scheme" => ?virt,
+            "warning" => BenchmonWarning::Virtualization
         );
     }
 }
+
+/// Report on the machine's hardware identity
+///
+/// The serial number is only reported if `include_serials` is true, as it
+/// uniquely identifies the machine, which may be a privacy concern.
+///
+pub fn identity_report(log: &Logger, identity: io::Result<SystemIdentity>, include_serials: bool) {
+    match identity {
+        Ok(identity) => {
+            let serial = if include_serials {
+                identity.serial.as_ref()
+            } else {
+                None
+            };
+            info!(log, "Received hardware identity";
+                  "vendor" => &identity.vendor,
+                  "product" => &identity.product,
+                  "serial number" => serial,
+                  "BIOS version" => &identity.bios_version,
+                  "BIOS date" => &identity.bios_date);
+        }
+        Err(err) => {
+            debug!(log, "Hardware identity is unavailable"; "error" => %err);
+        }
+    }
+}
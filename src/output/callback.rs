@@ -0,0 +1,41 @@
+//! Custom per-sample processing through a user-provided closure
+
+use crate::output::{Sample, SampleSink};
+
+use std::io;
+
+/// Sample sink which hands each sample over to a user-provided closure
+///
+/// This lets custom logic (e.g. pushing measurements to another metrics
+/// system) run on every tick without implementing a full `SampleSink`.
+///
+/// The closure is called synchronously by the monitoring loop, so it should be
+/// cheap: any time spent in it delays the next sample and shows up as sampling
+/// jitter. Expensive processing should be offloaded to another thread, for
+/// example by sending the sample through a channel.
+///
+// TODO: Once benchmon's core is usable as a library, also expose the
+//       monitoring loop as an async `Stream` of samples.
+pub struct Callback<F: FnMut(&Sample)>(F);
+
+impl<F: FnMut(&Sample)> Callback<F> {
+    /// Set up a sink which calls `callback` on every sample
+    pub fn new(callback: F) -> Self {
+        Self(callback)
+    }
+}
+
+impl<F: FnMut(&Sample)> SampleSink for Callback<F> {
+    fn write_header(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn write_sample(&mut self, sample: &Sample) -> io::Result<()> {
+        (self.0)(sample);
+        Ok(())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
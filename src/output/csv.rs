@@ -0,0 +1,366 @@
+//! Machine-readable recording of periodic measurements into CSV files
+
+use crate::{
+    cpu::RawCounters,
+    output::{titles, Columns, FileWriter, Sample, SampleSink, SyncPolicy},
+    psi::Resource,
+    warning::WarningQueue,
+};
+
+use heim::units::{frequency::megahertz, information::byte, time::second, Information, Time};
+
+use std::{
+    fmt::Display,
+    fs::File,
+    io::{self, BufRead, BufReader, Write},
+    path::Path,
+};
+
+/// CSV file of measurements, with one row per sample
+///
+/// Unlike the stdout table, which is meant for human consumption, the CSV
+/// output is meant to be analyzed by other programs. So it uses a fixed
+/// RFC 3339 timestamp format (complemented with the monotonic time elapsed
+/// since monitoring started, in nanoseconds, which is immune to wall clock
+/// adjustments), it records full-precision measurements without smoothing,
+/// and failed measurements are left empty. Measurement gaps are recorded in a
+/// dedicated column, which holds the duration of the gap (in seconds) before
+/// the samples that follow one and is empty otherwise, so that analysis tools
+/// do not interpolate across missing data. Network and disk totals, if enabled,
+/// are recorded in bytes. Raw counters, if enabled, are cumulative since boot.
+///
+/// Each row is flushed to the OS once written, so that a partial file remains
+/// valid if benchmon is killed, and synced to storage according to the
+/// selected `SyncPolicy`.
+///
+/// Optionally, benchmon's warnings are recorded as "#"-prefixed comment lines,
+/// in between the rows that were emitted before and after them.
+///
+pub struct CsvFile {
+    /// Output file
+    writer: FileWriter,
+
+    /// Columns to be emitted
+    columns: Columns,
+
+    /// Truth that a header row should be written (it is omitted when appending
+    /// to a non-empty file, which already has one)
+    needs_header: bool,
+
+    /// Warnings to be recorded as comments, if enabled
+    warnings: Option<WarningQueue>,
+}
+
+impl CsvFile {
+    /// Set up CSV output into a certain file
+    ///
+    /// If `append` is true, rows are appended to the file if it already
+    /// exists, otherwise it is an error for the file to exist. It is also an
+    /// error to append to a file whose header row does not match the columns
+    /// to be emitted. A path of "-" stands for stdout.
+    ///
+    /// If a `WarningQueue` is specified, the warnings which are recorded there
+    /// are written as comments.
+    ///
+    pub fn new(
+        path: &Path,
+        append: bool,
+        sync_policy: SyncPolicy,
+        columns: &Columns,
+        warnings: Option<WarningQueue>,
+    ) -> io::Result<Self> {
+        let writer = if path == Path::new("-") {
+            FileWriter::stdout()
+        } else if append {
+            match File::open(path) {
+                Ok(file) => {
+                    check_header(BufReader::new(file), &header(columns)).map_err(|err| {
+                        io::Error::new(err.kind(), format!("{}: {}", path.display(), err))
+                    })?
+                }
+                Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+                Err(err) => return Err(err),
+            }
+            FileWriter::append(path, sync_policy)?
+        } else {
+            FileWriter::create_new(path, sync_policy)?
+        };
+        Ok(Self {
+            needs_header: writer.is_empty()?,
+            writer,
+            columns: columns.clone(),
+            warnings,
+        })
+    }
+
+    /// Write the warnings which were logged since the last call as comments
+    fn write_warnings(&mut self) -> io::Result<()> {
+        let warnings = match &self.warnings {
+            Some(warnings) => warnings.take(),
+            None => return Ok(()),
+        };
+        for warning in warnings {
+            writeln!(
+                self.writer,
+                "# {} {}: {}",
+                warning.time.to_rfc3339(),
+                warning.code,
+                warning.message.replace('\n', " ")
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Number of bytes in a mebibyte
+const MIB: f64 = 1024.0 * 1024.0;
+
+/// Build a CSV row out of a set of fields
+fn write_row(output: &mut impl Write, fields: &[String]) -> io::Result<()> {
+    writeln!(output, "{}", fields.join(","))
+}
+
+/// Display an optional numeric field, which is left empty if unknown
+fn optional(value: Option<impl Display>) -> String {
+    value.map(|value| value.to_string()).unwrap_or_default()
+}
+
+/// Quote a free-form text field, doubling the quotes inside of it
+fn quoted(text: &str) -> String {
+    format!("\"{}\"", text.replace('"', "\"\""))
+}
+
+/// Column titles of the header row
+fn header(columns: &Columns) -> Vec<&'static str> {
+    let mut header = vec!["time", "elapsed_ns", "gap_s"];
+    if columns.idle {
+        header.push(titles::IDLE);
+    }
+    #[cfg(target_os = "linux")]
+    {
+        if columns.io_wait {
+            header.push(titles::IO_WAIT);
+        }
+    }
+    if columns.busiest_core {
+        header.push(titles::BUSIEST_CORE);
+    }
+    if columns.pressure {
+        header.extend_from_slice(&titles::PRESSURE);
+    }
+    if columns.memory {
+        header.extend_from_slice(&[titles::MEMORY, titles::SWAP]);
+    }
+    if columns.numa {
+        header.push(titles::NUMA);
+    }
+    if columns.freq_spread {
+        header.extend_from_slice(&[titles::FREQ_MIN, titles::FREQ_MAX]);
+    }
+    if columns.relative_freq {
+        header.push(titles::RELATIVE_FREQ);
+    }
+    if columns.involuntary_ctx_switches {
+        header.push(titles::INVOLUNTARY_CTX_SWITCHES);
+    }
+    if columns.net {
+        header.push(titles::NET);
+    }
+    if columns.ecc {
+        header.push(titles::ECC);
+    }
+    if columns.thermal_headroom {
+        header.push(titles::THERMAL_HEADROOM);
+    }
+    if columns.jitter {
+        header.push(titles::JITTER);
+    }
+    if columns.cpu_seconds {
+        header.push(titles::CPU_SECONDS);
+    }
+    if columns.net_totals {
+        header.extend_from_slice(&titles::NET_TOTALS);
+    }
+    if columns.disk_totals {
+        header.extend_from_slice(&titles::DISK_TOTALS);
+    }
+    if columns.raw_counters {
+        header.extend_from_slice(&titles::RAW_CPU_COUNTERS);
+        header.extend_from_slice(&titles::RAW_NET_COUNTERS);
+    }
+    if columns.marker {
+        header.push(titles::MARKER);
+    }
+    header
+}
+
+/// Check that the first line of an existing CSV file, if any, is the header row
+/// of the columns that are about to be appended to it
+fn check_header(mut existing: impl BufRead, header: &[&str]) -> io::Result<()> {
+    let mut first_line = String::new();
+    existing.read_line(&mut first_line)?;
+    let first_line = first_line.trim_end_matches(&['\r', '\n'][..]);
+    let header = header.join(",");
+    if first_line.is_empty() || first_line == header {
+        Ok(())
+    } else {
+        Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "cannot append to a CSV file with different columns (existing: {}, new: {})",
+                first_line, header
+            ),
+        ))
+    }
+}
+
+impl SampleSink for CsvFile {
+    fn write_header(&mut self) -> io::Result<()> {
+        if !self.needs_header {
+            return Ok(());
+        }
+        let header = header(&self.columns)
+            .into_iter()
+            .map(String::from)
+            .collect::<Vec<_>>();
+        write_row(&mut self.writer, &header)?;
+        self.needs_header = false;
+        Ok(())
+    }
+
+    fn write_sample(&mut self, sample: &Sample) -> io::Result<()> {
+        self.write_warnings()?;
+        let columns = &self.columns;
+        let percent = |frac: f32| (frac * 100.0).to_string();
+        let mut row = vec![
+            sample.local_time.to_rfc3339(),
+            sample.elapsed.as_nanos().to_string(),
+            optional(sample.gap.map(|gap| gap.as_secs_f64())),
+        ];
+        if columns.idle {
+            row.push(percent(sample.cpu_time.idle_frac));
+        }
+        #[cfg(target_os = "linux")]
+        {
+            if columns.io_wait {
+                row.push(percent(sample.cpu_time.linux_fracs.io_wait_frac));
+            }
+        }
+        if columns.busiest_core {
+            row.push(optional(sample.busiest_core.map(|frac| frac * 100.0)));
+        }
+        if columns.pressure {
+            for &resource in &Resource::ALL {
+                let pressure = sample
+                    .pressure
+                    .as_ref()
+                    .map(|pressure| pressure.get(resource));
+                row.push(optional(pressure.map(|frac| frac * 100.0)));
+            }
+        }
+        if columns.memory {
+            let memory = sample.memory.as_ref();
+            let mib = |information: Information| information.get::<byte>() as f64 / MIB;
+            row.push(optional(memory.map(|memory| mib(memory.ram_used))));
+            row.push(optional(memory.map(|memory| mib(memory.swap_used))));
+        }
+        if columns.numa {
+            let numa = sample.numa.as_ref().map(|numa| numa.max_usage_frac());
+            row.push(optional(numa.map(|frac| frac * 100.0)));
+        }
+        if columns.freq_spread {
+            let spread = sample.freq_spread.as_ref();
+            row.push(optional(spread.map(|spread| spread.min.get::<megahertz>())));
+            row.push(optional(spread.map(|spread| spread.max.get::<megahertz>())));
+        }
+        if columns.relative_freq {
+            row.push(optional(sample.relative_freq.map(|frac| frac * 100.0)));
+        }
+        if columns.involuntary_ctx_switches {
+            row.push(optional(sample.involuntary_ctx_switch_rate));
+        }
+        if columns.net {
+            let net_kib_per_sec = sample.net_throughput.map(|throughput| throughput / 1024.0);
+            row.push(optional(net_kib_per_sec));
+        }
+        if columns.ecc {
+            row.push(optional(sample.ecc_errors));
+        }
+        if columns.thermal_headroom {
+            row.push(optional(sample.thermal_headroom.map(|frac| frac * 100.0)));
+        }
+        if columns.jitter {
+            row.push(optional(sample.jitter.map(|jitter| jitter.as_micros())));
+        }
+        if columns.cpu_seconds {
+            row.push(optional(
+                sample.cpu_seconds.map(|cpu_time| cpu_time.as_secs_f64()),
+            ));
+        }
+        let bytes = |information: Information| information.get::<byte>();
+        if columns.net_totals {
+            let totals = sample.net_totals;
+            row.push(optional(totals.map(|(received, _sent)| bytes(received))));
+            row.push(optional(totals.map(|(_received, sent)| bytes(sent))));
+        }
+        if columns.disk_totals {
+            let totals = sample.disk_totals;
+            row.push(optional(totals.map(|(read, _written)| bytes(read))));
+            row.push(optional(totals.map(|(_read, written)| bytes(written))));
+        }
+        if columns.raw_counters {
+            let cpu = sample.raw_cpu_counters.as_ref();
+            let secs =
+                |time: fn(&RawCounters) -> Time| optional(cpu.map(|cpu| time(cpu).get::<second>()));
+            row.push(secs(|cpu| cpu.user));
+            row.push(secs(|cpu| cpu.system));
+            row.push(secs(|cpu| cpu.idle));
+            #[cfg(target_os = "linux")]
+            {
+                row.push(secs(|cpu| cpu.nice));
+                row.push(secs(|cpu| cpu.io_wait));
+                row.push(secs(|cpu| cpu.steal));
+            }
+            row.push(optional(cpu.map(|cpu| cpu.ctx_switches)));
+            row.push(optional(cpu.map(|cpu| cpu.interrupts)));
+            let net = sample.raw_net_counters.as_ref();
+            row.push(optional(net.map(|net| net.bytes_recv)));
+            row.push(optional(net.map(|net| net.bytes_sent)));
+        }
+        if columns.marker {
+            row.push(sample.marker.as_deref().map(quoted).unwrap_or_default());
+        }
+        write_row(&mut self.writer, &row)?;
+        self.writer.end_sample()
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.write_warnings()?;
+        self.writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn append_header_check() {
+        let columns = Columns {
+            idle: true,
+            ..Columns::default()
+        };
+        let header = header(&columns);
+        assert_eq!(header, ["time", "elapsed_ns", "gap_s", titles::IDLE]);
+
+        // Empty files and files with the same columns can be appended to
+        assert!(check_header(&b""[..], &header).is_ok());
+        assert!(check_header(&b"time,elapsed_ns,gap_s,idle%\n1,2,,3\n"[..], &header).is_ok());
+        assert!(check_header(&b"time,elapsed_ns,gap_s,idle%\r\n"[..], &header).is_ok());
+
+        // Files with other columns cannot
+        let err = check_header(&b"time,elapsed_ns,gap_s\n1,2,\n"[..], &header).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(check_header(&b"time,elapsed_ns,gap_s,idle%,marker\n"[..], &header).is_err());
+    }
+}
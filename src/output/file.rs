@@ -0,0 +1,173 @@
+//! Durable writing of measurements into files
+
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, BufWriter, Write},
+    path::Path,
+    str::FromStr,
+    time::{Duration, Instant},
+};
+
+/// How aggressively file output is synced to storage
+///
+/// Syncing often guarantees that little data is lost if the machine crashes
+/// during a benchmark, but it adds I/O latency which can perturb I/O-sensitive
+/// benchmarks.
+///
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SyncPolicy {
+    /// Rely on OS buffering, only flush our own buffers on shutdown
+    Never,
+
+    /// Sync after every sample, so that no data is lost on crash
+    PerSample,
+
+    /// Sync at most once per time interval
+    Interval(Duration),
+}
+
+impl SyncPolicy {
+    /// Interval between two syncs in `Interval` mode, if not specified
+    const DEFAULT_INTERVAL: Duration = Duration::from_secs(10);
+}
+
+impl Default for SyncPolicy {
+    fn default() -> Self {
+        SyncPolicy::Never
+    }
+}
+
+impl FromStr for SyncPolicy {
+    type Err = String;
+
+    /// Parse "never", "per-sample", "interval" or "interval=<seconds>"
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut name_and_param = s.splitn(2, '=');
+        match (name_and_param.next(), name_and_param.next()) {
+            (Some("never"), None) => Ok(SyncPolicy::Never),
+            (Some("per-sample"), None) => Ok(SyncPolicy::PerSample),
+            (Some("interval"), None) => Ok(SyncPolicy::Interval(Self::DEFAULT_INTERVAL)),
+            (Some("interval"), Some(secs)) => secs
+                .parse::<f64>()
+                .ok()
+                .filter(|secs| *secs > 0.0)
+                .map(|secs| SyncPolicy::Interval(Duration::from_secs_f64(secs)))
+                .ok_or_else(|| format!("Invalid fsync interval \"{}\"", secs)),
+            _ => Err(format!("Unknown fsync policy \"{}\"", s)),
+        }
+    }
+}
+
+/// Destination of a `FileWriter`
+enum Destination {
+    /// Regular file
+    File(File),
+
+    /// Standard output, which is used as a file by pipelines
+    Stdout(io::Stdout),
+}
+
+impl Write for Destination {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Destination::File(file) => file.write(buf),
+            Destination::Stdout(stdout) => stdout.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Destination::File(file) => file.flush(),
+            Destination::Stdout(stdout) => stdout.flush(),
+        }
+    }
+}
+
+/// Buffered file writer which syncs its output according to a `SyncPolicy`
+///
+/// File-based sample sinks should write through this, and call `end_sample()`
+/// after writing each sample. Buffers are flushed when the writer is dropped.
+///
+/// The writer may also target stdout, which is then treated as an initially
+/// empty file that cannot be synced to storage.
+///
+pub struct FileWriter {
+    /// Buffered handle to the output file
+    file: BufWriter<Destination>,
+
+    /// Sync policy
+    sync_policy: SyncPolicy,
+
+    /// Time of the last sync
+    last_sync: Instant,
+}
+
+impl FileWriter {
+    /// Create an output file, failing if it already exists
+    pub fn create_new(path: &Path, sync_policy: SyncPolicy) -> io::Result<Self> {
+        let file = OpenOptions::new().write(true).create_new(true).open(path)?;
+        Ok(Self::new(Destination::File(file), sync_policy))
+    }
+
+    /// Open an output file for appending, creating it if needed
+    pub fn append(path: &Path, sync_policy: SyncPolicy) -> io::Result<Self> {
+        let file = OpenOptions::new().append(true).create(true).open(path)?;
+        Ok(Self::new(Destination::File(file), sync_policy))
+    }
+
+    /// Write to stdout
+    pub fn stdout() -> Self {
+        Self::new(Destination::Stdout(io::stdout()), SyncPolicy::Never)
+    }
+
+    /// Truth that nothing was written to the output file yet
+    pub fn is_empty(&self) -> io::Result<bool> {
+        let file_is_empty = match self.file.get_ref() {
+            Destination::File(file) => file.metadata()?.len() == 0,
+            Destination::Stdout(_) => true,
+        };
+        Ok(file_is_empty && self.file.buffer().is_empty())
+    }
+
+    /// Wrap an open output file
+    fn new(file: Destination, sync_policy: SyncPolicy) -> Self {
+        Self {
+            file: BufWriter::new(file),
+            sync_policy,
+            last_sync: Instant::now(),
+        }
+    }
+
+    /// Signal that a sample was fully written, and sync if the policy says so
+    pub fn end_sample(&mut self) -> io::Result<()> {
+        let should_sync = match self.sync_policy {
+            SyncPolicy::Never => false,
+            SyncPolicy::PerSample => true,
+            SyncPolicy::Interval(interval) => self.last_sync.elapsed() >= interval,
+        };
+        if should_sync {
+            self.sync()?;
+        }
+        Ok(())
+    }
+
+    /// Flush our buffers and ask the OS to persist the file contents
+    pub fn sync(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        if let Destination::File(file) = self.file.get_ref() {
+            file.sync_data()?;
+        }
+        self.last_sync = Instant::now();
+        Ok(())
+    }
+}
+
+impl Write for FileWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.file.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
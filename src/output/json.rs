@@ -0,0 +1,311 @@
+//! Machine-readable JSON representation of periodic measurements
+
+use crate::{
+    output::{FileWriter, Sample, SampleSink, SyncPolicy},
+    psi::Resource,
+};
+
+use heim::units::{frequency::megahertz, information::byte, time::second};
+
+use serde::{Deserialize, Serialize};
+
+use std::{
+    io::{self, Write},
+    path::Path,
+};
+
+/// Plain-data version of a `Sample`, which can be serialized as JSON
+///
+/// Every measurement is recorded at full precision in a fixed unit (given by
+/// the field name suffix), and measurements which were not taken are `null`.
+///
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct SampleRecord {
+    /// Local date and time of the sample, in RFC 3339 format
+    pub time: String,
+
+    /// Monotonic time elapsed since monitoring started, in nanoseconds
+    pub elapsed_ns: u64,
+
+    /// Fraction of CPU time spent in user mode processes, in percent
+    pub user_pct: f32,
+
+    /// Fraction of CPU time spent in kernel mode processes, in percent
+    pub system_pct: f32,
+
+    /// Fraction of CPU time spent idle, in percent
+    pub idle_pct: f32,
+
+    /// Fraction of CPU time spent waiting for I/O, in percent (Linux-only)
+    pub iowait_pct: Option<f32>,
+
+    /// Utilization of the busiest logical CPU core, in percent
+    pub busiest_core_pct: Option<f32>,
+
+    /// CPU pressure, in percent
+    pub cpu_psi_pct: Option<f32>,
+
+    /// Memory pressure, in percent
+    pub mem_psi_pct: Option<f32>,
+
+    /// I/O pressure, in percent
+    pub io_psi_pct: Option<f32>,
+
+    /// RAM in use, in MiB
+    pub mem_mib: Option<f64>,
+
+    /// Swap in use, in MiB
+    pub swap_mib: Option<f64>,
+
+    /// Memory usage of the most loaded NUMA node, in percent
+    pub numa_max_pct: Option<f32>,
+
+    /// Frequency of the slowest CPU core, in MHz
+    pub freq_min_mhz: Option<u64>,
+
+    /// Frequency of the fastest CPU core, in MHz
+    pub freq_max_mhz: Option<u64>,
+
+    /// Lowest frequency of a CPU core relative to its frequency range, in
+    /// percent
+    pub freq_rel_pct: Option<f32>,
+
+    /// Involuntary context switches of the tracked process, per second
+    pub invcsw_per_sec: Option<f64>,
+
+    /// Total network throughput, in bytes per second
+    pub net_bytes_per_sec: Option<f64>,
+
+    /// Corrected memory errors since monitoring started
+    pub ecc_ce: Option<u64>,
+
+    /// Highest thermal headroom used by a temperature sensor, in percent
+    pub thermal_headroom_pct: Option<f32>,
+
+    /// Sampling jitter, in microseconds
+    pub jitter_us: Option<u64>,
+
+    /// Non-idle CPU time consumed since monitoring started, in seconds
+    pub cpu_s: Option<f64>,
+
+    /// Bytes received by non-loopback network interfaces since monitoring
+    /// started
+    pub net_rx_bytes: Option<u64>,
+
+    /// Bytes sent by non-loopback network interfaces since monitoring started
+    pub net_tx_bytes: Option<u64>,
+
+    /// Bytes read by physical disks since monitoring started
+    pub disk_read_bytes: Option<u64>,
+
+    /// Bytes written by physical disks since monitoring started
+    pub disk_write_bytes: Option<u64>,
+
+    /// Measurement gap before this sample, in seconds
+    pub gap_s: Option<f64>,
+
+    /// CPU time spent in user mode processes since boot, in seconds
+    pub raw_user_s: Option<f64>,
+
+    /// CPU time spent in kernel mode processes since boot, in seconds
+    pub raw_system_s: Option<f64>,
+
+    /// CPU time spent idle since boot, in seconds
+    pub raw_idle_s: Option<f64>,
+
+    /// CPU time spent in niced user mode processes since boot, in seconds
+    /// (Linux-only)
+    pub raw_nice_s: Option<f64>,
+
+    /// CPU time spent waiting for I/O since boot, in seconds (Linux-only)
+    pub raw_iowait_s: Option<f64>,
+
+    /// CPU time stolen by the hypervisor since boot, in seconds (Linux-only)
+    pub raw_steal_s: Option<f64>,
+
+    /// Context switches since boot
+    pub raw_ctx_switches: Option<u64>,
+
+    /// Interrupts since boot
+    pub raw_interrupts: Option<u64>,
+
+    /// Bytes received by non-loopback network interfaces since boot
+    pub raw_net_recv_bytes: Option<u64>,
+
+    /// Bytes sent by non-loopback network interfaces since boot
+    pub raw_net_sent_bytes: Option<u64>,
+
+    /// User-provided marker
+    pub marker: Option<String>,
+}
+
+impl SampleRecord {
+    /// Names of the fields, in declaration order, for tabular exports
+    pub const FIELDS: &'static [&'static str] = &[
+        "time",
+        "elapsed_ns",
+        "user_pct",
+        "system_pct",
+        "idle_pct",
+        "iowait_pct",
+        "busiest_core_pct",
+        "cpu_psi_pct",
+        "mem_psi_pct",
+        "io_psi_pct",
+        "mem_mib",
+        "swap_mib",
+        "numa_max_pct",
+        "freq_min_mhz",
+        "freq_max_mhz",
+        "freq_rel_pct",
+        "invcsw_per_sec",
+        "net_bytes_per_sec",
+        "ecc_ce",
+        "thermal_headroom_pct",
+        "jitter_us",
+        "cpu_s",
+        "net_rx_bytes",
+        "net_tx_bytes",
+        "disk_read_bytes",
+        "disk_write_bytes",
+        "gap_s",
+        "raw_user_s",
+        "raw_system_s",
+        "raw_idle_s",
+        "raw_nice_s",
+        "raw_iowait_s",
+        "raw_steal_s",
+        "raw_ctx_switches",
+        "raw_interrupts",
+        "raw_net_recv_bytes",
+        "raw_net_sent_bytes",
+        "marker",
+    ];
+
+    /// Extract the plain data of a sample
+    pub fn new(sample: &Sample) -> Self {
+        const MIB: f64 = 1024.0 * 1024.0;
+        let percent = |frac: f32| frac * 100.0;
+        let cpu_time = &sample.cpu_time;
+        let pressure = |resource| {
+            let pressure = sample.pressure.as_ref()?;
+            Some(percent(pressure.get(resource)))
+        };
+        let memory = sample.memory.as_ref();
+        let spread = sample.freq_spread.as_ref();
+        let raw_cpu = sample.raw_cpu_counters.as_ref();
+        let raw_net = sample.raw_net_counters.as_ref();
+        let net_totals = sample.net_totals;
+        let disk_totals = sample.disk_totals;
+        Self {
+            time: sample.local_time.to_rfc3339(),
+            elapsed_ns: sample.elapsed.as_nanos() as u64,
+            user_pct: percent(cpu_time.user_frac),
+            system_pct: percent(cpu_time.system_frac),
+            idle_pct: percent(cpu_time.idle_frac),
+            #[cfg(target_os = "linux")]
+            iowait_pct: Some(percent(cpu_time.linux_fracs.io_wait_frac)),
+            #[cfg(not(target_os = "linux"))]
+            iowait_pct: None,
+            busiest_core_pct: sample.busiest_core.map(percent),
+            cpu_psi_pct: pressure(Resource::Cpu),
+            mem_psi_pct: pressure(Resource::Memory),
+            io_psi_pct: pressure(Resource::Io),
+            mem_mib: memory.map(|memory| memory.ram_used.get::<byte>() as f64 / MIB),
+            swap_mib: memory.map(|memory| memory.swap_used.get::<byte>() as f64 / MIB),
+            numa_max_pct: sample
+                .numa
+                .as_ref()
+                .map(|numa| percent(numa.max_usage_frac())),
+            freq_min_mhz: spread.map(|spread| spread.min.get::<megahertz>()),
+            freq_max_mhz: spread.map(|spread| spread.max.get::<megahertz>()),
+            freq_rel_pct: sample.relative_freq.map(percent),
+            invcsw_per_sec: sample.involuntary_ctx_switch_rate,
+            net_bytes_per_sec: sample.net_throughput,
+            ecc_ce: sample.ecc_errors,
+            thermal_headroom_pct: sample.thermal_headroom.map(percent),
+            jitter_us: sample.jitter.map(|jitter| jitter.as_micros() as u64),
+            cpu_s: sample.cpu_seconds.map(|cpu_time| cpu_time.as_secs_f64()),
+            net_rx_bytes: net_totals.map(|(received, _sent)| received.get::<byte>()),
+            net_tx_bytes: net_totals.map(|(_received, sent)| sent.get::<byte>()),
+            disk_read_bytes: disk_totals.map(|(read, _written)| read.get::<byte>()),
+            disk_write_bytes: disk_totals.map(|(_read, written)| written.get::<byte>()),
+            gap_s: sample.gap.map(|gap| gap.as_secs_f64()),
+            raw_user_s: raw_cpu.map(|cpu| cpu.user.get::<second>()),
+            raw_system_s: raw_cpu.map(|cpu| cpu.system.get::<second>()),
+            raw_idle_s: raw_cpu.map(|cpu| cpu.idle.get::<second>()),
+            #[cfg(target_os = "linux")]
+            raw_nice_s: raw_cpu.map(|cpu| cpu.nice.get::<second>()),
+            #[cfg(not(target_os = "linux"))]
+            raw_nice_s: None,
+            #[cfg(target_os = "linux")]
+            raw_iowait_s: raw_cpu.map(|cpu| cpu.io_wait.get::<second>()),
+            #[cfg(not(target_os = "linux"))]
+            raw_iowait_s: None,
+            #[cfg(target_os = "linux")]
+            raw_steal_s: raw_cpu.map(|cpu| cpu.steal.get::<second>()),
+            #[cfg(not(target_os = "linux"))]
+            raw_steal_s: None,
+            raw_ctx_switches: raw_cpu.map(|cpu| cpu.ctx_switches),
+            raw_interrupts: raw_cpu.map(|cpu| cpu.interrupts),
+            raw_net_recv_bytes: raw_net.map(|net| net.bytes_recv),
+            raw_net_sent_bytes: raw_net.map(|net| net.bytes_sent),
+            marker: sample.marker.clone(),
+        }
+    }
+}
+
+/// JSON Lines file of measurements, with one `SampleRecord` object per line
+///
+/// Like the CSV output, this is meant to be analyzed by other programs, and
+/// each line is flushed to the OS once written so that a partial file remains
+/// valid if benchmon is killed.
+///
+pub struct JsonLinesFile {
+    /// Output file
+    writer: FileWriter,
+}
+
+impl JsonLinesFile {
+    /// Set up JSON Lines output into a certain file, which must not exist yet
+    /// (a path of "-" stands for stdout)
+    pub fn new(path: &Path, sync_policy: SyncPolicy) -> io::Result<Self> {
+        let writer = if path == Path::new("-") {
+            FileWriter::stdout()
+        } else {
+            FileWriter::create_new(path, sync_policy)?
+        };
+        Ok(Self { writer })
+    }
+}
+
+impl SampleSink for JsonLinesFile {
+    fn write_header(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn write_sample(&mut self, sample: &Sample) -> io::Result<()> {
+        serde_json::to_writer(&mut self.writer, &SampleRecord::new(sample))?;
+        writeln!(self.writer)?;
+        self.writer.end_sample()
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn field_names() {
+        let value = serde_json::to_value(SampleRecord::default()).unwrap();
+        let object = value.as_object().unwrap();
+        assert_eq!(object.len(), SampleRecord::FIELDS.len());
+        for field in SampleRecord::FIELDS {
+            assert!(object.contains_key(*field), "Missing field {}", field);
+        }
+    }
+}
@@ -0,0 +1,200 @@
+//! Output backends for periodic measurements
+
+mod callback;
+mod csv;
+mod file;
+mod json;
+#[cfg(feature = "parquet")]
+mod parquet;
+mod snapshot;
+mod stdout;
+
+pub use self::{
+    callback::Callback,
+    csv::CsvFile,
+    file::{FileWriter, SyncPolicy},
+    json::{JsonLinesFile, SampleRecord},
+    snapshot::Snapshot,
+    stdout::{Columns, StdoutTable},
+};
+
+#[cfg(feature = "parquet")]
+pub use self::parquet::ParquetFile;
+
+use crate::{
+    cpu::{DurationBreakdown, FrequencySpread, RawCounters},
+    memory::MemoryUsage,
+    network::InterfaceCounters,
+    numa::NumaUsage,
+    psi::SomePressure,
+};
+
+use chrono::{DateTime, Local};
+
+use heim::units::Information;
+
+use std::{io, time::Duration};
+
+/// Titles of the measurement columns, which all tabular sinks share
+mod titles {
+    pub const IDLE: &str = "idle%";
+    #[cfg(target_os = "linux")]
+    pub const IO_WAIT: &str = "iowait%";
+    pub const BUSIEST_CORE: &str = "core_max%";
+    pub const PRESSURE: [&str; 3] = ["cpu_psi%", "mem_psi%", "io_psi%"];
+    pub const MEMORY: &str = "mem_MiB";
+    pub const SWAP: &str = "swap_MiB";
+    pub const NUMA: &str = "numa_max%";
+    pub const FREQ_MIN: &str = "freq_min";
+    pub const FREQ_MAX: &str = "freq_max";
+    pub const RELATIVE_FREQ: &str = "freq_rel%";
+    pub const INVOLUNTARY_CTX_SWITCHES: &str = "invcsw/s";
+    pub const NET: &str = "net_KiB/s";
+    pub const ECC: &str = "ecc_ce";
+    pub const THERMAL_HEADROOM: &str = "thermal%";
+    pub const JITTER: &str = "jitter_us";
+    pub const CPU_SECONDS: &str = "cpu_s";
+    pub const NET_TOTALS: [&str; 2] = ["net_rx", "net_tx"];
+    pub const DISK_TOTALS: [&str; 2] = ["disk_read", "disk_write"];
+    #[cfg(target_os = "linux")]
+    pub const RAW_CPU_COUNTERS: [&str; 8] = [
+        "raw_user_s",
+        "raw_system_s",
+        "raw_idle_s",
+        "raw_nice_s",
+        "raw_iowait_s",
+        "raw_steal_s",
+        "raw_ctx_switches",
+        "raw_interrupts",
+    ];
+    #[cfg(not(target_os = "linux"))]
+    pub const RAW_CPU_COUNTERS: [&str; 5] = [
+        "raw_user_s",
+        "raw_system_s",
+        "raw_idle_s",
+        "raw_ctx_switches",
+        "raw_interrupts",
+    ];
+    pub const RAW_NET_COUNTERS: [&str; 2] = ["raw_net_recv_bytes", "raw_net_sent_bytes"];
+    pub const MARKER: &str = "marker";
+}
+
+/// Set of measurements taken at a given point in time
+#[derive(Clone)]
+pub struct Sample {
+    /// Local date and time at which the measurements were taken
+    pub local_time: DateTime<Local>,
+
+    /// Time elapsed since monitoring started, measured using the monotonic
+    /// clock (unlike `local_time`, this is not affected by clock adjustments)
+    pub elapsed: Duration,
+
+    /// Breakdown of the CPU time elapsed since the previous sample
+    pub cpu_time: DurationBreakdown,
+
+    /// Utilization of the busiest logical CPU core since the previous sample
+    /// (if monitored)
+    pub busiest_core: Option<f32>,
+
+    /// Spread of the current CPU frequencies across logical cores (unknown if
+    /// not monitored, or not available on this platform)
+    pub freq_spread: Option<FrequencySpread>,
+
+    /// Lowest current frequency of a logical CPU core, relative to its
+    /// frequency range (see `cpu::Monitor::relative_frequencies()`), unknown
+    /// if not monitored or if no core's relative frequency is known
+    pub relative_freq: Option<f32>,
+
+    /// Delay between the intended and actual time of this sample, measured
+    /// using the monotonic clock (unknown for the first sample)
+    pub jitter: Option<Duration>,
+
+    /// Interval since the previous sample, if it was much longer than the
+    /// sampling period (e.g. because a stalled sample was skipped), in which
+    /// case no data is available for this interval and consumers should not
+    /// interpolate across it
+    pub gap: Option<Duration>,
+
+    /// Non-idle CPU time consumed system-wide since monitoring started (if
+    /// monitored)
+    pub cpu_seconds: Option<Duration>,
+
+    /// Bytes received and sent by non-loopback network interfaces since
+    /// monitoring started (if requested and the network is monitored)
+    pub net_totals: Option<(Information, Information)>,
+
+    /// Bytes read and written by physical disks since monitoring started (if
+    /// requested)
+    pub disk_totals: Option<(Information, Information)>,
+
+    /// Resource pressure since the previous sample (if monitored)
+    pub pressure: Option<SomePressure>,
+
+    /// RAM and swap usage (if monitored)
+    pub memory: Option<MemoryUsage>,
+
+    /// Memory usage of each NUMA node (if monitored)
+    pub numa: Option<NumaUsage>,
+
+    /// Rate of involuntary context switches of the tracked process, in
+    /// switches/second (if a process is tracked)
+    pub involuntary_ctx_switch_rate: Option<f64>,
+
+    /// Total network throughput of non-loopback interfaces since the previous
+    /// sample, in bytes/second (if monitored)
+    pub net_throughput: Option<f64>,
+
+    /// Number of corrected memory errors since monitoring started (if
+    /// monitored)
+    pub ecc_errors: Option<u64>,
+
+    /// Highest thermal headroom used by a temperature sensor, as a fraction
+    /// (see `sensors::SensorReading::headroom_used()`), unknown if not
+    /// monitored or if no sensor has both a high and a critical trip point
+    pub thermal_headroom: Option<f32>,
+
+    /// Raw cumulative CPU counters, which are monotonic since boot (if
+    /// requested)
+    pub raw_cpu_counters: Option<RawCounters>,
+
+    /// Raw traffic counters of non-loopback network interfaces, summed over
+    /// interfaces, which are monotonic since boot (if requested and the
+    /// network is monitored)
+    pub raw_net_counters: Option<InterfaceCounters>,
+
+    /// User-provided marker which was received since the previous sample, if
+    /// any (see the `marker` module)
+    pub marker: Option<String>,
+}
+
+/// Destination for periodic measurements (stdout table, file...)
+///
+/// Every configured sink is fed the same sequence of samples by the main
+/// monitoring loop, so that e.g. a live stdout display and a data file can be
+/// produced during the same run.
+///
+pub trait SampleSink {
+    /// Describe the measurements that are about to be emitted
+    ///
+    /// This is called once before the first sample is emitted. Sinks which
+    /// need to re-emit their header later on (like terminal tables, which
+    /// reprint it once per page of output) are responsible for doing so.
+    ///
+    fn write_header(&mut self) -> io::Result<()>;
+
+    /// Emit a new set of measurements
+    fn write_sample(&mut self, sample: &Sample) -> io::Result<()>;
+
+    /// Make sure that all previously emitted measurements reached their final
+    /// destination
+    fn flush(&mut self) -> io::Result<()>;
+
+    /// Complete the output once monitoring is over
+    ///
+    /// No sample is emitted after this is called. By default, this flushes
+    /// the sink, but file formats with a footer write it here.
+    ///
+    fn finish(&mut self) -> io::Result<()> {
+        self.flush()
+    }
+}
@@ -0,0 +1,334 @@
+//! Columnar Parquet file of periodic measurements, for data analysis pipelines
+//!
+//! This output is only available when benchmon is built with the "parquet"
+//! cargo feature, as the parquet crate is a heavy dependency.
+
+use crate::output::{Columns, FileWriter, Sample, SampleRecord, SampleSink, SyncPolicy};
+
+use parquet::{
+    basic::{LogicalType, Repetition, TimeUnit, Type as PhysicalType},
+    data_type::{ByteArray, ByteArrayType, DataType, DoubleType, Int64Type},
+    file::{
+        properties::WriterProperties,
+        writer::{SerializedFileWriter, SerializedRowGroupWriter},
+    },
+    format::MicroSeconds,
+    schema::types::Type,
+};
+
+use std::{
+    io::{self, Write},
+    path::Path,
+    sync::Arc,
+};
+
+/// Number of samples which are buffered in RAM before being written out as a
+/// row group
+const ROW_GROUP_SAMPLES: usize = 1024;
+
+/// Parquet file of measurements, with one row per sample
+///
+/// The schema has a timestamp column, a monotonic elapsed time column, and one
+/// column per measurement of the active column set, which is named after the
+/// matching `SampleRecord` field and recorded in the same unit, as an optional
+/// double (null when the measurement is unknown). User-provided markers are
+/// recorded as an optional string column.
+///
+/// Parquet is a columnar format, so samples are buffered in RAM and written out
+/// one row group at a time, which keeps memory usage bounded on long runs. The
+/// file only becomes readable once its footer is written by `finish()`, and the
+/// sync policy is applied after each row group.
+///
+pub struct ParquetFile {
+    /// Parquet writer, which is taken away once the file is finished
+    writer: Option<SerializedFileWriter<FileWriter>>,
+
+    /// `SampleRecord` fields of the measurement columns
+    fields: Vec<&'static str>,
+
+    /// Truth that the marker column is enabled
+    marker: bool,
+
+    /// Buffered wall-clock timestamps, in microseconds since the Unix epoch
+    times_us: Vec<i64>,
+
+    /// Buffered monotonic times since monitoring started, in nanoseconds
+    elapsed_ns: Vec<i64>,
+
+    /// Buffered measurements, with one entry per measurement column
+    measurements: Vec<Vec<Option<f64>>>,
+
+    /// Buffered markers (empty if the marker column is disabled)
+    markers: Vec<Option<ByteArray>>,
+}
+
+impl ParquetFile {
+    /// Set up Parquet output into a certain file, which must not exist yet
+    pub fn new(path: &Path, sync_policy: SyncPolicy, columns: &Columns) -> io::Result<Self> {
+        let fields = measurement_fields(columns);
+        let schema = Arc::new(schema(&fields, columns.marker)?);
+        let properties = Arc::new(
+            WriterProperties::builder()
+                .set_created_by(format!("benchmon {}", env!("CARGO_PKG_VERSION")))
+                .build(),
+        );
+        let file = FileWriter::create_new(path, sync_policy)?;
+        Ok(Self {
+            writer: Some(SerializedFileWriter::new(file, schema, properties)?),
+            measurements: vec![Vec::with_capacity(ROW_GROUP_SAMPLES); fields.len()],
+            fields,
+            marker: columns.marker,
+            times_us: Vec::with_capacity(ROW_GROUP_SAMPLES),
+            elapsed_ns: Vec::with_capacity(ROW_GROUP_SAMPLES),
+            markers: Vec::new(),
+        })
+    }
+
+    /// Write the buffered samples out as a row group, if there are any
+    fn write_row_group(&mut self) -> io::Result<()> {
+        let writer = match &mut self.writer {
+            Some(writer) if !self.elapsed_ns.is_empty() => writer,
+            _ => return Ok(()),
+        };
+        let mut row_group = writer.next_row_group()?;
+        write_column::<Int64Type>(&mut row_group, &self.times_us, None)?;
+        write_column::<Int64Type>(&mut row_group, &self.elapsed_ns, None)?;
+        for values in &self.measurements {
+            write_optional_column::<DoubleType>(&mut row_group, values)?;
+        }
+        if self.marker {
+            write_optional_column::<ByteArrayType>(&mut row_group, &self.markers)?;
+        }
+        row_group.close()?;
+        writer.inner_mut().end_sample()?;
+
+        self.times_us.clear();
+        self.elapsed_ns.clear();
+        for values in &mut self.measurements {
+            values.clear();
+        }
+        self.markers.clear();
+        Ok(())
+    }
+}
+
+/// `SampleRecord` fields of the measurements which are recorded, in the same
+/// order as the columns of the CSV output
+fn measurement_fields(columns: &Columns) -> Vec<&'static str> {
+    let mut fields = vec!["gap_s"];
+    if columns.idle {
+        fields.push("idle_pct");
+    }
+    #[cfg(target_os = "linux")]
+    {
+        if columns.io_wait {
+            fields.push("iowait_pct");
+        }
+    }
+    if columns.busiest_core {
+        fields.push("busiest_core_pct");
+    }
+    if columns.pressure {
+        fields.extend_from_slice(&["cpu_psi_pct", "mem_psi_pct", "io_psi_pct"]);
+    }
+    if columns.memory {
+        fields.extend_from_slice(&["mem_mib", "swap_mib"]);
+    }
+    if columns.numa {
+        fields.push("numa_max_pct");
+    }
+    if columns.freq_spread {
+        fields.extend_from_slice(&["freq_min_mhz", "freq_max_mhz"]);
+    }
+    if columns.relative_freq {
+        fields.push("freq_rel_pct");
+    }
+    if columns.involuntary_ctx_switches {
+        fields.push("invcsw_per_sec");
+    }
+    if columns.net {
+        fields.push("net_bytes_per_sec");
+    }
+    if columns.ecc {
+        fields.push("ecc_ce");
+    }
+    if columns.thermal_headroom {
+        fields.push("thermal_headroom_pct");
+    }
+    if columns.jitter {
+        fields.push("jitter_us");
+    }
+    if columns.cpu_seconds {
+        fields.push("cpu_s");
+    }
+    if columns.net_totals {
+        fields.extend_from_slice(&["net_rx_bytes", "net_tx_bytes"]);
+    }
+    if columns.disk_totals {
+        fields.extend_from_slice(&["disk_read_bytes", "disk_write_bytes"]);
+    }
+    if columns.raw_counters {
+        fields.extend_from_slice(&[
+            "raw_user_s",
+            "raw_system_s",
+            "raw_idle_s",
+            "raw_nice_s",
+            "raw_iowait_s",
+            "raw_steal_s",
+            "raw_ctx_switches",
+            "raw_interrupts",
+            "raw_net_recv_bytes",
+            "raw_net_sent_bytes",
+        ]);
+    }
+    fields
+}
+
+/// Parquet schema of the recorded columns
+fn schema(fields: &[&str], marker: bool) -> io::Result<Type> {
+    let timestamp = LogicalType::Timestamp {
+        is_adjusted_to_u_t_c: true,
+        unit: TimeUnit::MICROS(MicroSeconds {}),
+    };
+    let mut columns = vec![
+        Type::primitive_type_builder("time", PhysicalType::INT64)
+            .with_repetition(Repetition::REQUIRED)
+            .with_logical_type(Some(timestamp))
+            .build()?,
+        Type::primitive_type_builder("elapsed_ns", PhysicalType::INT64)
+            .with_repetition(Repetition::REQUIRED)
+            .build()?,
+    ];
+    for field in fields {
+        columns.push(
+            Type::primitive_type_builder(field, PhysicalType::DOUBLE)
+                .with_repetition(Repetition::OPTIONAL)
+                .build()?,
+        );
+    }
+    if marker {
+        columns.push(
+            Type::primitive_type_builder("marker", PhysicalType::BYTE_ARRAY)
+                .with_repetition(Repetition::OPTIONAL)
+                .with_logical_type(Some(LogicalType::String))
+                .build()?,
+        );
+    }
+    Ok(Type::group_type_builder("sample")
+        .with_fields(columns.into_iter().map(Arc::new).collect())
+        .build()?)
+}
+
+/// Write the next column of a row group
+fn write_column<T: DataType>(
+    row_group: &mut SerializedRowGroupWriter<'_, FileWriter>,
+    values: &[T::T],
+    def_levels: Option<&[i16]>,
+) -> io::Result<()> {
+    let mut column = row_group
+        .next_column()?
+        .expect("Buffered columns should match the Parquet schema");
+    column.typed::<T>().write_batch(values, def_levels, None)?;
+    column.close()?;
+    Ok(())
+}
+
+/// Write the next column of a row group, which is optional
+fn write_optional_column<T: DataType>(
+    row_group: &mut SerializedRowGroupWriter<'_, FileWriter>,
+    values: &[Option<T::T>],
+) -> io::Result<()> {
+    let def_levels = values
+        .iter()
+        .map(|value| value.is_some() as i16)
+        .collect::<Vec<_>>();
+    let values = values.iter().flatten().cloned().collect::<Vec<_>>();
+    write_column::<T>(row_group, &values, Some(&def_levels))
+}
+
+impl SampleSink for ParquetFile {
+    fn write_header(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn write_sample(&mut self, sample: &Sample) -> io::Result<()> {
+        let local_time = &sample.local_time;
+        self.times_us.push(
+            local_time.timestamp() * 1_000_000 + i64::from(local_time.timestamp_subsec_micros()),
+        );
+        self.elapsed_ns.push(sample.elapsed.as_nanos() as i64);
+        let record = serde_json::to_value(SampleRecord::new(sample))?;
+        for (field, values) in self.fields.iter().zip(&mut self.measurements) {
+            values.push(record[*field].as_f64());
+        }
+        if self.marker {
+            let marker = sample.marker.as_deref().map(ByteArray::from);
+            self.markers.push(marker);
+        }
+        if self.elapsed_ns.len() >= ROW_GROUP_SAMPLES {
+            self.write_row_group()?;
+        }
+        Ok(())
+    }
+
+    /// Samples are written out one row group at a time, so this only makes
+    /// sure that the row groups that were written reached the file
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.writer {
+            Some(writer) => writer.inner_mut().flush(),
+            None => Ok(()),
+        }
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        self.write_row_group()?;
+        if let Some(writer) = self.writer.take() {
+            let mut file = writer.into_inner()?;
+            file.flush()?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for ParquetFile {
+    /// Try to leave a readable file behind if monitoring was aborted, errors
+    /// can only be reported by calling `finish()` beforehand
+    fn drop(&mut self) {
+        let _ = self.finish();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn field_names() {
+        let columns = Columns {
+            idle: true,
+            busiest_core: true,
+            pressure: true,
+            memory: true,
+            numa: true,
+            freq_spread: true,
+            relative_freq: true,
+            involuntary_ctx_switches: true,
+            net: true,
+            ecc: true,
+            thermal_headroom: true,
+            jitter: true,
+            cpu_seconds: true,
+            net_totals: true,
+            disk_totals: true,
+            raw_counters: true,
+            ..Columns::default()
+        };
+        for field in measurement_fields(&columns) {
+            assert!(
+                SampleRecord::FIELDS.contains(&field),
+                "Unknown field {}",
+                field
+            );
+        }
+    }
+}
@@ -0,0 +1,44 @@
+//! Shared snapshot of the latest measurements, for concurrent readers
+
+use crate::output::{Sample, SampleSink};
+
+use arc_swap::ArcSwapOption;
+
+use std::{io, sync::Arc};
+
+/// Latest sample, shared with concurrent readers
+///
+/// The monitoring loop atomically replaces the sample on every tick, and
+/// readers can grab the current one without blocking it, which decouples the
+/// measurement rate from the rate at which measurements are consumed.
+///
+/// Cloning this struct yields another handle to the same snapshot.
+#[derive(Clone, Default)]
+pub struct Snapshot(Arc<ArcSwapOption<Sample>>);
+
+impl Snapshot {
+    /// Set up a snapshot, initially containing no sample
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get the latest sample, if any was taken yet
+    pub fn latest(&self) -> Option<Arc<Sample>> {
+        self.0.load_full()
+    }
+}
+
+impl SampleSink for Snapshot {
+    fn write_header(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn write_sample(&mut self, sample: &Sample) -> io::Result<()> {
+        self.0.store(Some(Arc::new(sample.clone())));
+        Ok(())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,878 @@
+//! Tabular display of periodic measurements on stdout (or in a text file)
+
+use crate::{
+    cpu::{freq, FrequencyRange},
+    format::{
+        self, InformationFormatter, IntegerFormatter, PercentageFormatter, RateFormatter,
+        RoundingMode, SmallRateNotation, TableStyle,
+    },
+    output::{titles, FileWriter, Sample, SampleSink},
+    psi::Resource,
+    time,
+};
+
+use chrono::{DateTime, Local};
+
+use heim::units::information::mebibyte;
+
+use std::{
+    collections::VecDeque,
+    fmt::Display,
+    io::{self, Stdout, Write},
+};
+
+/// Optional columns of the stdout table and other tabular outputs
+#[derive(Clone, Default)]
+pub struct Columns {
+    /// Display the fraction of CPU time spent idle
+    pub idle: bool,
+
+    /// Display the fraction of CPU time spent waiting for I/O
+    #[cfg(target_os = "linux")]
+    pub io_wait: bool,
+
+    /// Display the utilization of the busiest logical CPU core
+    pub busiest_core: bool,
+
+    /// Display the fraction of time during which some tasks were stalled on
+    /// CPU, memory and I/O (requires Linux PSI support)
+    pub pressure: bool,
+
+    /// Display the amount of RAM and swap in use in MiB
+    pub memory: bool,
+
+    /// Display the fraction of memory in use on the most loaded NUMA node
+    pub numa: bool,
+
+    /// Display the involuntary context switch rate of the tracked process
+    pub involuntary_ctx_switches: bool,
+
+    /// Display the total network throughput in KiB/s
+    pub net: bool,
+
+    /// Display the number of corrected memory errors since startup
+    pub ecc: bool,
+
+    /// Display the highest thermal headroom used by a temperature sensor
+    pub thermal_headroom: bool,
+
+    /// Display the lowest and highest current CPU core frequency in MHz
+    pub freq_spread: bool,
+
+    /// Range of CPU frequencies, which the frequency columns are sized for
+    pub freq_range: FrequencyRange,
+
+    /// Display the lowest current CPU core frequency, relative to its range
+    pub relative_freq: bool,
+
+    /// Display the sampling jitter in microseconds
+    pub jitter: bool,
+
+    /// Display the non-idle CPU time consumed since startup in seconds
+    pub cpu_seconds: bool,
+
+    /// Display the bytes received and sent over the network since startup
+    pub net_totals: bool,
+
+    /// Display the bytes read and written by physical disks since startup
+    pub disk_totals: bool,
+
+    /// Record the raw cumulative CPU and network counters (machine-readable
+    /// outputs only, as these are not meant to be read by humans)
+    pub raw_counters: bool,
+
+    /// Display user-provided markers
+    pub marker: bool,
+
+    /// Maximal width of a column, beyond which its contents are truncated
+    pub max_width: Option<usize>,
+
+    /// Number of samples over which numeric columns are averaged (0 or 1
+    /// means that no averaging is performed)
+    pub smoothing_window: usize,
+
+    /// Characters used to draw the table borders
+    pub style: TableStyle,
+
+    /// Rounding mode of the displayed percentages
+    pub rounding: RoundingMode,
+
+    /// Notation of rates which are too small for their column's unit
+    pub small_rates: SmallRateNotation,
+
+    /// Timezone of the time column
+    pub timezone: time::Timezone,
+}
+
+/// Simple moving average over the last few values of a quantity
+struct MovingAverage {
+    /// Last recorded values, from oldest to newest
+    values: VecDeque<f32>,
+
+    /// Maximal number of values to be averaged
+    window: usize,
+}
+
+impl MovingAverage {
+    /// Set up a moving average over a certain number of values
+    fn new(window: usize) -> Self {
+        let window = window.max(1);
+        Self {
+            values: VecDeque::with_capacity(window),
+            window,
+        }
+    }
+
+    /// Record a new value and return the average of the recorded values
+    ///
+    /// Until the averaging window is full, only the values which were actually
+    /// recorded are averaged.
+    ///
+    fn push(&mut self, value: f32) -> f32 {
+        if self.values.len() == self.window {
+            self.values.pop_front();
+        }
+        self.values.push_back(value);
+        self.values.iter().sum::<f32>() / self.values.len() as f32
+    }
+}
+
+/// Column of percentages, optionally smoothed by a moving average
+struct PercentageColumn {
+    /// Formatter for the column
+    formatter: PercentageFormatter,
+
+    /// Moving average of the displayed fractions
+    average: MovingAverage,
+}
+
+impl PercentageColumn {
+    /// Display the title of the column
+    fn display_title(&self, fill: char) -> impl Display + '_ {
+        self.formatter.display_title(fill)
+    }
+
+    /// Record a new fraction and display its moving average within the column
+    fn display_data(&mut self, fraction: f32) -> impl Display {
+        self.formatter.display_data(self.average.push(fraction))
+    }
+
+    /// Display a placeholder for unknown data within the column
+    fn display_unavailable(&self) -> impl Display {
+        self.formatter.display_unavailable()
+    }
+
+    /// Display a placeholder for a failed measurement within the column
+    fn display_error(&self) -> impl Display {
+        self.formatter.display_error()
+    }
+}
+
+/// Destination of the table
+enum TableOutput {
+    /// Standard output, which is assumed to be a terminal
+    Stdout(Stdout),
+
+    /// Text file, which unlike a terminal does not page
+    File(FileWriter),
+}
+
+/// Live table of measurements on stdout (or in a text file)
+pub struct StdoutTable {
+    /// Destination of the table
+    output: TableOutput,
+
+    /// Formatter for the time column
+    time_formatter: time::Formatter,
+
+    /// Idle CPU time column, if enabled
+    idle_column: Option<PercentageColumn>,
+
+    /// I/O wait CPU time column, if enabled
+    #[cfg(target_os = "linux")]
+    io_wait_column: Option<PercentageColumn>,
+
+    /// Busiest CPU core utilization column, if enabled
+    busiest_core_column: Option<PercentageColumn>,
+
+    /// CPU, memory and I/O pressure columns, if enabled
+    pressure_columns: Option<[PercentageColumn; 3]>,
+
+    /// Formatters for the RAM and swap usage columns, if enabled
+    memory_formatters: Option<(IntegerFormatter, IntegerFormatter)>,
+
+    /// Most loaded NUMA node memory usage column, if enabled
+    numa_column: Option<PercentageColumn>,
+
+    /// Formatter for the involuntary context switch rate column, if enabled
+    invcsw_formatter: Option<IntegerFormatter>,
+
+    /// Formatter for the network throughput column, if enabled
+    net_formatter: Option<RateFormatter>,
+
+    /// Formatter for the corrected memory error column, if enabled
+    ecc_formatter: Option<IntegerFormatter>,
+
+    /// Thermal headroom column, if enabled
+    thermal_column: Option<PercentageColumn>,
+
+    /// Formatters for the lowest and highest core frequency columns, if enabled
+    freq_spread_formatters: Option<(freq::Formatter, freq::Formatter)>,
+
+    /// Lowest relative core frequency column, if enabled
+    relative_freq_column: Option<PercentageColumn>,
+
+    /// Formatter for the sampling jitter column, if enabled
+    jitter_formatter: Option<IntegerFormatter>,
+
+    /// Formatter for the consumed CPU time column, if enabled
+    cpu_seconds_formatter: Option<IntegerFormatter>,
+
+    /// Formatters for the network bytes received and sent columns, if enabled
+    net_totals_formatters: Option<(InformationFormatter, InformationFormatter)>,
+
+    /// Formatters for the disk bytes read and written columns, if enabled
+    disk_totals_formatters: Option<(InformationFormatter, InformationFormatter)>,
+
+    /// Truth that user-provided markers should be displayed, as a last column
+    /// of unbounded width (unless a maximal column width is set)
+    marker: bool,
+
+    /// Maximal width of a column
+    max_width: Option<usize>,
+
+    /// Characters used to draw the table borders
+    style: TableStyle,
+
+    /// Timezone of the time column
+    timezone: time::Timezone,
+
+    /// Position within the current terminal page (only tracked on stdout)
+    page: PageTracker,
+}
+
+/// Position within the current terminal page, which tells when the table
+/// header should be reprinted
+///
+/// Positions are counted in terminal lines, not in samples, since a sample's
+/// row may take several lines once wrapped by the terminal.
+///
+#[derive(Default)]
+struct PageTracker {
+    /// Number of lines that were printed since the last table header,
+    /// including the header itself
+    lines_since_header: u64,
+}
+
+impl PageTracker {
+    /// Record that a table header of a certain height was printed
+    fn header_printed(&mut self, header_height: u64) {
+        self.lines_since_header = header_height;
+    }
+
+    /// Truth that the header must be reprinted before a row of a certain
+    /// height, so that it does not scroll out of a terminal of a certain height
+    fn needs_header(&self, row_height: u64, term_height: u64) -> bool {
+        self.lines_since_header + row_height >= term_height
+    }
+
+    /// Record that a row of a certain height was printed
+    fn row_printed(&mut self, row_height: u64) {
+        self.lines_since_header += row_height;
+    }
+}
+
+impl StdoutTable {
+    /// Set up the stdout table
+    pub fn new(time_formatter: time::Formatter, columns: &Columns) -> Self {
+        Self::with_output(TableOutput::Stdout(io::stdout()), time_formatter, columns)
+    }
+
+    /// Set up a table which is written into a file instead of stdout
+    ///
+    /// Since files do not page, the header is only written once at the top of
+    /// the file, instead of being reprinted once per terminal page.
+    ///
+    pub fn for_file(
+        writer: FileWriter,
+        time_formatter: time::Formatter,
+        columns: &Columns,
+    ) -> Self {
+        Self::with_output(TableOutput::File(writer), time_formatter, columns)
+    }
+
+    /// Set up a table with a certain destination
+    fn with_output(
+        output: TableOutput,
+        time_formatter: time::Formatter,
+        columns: &Columns,
+    ) -> Self {
+        let column = |title| PercentageColumn {
+            formatter: PercentageFormatter::new(title, columns.max_width, columns.rounding),
+            average: MovingAverage::new(columns.smoothing_window),
+        };
+        let enable = |enabled: bool, title| {
+            if enabled {
+                Some(column(title))
+            } else {
+                None
+            }
+        };
+        Self {
+            output,
+            time_formatter,
+            idle_column: enable(columns.idle, titles::IDLE),
+            #[cfg(target_os = "linux")]
+            io_wait_column: enable(columns.io_wait, titles::IO_WAIT),
+            busiest_core_column: enable(columns.busiest_core, titles::BUSIEST_CORE),
+            pressure_columns: if columns.pressure {
+                let [cpu, memory, io] = titles::PRESSURE;
+                Some([column(cpu), column(memory), column(io)])
+            } else {
+                None
+            },
+            memory_formatters: if columns.memory {
+                Some((
+                    IntegerFormatter::new(titles::MEMORY, columns.max_width),
+                    IntegerFormatter::new(titles::SWAP, columns.max_width),
+                ))
+            } else {
+                None
+            },
+            numa_column: enable(columns.numa, titles::NUMA),
+            invcsw_formatter: if columns.involuntary_ctx_switches {
+                Some(IntegerFormatter::new(
+                    titles::INVOLUNTARY_CTX_SWITCHES,
+                    columns.max_width,
+                ))
+            } else {
+                None
+            },
+            net_formatter: if columns.net {
+                Some(RateFormatter::new(
+                    titles::NET,
+                    1024.0,
+                    "B",
+                    columns.max_width,
+                    columns.small_rates,
+                ))
+            } else {
+                None
+            },
+            ecc_formatter: if columns.ecc {
+                Some(IntegerFormatter::new(titles::ECC, columns.max_width))
+            } else {
+                None
+            },
+            thermal_column: enable(columns.thermal_headroom, titles::THERMAL_HEADROOM),
+            freq_spread_formatters: if columns.freq_spread {
+                let range = &columns.freq_range;
+                Some((
+                    freq::Formatter::new(titles::FREQ_MIN, range, columns.max_width),
+                    freq::Formatter::new(titles::FREQ_MAX, range, columns.max_width),
+                ))
+            } else {
+                None
+            },
+            relative_freq_column: enable(columns.relative_freq, titles::RELATIVE_FREQ),
+            jitter_formatter: if columns.jitter {
+                Some(IntegerFormatter::new(titles::JITTER, columns.max_width))
+            } else {
+                None
+            },
+            cpu_seconds_formatter: if columns.cpu_seconds {
+                Some(IntegerFormatter::new(
+                    titles::CPU_SECONDS,
+                    columns.max_width,
+                ))
+            } else {
+                None
+            },
+            net_totals_formatters: if columns.net_totals {
+                let [received, sent] = titles::NET_TOTALS;
+                Some((
+                    InformationFormatter::new(received, columns.max_width),
+                    InformationFormatter::new(sent, columns.max_width),
+                ))
+            } else {
+                None
+            },
+            disk_totals_formatters: if columns.disk_totals {
+                let [read, written] = titles::DISK_TOTALS;
+                Some((
+                    InformationFormatter::new(read, columns.max_width),
+                    InformationFormatter::new(written, columns.max_width),
+                ))
+            } else {
+                None
+            },
+            marker: columns.marker,
+            max_width: columns.max_width,
+            style: columns.style,
+            timezone: columns.timezone,
+            page: PageTracker::default(),
+        }
+    }
+
+    /// Print the table header
+    ///
+    /// When the header is reprinted in the middle of the output, the time of
+    /// the sample that follows is appended to it, so that every page of
+    /// output can be dated when scrolling back through it.
+    ///
+    fn print_header(&mut self, page_time: Option<DateTime<Local>>) -> io::Result<()> {
+        // Render the header before printing it, so that we know how many lines
+        // of output it takes once wrapped by the terminal
+        let mut header = Vec::new();
+        {
+            let output = &mut header;
+            let fill = self.style.header_fill;
+            let separator = self.style.header_separator;
+            write!(
+                output,
+                "{}{}",
+                self.time_formatter.display_title(fill),
+                separator
+            )?;
+            if let Some(idle_column) = &self.idle_column {
+                write!(output, "{}{}", idle_column.display_title(fill), separator)?;
+            }
+            #[cfg(target_os = "linux")]
+            {
+                if let Some(io_wait_column) = &self.io_wait_column {
+                    write!(
+                        output,
+                        "{}{}",
+                        io_wait_column.display_title(fill),
+                        separator
+                    )?;
+                }
+            }
+            if let Some(busiest_core_column) = &self.busiest_core_column {
+                write!(
+                    output,
+                    "{}{}",
+                    busiest_core_column.display_title(fill),
+                    separator
+                )?;
+            }
+            for pressure_column in self.pressure_columns.iter().flatten() {
+                write!(
+                    output,
+                    "{}{}",
+                    pressure_column.display_title(fill),
+                    separator
+                )?;
+            }
+            if let Some((ram_formatter, swap_formatter)) = &self.memory_formatters {
+                write!(
+                    output,
+                    "{}{}{}{}",
+                    ram_formatter.display_title(fill),
+                    separator,
+                    swap_formatter.display_title(fill),
+                    separator
+                )?;
+            }
+            if let Some(numa_column) = &self.numa_column {
+                write!(output, "{}{}", numa_column.display_title(fill), separator)?;
+            }
+            if let Some((min_formatter, max_formatter)) = &self.freq_spread_formatters {
+                write!(
+                    output,
+                    "{}{}{}{}",
+                    min_formatter.display_title(fill),
+                    separator,
+                    max_formatter.display_title(fill),
+                    separator
+                )?;
+            }
+            if let Some(relative_freq_column) = &self.relative_freq_column {
+                write!(
+                    output,
+                    "{}{}",
+                    relative_freq_column.display_title(fill),
+                    separator
+                )?;
+            }
+            if let Some(invcsw_formatter) = &self.invcsw_formatter {
+                write!(
+                    output,
+                    "{}{}",
+                    invcsw_formatter.display_title(fill),
+                    separator
+                )?;
+            }
+            if let Some(net_formatter) = &self.net_formatter {
+                write!(output, "{}{}", net_formatter.display_title(fill), separator)?;
+            }
+            if let Some(ecc_formatter) = &self.ecc_formatter {
+                write!(output, "{}{}", ecc_formatter.display_title(fill), separator)?;
+            }
+            if let Some(thermal_column) = &self.thermal_column {
+                write!(
+                    output,
+                    "{}{}",
+                    thermal_column.display_title(fill),
+                    separator
+                )?;
+            }
+            if let Some(jitter_formatter) = &self.jitter_formatter {
+                write!(
+                    output,
+                    "{}{}",
+                    jitter_formatter.display_title(fill),
+                    separator
+                )?;
+            }
+            if let Some(cpu_seconds_formatter) = &self.cpu_seconds_formatter {
+                write!(
+                    output,
+                    "{}{}",
+                    cpu_seconds_formatter.display_title(fill),
+                    separator
+                )?;
+            }
+            for (first_formatter, second_formatter) in self
+                .net_totals_formatters
+                .iter()
+                .chain(&self.disk_totals_formatters)
+            {
+                write!(
+                    output,
+                    "{}{}{}{}",
+                    first_formatter.display_title(fill),
+                    separator,
+                    second_formatter.display_title(fill),
+                    separator
+                )?;
+            }
+            if self.marker {
+                write!(output, "{}", titles::MARKER)?;
+            }
+            if let Some(page_time) = page_time {
+                let page_time = self.timezone.convert(page_time);
+                write!(output, " [{}]", page_time.format("%H:%M:%S"))?;
+            }
+            writeln!(output)?;
+        }
+        match &mut self.output {
+            TableOutput::Stdout(stdout) => {
+                stdout.lock().write_all(&header)?;
+                self.page.header_printed(format::line_count(
+                    &String::from_utf8_lossy(&header),
+                    format::terminal_width(),
+                ));
+            }
+            TableOutput::File(writer) => writer.write_all(&header)?,
+        }
+        Ok(())
+    }
+}
+
+impl SampleSink for StdoutTable {
+    fn write_header(&mut self) -> io::Result<()> {
+        self.print_header(None)
+    }
+
+    fn write_sample(&mut self, sample: &Sample) -> io::Result<()> {
+        // Render the measurements
+        let mut row = Vec::new();
+        {
+            let output = &mut row;
+            let separator = self.style.data_separator;
+            write!(
+                output,
+                "{}{}",
+                self.time_formatter
+                    .display_data(self.timezone.convert(sample.local_time)),
+                separator
+            )?;
+            if let Some(idle_column) = &mut self.idle_column {
+                let idle_frac = sample.cpu_time.idle_frac;
+                write!(
+                    output,
+                    "{}{}",
+                    idle_column.display_data(idle_frac),
+                    separator
+                )?;
+            }
+            #[cfg(target_os = "linux")]
+            {
+                if let Some(io_wait_column) = &mut self.io_wait_column {
+                    let io_wait_frac = sample.cpu_time.linux_fracs.io_wait_frac;
+                    write!(
+                        output,
+                        "{}{}",
+                        io_wait_column.display_data(io_wait_frac),
+                        separator
+                    )?;
+                }
+            }
+            // NOTE: Optional measurements are missing from a sample where they
+            //       are enabled if they failed, which we display as an error.
+            if let Some(busiest_core_column) = &mut self.busiest_core_column {
+                match sample.busiest_core {
+                    Some(busy_frac) => write!(
+                        output,
+                        "{}{}",
+                        busiest_core_column.display_data(busy_frac),
+                        separator
+                    )?,
+                    None => write!(
+                        output,
+                        "{}{}",
+                        busiest_core_column.display_error(),
+                        separator
+                    )?,
+                }
+            }
+            if let Some(pressure_columns) = &mut self.pressure_columns {
+                for (pressure_column, &resource) in pressure_columns.iter_mut().zip(&Resource::ALL)
+                {
+                    match &sample.pressure {
+                        Some(pressure) => write!(
+                            output,
+                            "{}{}",
+                            pressure_column.display_data(pressure.get(resource)),
+                            separator
+                        )?,
+                        None => write!(output, "{}{}", pressure_column.display_error(), separator)?,
+                    }
+                }
+            }
+            if let Some((ram_formatter, swap_formatter)) = &self.memory_formatters {
+                match &sample.memory {
+                    Some(memory) => write!(
+                        output,
+                        "{}{}{}{}",
+                        ram_formatter.display_data(Some(memory.ram_used.get::<mebibyte>())),
+                        separator,
+                        swap_formatter.display_data(Some(memory.swap_used.get::<mebibyte>())),
+                        separator
+                    )?,
+                    None => write!(
+                        output,
+                        "{}{}{}{}",
+                        ram_formatter.display_error(),
+                        separator,
+                        swap_formatter.display_error(),
+                        separator
+                    )?,
+                }
+            }
+            if let Some(numa_column) = &mut self.numa_column {
+                match &sample.numa {
+                    Some(numa) => write!(
+                        output,
+                        "{}{}",
+                        numa_column.display_data(numa.max_usage_frac()),
+                        separator
+                    )?,
+                    None => write!(output, "{}{}", numa_column.display_error(), separator)?,
+                }
+            }
+            if let Some((min_formatter, max_formatter)) = &self.freq_spread_formatters {
+                match &sample.freq_spread {
+                    Some(spread) => write!(
+                        output,
+                        "{}{}{}{}",
+                        min_formatter.display_data(spread.min),
+                        separator,
+                        max_formatter.display_data(spread.max),
+                        separator
+                    )?,
+                    None => write!(
+                        output,
+                        "{}{}{}{}",
+                        min_formatter.display_unavailable(),
+                        separator,
+                        max_formatter.display_unavailable(),
+                        separator
+                    )?,
+                }
+            }
+            if let Some(relative_freq_column) = &mut self.relative_freq_column {
+                match sample.relative_freq {
+                    Some(relative_freq) => write!(
+                        output,
+                        "{}{}",
+                        relative_freq_column.display_data(relative_freq),
+                        separator
+                    )?,
+                    None => write!(
+                        output,
+                        "{}{}",
+                        relative_freq_column.display_unavailable(),
+                        separator
+                    )?,
+                }
+            }
+            if let Some(invcsw_formatter) = &self.invcsw_formatter {
+                let invcsw_per_sec = sample
+                    .involuntary_ctx_switch_rate
+                    .map(|rate| rate.round() as u64);
+                write!(
+                    output,
+                    "{}{}",
+                    invcsw_formatter.display_data(invcsw_per_sec),
+                    separator
+                )?;
+            }
+            if let Some(net_formatter) = &self.net_formatter {
+                match sample.net_throughput {
+                    Some(throughput) => write!(
+                        output,
+                        "{}{}",
+                        net_formatter.display_data(throughput),
+                        separator
+                    )?,
+                    None => write!(output, "{}{}", net_formatter.display_error(), separator)?,
+                }
+            }
+            if let Some(ecc_formatter) = &self.ecc_formatter {
+                match sample.ecc_errors {
+                    Some(ecc_errors) => write!(
+                        output,
+                        "{}{}",
+                        ecc_formatter.display_data(Some(ecc_errors)),
+                        separator
+                    )?,
+                    None => write!(output, "{}{}", ecc_formatter.display_error(), separator)?,
+                }
+            }
+            if let Some(thermal_column) = &mut self.thermal_column {
+                match sample.thermal_headroom {
+                    Some(headroom) => write!(
+                        output,
+                        "{}{}",
+                        thermal_column.display_data(headroom),
+                        separator
+                    )?,
+                    None => write!(
+                        output,
+                        "{}{}",
+                        thermal_column.display_unavailable(),
+                        separator
+                    )?,
+                }
+            }
+            if let Some(jitter_formatter) = &self.jitter_formatter {
+                let jitter_us = sample.jitter.map(|jitter| jitter.as_micros() as u64);
+                write!(
+                    output,
+                    "{}{}",
+                    jitter_formatter.display_data(jitter_us),
+                    separator
+                )?;
+            }
+            if let Some(cpu_seconds_formatter) = &self.cpu_seconds_formatter {
+                let cpu_seconds = sample.cpu_seconds.map(|cpu_time| cpu_time.as_secs());
+                write!(
+                    output,
+                    "{}{}",
+                    cpu_seconds_formatter.display_data(cpu_seconds),
+                    separator
+                )?;
+            }
+            let totals_columns = [
+                (&self.net_totals_formatters, sample.net_totals),
+                (&self.disk_totals_formatters, sample.disk_totals),
+            ];
+            for (formatters, totals) in &totals_columns {
+                if let Some((first_formatter, second_formatter)) = formatters {
+                    match totals {
+                        Some((first, second)) => write!(
+                            output,
+                            "{}{}{}{}",
+                            first_formatter.display_data(*first),
+                            separator,
+                            second_formatter.display_data(*second),
+                            separator
+                        )?,
+                        None => write!(
+                            output,
+                            "{}{}{}{}",
+                            first_formatter.display_error(),
+                            separator,
+                            second_formatter.display_error(),
+                            separator
+                        )?,
+                    }
+                }
+            }
+            if let (true, Some(marker)) = (self.marker, &sample.marker) {
+                match self.max_width {
+                    Some(max_width) => write!(output, "{}", format::truncate(marker, max_width))?,
+                    None => write!(output, "{}", marker)?,
+                }
+            }
+            writeln!(output)?;
+        }
+
+        // Files do not page, so their rows are written as is
+        if let TableOutput::File(writer) = &mut self.output {
+            writer.write_all(&row)?;
+            return writer.end_sample();
+        }
+
+        // If we are outputting to a terminal, re-print the header once per page
+        // of output so that the columns remain easy to identify. A row may take
+        // more than one line of output if the terminal wraps it.
+        let row_height =
+            format::line_count(&String::from_utf8_lossy(&row), format::terminal_width());
+        let term_height = format::terminal_height()
+            .map(|height| height as u64)
+            .unwrap_or(u64::MAX);
+        if self.page.needs_header(row_height, term_height) {
+            self.print_header(Some(sample.local_time))?;
+        }
+
+        // Display the measurements
+        if let TableOutput::Stdout(stdout) = &mut self.output {
+            stdout.lock().write_all(&row)?;
+        }
+        self.page.row_printed(row_height);
+        Ok(())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.output {
+            TableOutput::Stdout(stdout) => stdout.flush(),
+            TableOutput::File(writer) => writer.flush(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn multi_line_paging() {
+        // Rows of three lines each, one of which wraps, below a one-line header,
+        // on a terminal that is ten lines high and ten columns wide
+        const TERM_HEIGHT: u64 = 10;
+        const TERM_WIDTH: Option<usize> = Some(10);
+        let header_height = format::line_count("time  idle\n", TERM_WIDTH);
+        let row = "12:00 42.0\n(marker)\nlong enough to wrap\n";
+        let row_height = format::line_count(row, TERM_WIDTH);
+        assert_eq!(header_height, 1);
+        assert_eq!(row_height, 4);
+
+        // The header is reprinted every two rows, before the third row would
+        // push it off the screen
+        let mut page = PageTracker::default();
+        page.header_printed(header_height);
+        let mut header_rows = Vec::new();
+        for row_idx in 0..7 {
+            if page.needs_header(row_height, TERM_HEIGHT) {
+                page.header_printed(header_height);
+                header_rows.push(row_idx);
+            }
+            page.row_printed(row_height);
+            assert!(page.lines_since_header < TERM_HEIGHT);
+        }
+        assert_eq!(header_rows, vec![2, 4, 6]);
+    }
+}
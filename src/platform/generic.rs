@@ -0,0 +1,141 @@
+//! Best-effort system queries for OSes without a dedicated implementation
+
+use super::Platform;
+
+use crate::{
+    cpu::{idle::IdleState, ContextSwitches},
+    filesystem::BlockQueue,
+    memory::{HugePagePool, MemoryController, SwapArea},
+    numa::NodeMemory,
+    os::{LoadAverage, SystemIdentity},
+    sensors::ThermalZone,
+};
+
+use futures_util::future::{self, BoxFuture, FutureExt};
+
+use heim::{cpu::CpuFrequency, units::Frequency};
+
+use std::{collections::BTreeSet, io, path::Path};
+
+/// Generic implementation of `Platform`, which only reports the data that
+/// cross-platform heim APIs provide
+pub struct Generic;
+
+impl Platform for Generic {
+    fn per_cpu_frequencies() -> BoxFuture<'static, heim::Result<Option<Vec<CpuFrequency>>>> {
+        future::ok(None).boxed()
+    }
+
+    fn current_frequencies() -> BoxFuture<'static, heim::Result<Option<Vec<Frequency>>>> {
+        future::ok(None).boxed()
+    }
+
+    fn cpu_model() -> io::Result<String> {
+        Err(io::Error::new(
+            io::ErrorKind::Other,
+            "The CPU model name can only be queried on Linux",
+        ))
+    }
+
+    fn cpu_flags() -> io::Result<BTreeSet<String>> {
+        Err(io::Error::new(
+            io::ErrorKind::Other,
+            "CPU flags can only be queried on Linux",
+        ))
+    }
+
+    fn cpu_idle_states() -> io::Result<Vec<Vec<IdleState>>> {
+        Ok(Vec::new())
+    }
+
+    fn isolated_cpus() -> io::Result<BTreeSet<usize>> {
+        Err(io::Error::new(
+            io::ErrorKind::Other,
+            "Isolated CPUs can only be queried on Linux",
+        ))
+    }
+
+    fn tickless_cpus() -> io::Result<BTreeSet<usize>> {
+        Err(io::Error::new(
+            io::ErrorKind::Other,
+            "Tickless CPUs can only be queried on Linux",
+        ))
+    }
+
+    fn socket_count() -> Option<u64> {
+        None
+    }
+
+    fn thermal_zones() -> io::Result<Vec<ThermalZone>> {
+        Ok(Vec::new())
+    }
+
+    fn dmi_table() -> io::Result<Vec<u8>> {
+        Err(io::Error::new(
+            io::ErrorKind::Other,
+            "DMI tables can only be read on Linux",
+        ))
+    }
+
+    fn system_identity() -> io::Result<SystemIdentity> {
+        Err(io::Error::new(
+            io::ErrorKind::Other,
+            "The system identity can only be queried on Linux",
+        ))
+    }
+
+    fn swap_areas() -> io::Result<Vec<SwapArea>> {
+        Ok(Vec::new())
+    }
+
+    fn memory_controllers() -> io::Result<Vec<MemoryController>> {
+        Ok(Vec::new())
+    }
+
+    fn kernel_command_line() -> io::Result<String> {
+        Err(io::Error::new(
+            io::ErrorKind::Other,
+            "The kernel command line can only be read on Linux",
+        ))
+    }
+
+    fn huge_page_pools() -> io::Result<Vec<HugePagePool>> {
+        Ok(Vec::new())
+    }
+
+    fn block_queue(_device: &Path) -> io::Result<BlockQueue> {
+        Err(io::Error::new(
+            io::ErrorKind::Other,
+            "Block device queues can only be queried on Linux",
+        ))
+    }
+
+    fn is_physical_disk(_name: &str) -> Option<bool> {
+        None
+    }
+
+    fn mount_options(_mount_point: &Path) -> io::Result<Vec<String>> {
+        Err(io::Error::new(
+            io::ErrorKind::Other,
+            "Mount options can only be queried on Linux",
+        ))
+    }
+
+    fn numa_node_memory() -> io::Result<Vec<NodeMemory>> {
+        Ok(Vec::new())
+    }
+
+    fn process_context_switches(_pid: u32) -> io::Result<ContextSwitches> {
+        Err(io::Error::new(
+            io::ErrorKind::Other,
+            "Per-process context switches can only be queried on Linux",
+        ))
+    }
+
+    fn load_average() -> io::Result<LoadAverage> {
+        Err(io::Error::new(
+            io::ErrorKind::Other,
+            "Load averages can only be queried on Linux",
+        ))
+    }
+}
@@ -0,0 +1,575 @@
+//! Linux-specific system queries
+
+use super::Platform;
+
+use crate::{
+    cpu::{idle::IdleState, ContextSwitches},
+    filesystem::BlockQueue,
+    memory::{HugePagePool, MemoryController, SwapArea},
+    numa::NodeMemory,
+    os::{LoadAverage, SystemIdentity},
+    sensors::ThermalZone,
+};
+
+use futures_util::{
+    future::{BoxFuture, FutureExt, TryFutureExt},
+    stream::TryStreamExt,
+};
+
+use heim::{
+    cpu::CpuFrequency,
+    units::{information::kibibyte, Frequency, Information},
+    units::{thermodynamic_temperature::degree_celsius, ThermodynamicTemperature as Temperature},
+};
+
+use std::{
+    collections::BTreeSet,
+    fs, io,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+/// Find the sysfs directory of the physical block device which is, or stores,
+/// a certain file
+fn physical_block_device_dir(path: &Path, is_device: bool) -> io::Result<PathBuf> {
+    use std::os::unix::fs::MetadataExt;
+
+    // Find out the device number of the block device
+    let metadata = fs::metadata(path)?;
+    let dev = if is_device {
+        metadata.rdev()
+    } else {
+        metadata.dev()
+    };
+    let major = ((dev >> 8) & 0xfff) | ((dev >> 32) & !0xfff);
+    let minor = (dev & 0xff) | ((dev >> 12) & !0xff);
+
+    // Partitions do not have a "queue" directory, only their parent has
+    let sysfs_path = fs::canonicalize(format!("/sys/dev/block/{}:{}", major, minor))?;
+    if sysfs_path.join("queue").is_dir() {
+        return Ok(sysfs_path);
+    }
+    match sysfs_path.parent() {
+        Some(parent) if parent.join("queue").is_dir() => Ok(parent.to_owned()),
+        _ => Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            "No physical block device found",
+        )),
+    }
+}
+
+/// Check if a block device, or the block device that stores a file, is
+/// rotational (i.e. a HDD), if possible
+fn backing_device_is_rotational(path: &Path, is_device: bool) -> Option<bool> {
+    let device_dir = physical_block_device_dir(path, is_device).ok()?;
+    let rotational = fs::read_to_string(device_dir.join("queue/rotational")).ok()?;
+    Some(rotational.trim() == "1")
+}
+
+/// Read a sysfs CPU list, which is of the form "0-3,8,10-11"
+fn read_cpu_list(path: impl AsRef<Path>) -> io::Result<BTreeSet<usize>> {
+    let bad_format = || io::Error::new(io::ErrorKind::InvalidData, "Bad CPU list format");
+    let mut result = BTreeSet::new();
+    for range in fs::read_to_string(path)?.trim().split(',') {
+        if range.is_empty() {
+            continue;
+        }
+        let mut bounds = range.splitn(2, '-');
+        let start = bounds
+            .next()
+            .and_then(|start| start.parse::<usize>().ok())
+            .ok_or_else(bad_format)?;
+        let end = match bounds.next() {
+            Some(end) => end.parse::<usize>().map_err(|_| bad_format())?,
+            None => start,
+        };
+        result.extend(start..=end);
+    }
+    Ok(result)
+}
+
+/// Linux implementation of `Platform`, based on heim's Linux extensions and on
+/// the sysfs interface
+pub struct Linux;
+
+impl Platform for Linux {
+    fn per_cpu_frequencies() -> BoxFuture<'static, heim::Result<Option<Vec<CpuFrequency>>>> {
+        heim::cpu::os::linux::frequencies()
+            .try_collect::<Vec<_>>()
+            .map_ok(Some)
+            .boxed()
+    }
+
+    fn current_frequencies() -> BoxFuture<'static, heim::Result<Option<Vec<Frequency>>>> {
+        heim::cpu::os::linux::frequencies()
+            .map_ok(|freq| freq.current())
+            .try_collect::<Vec<_>>()
+            .map_ok(Some)
+            .boxed()
+    }
+
+    fn cpu_model() -> io::Result<String> {
+        // The model name is repeated for each CPU core, so we only parse the
+        // first occurence. Some ARM kernels do not report it.
+        fs::read_to_string("/proc/cpuinfo")?
+            .lines()
+            .find(|line| line.starts_with("model name"))
+            .and_then(|line| Some(line.splitn(2, ':').nth(1)?.trim().to_owned()))
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "No CPU model name found in /proc/cpuinfo",
+                )
+            })
+    }
+
+    fn cpu_flags() -> io::Result<BTreeSet<String>> {
+        // Flags are listed on a "flags" line on x86 and a "Features" line on
+        // ARM, which is repeated for each CPU core, so we only parse the first.
+        let cpuinfo = fs::read_to_string("/proc/cpuinfo")?;
+        let flags_line = cpuinfo
+            .lines()
+            .find(|line| line.starts_with("flags") || line.starts_with("Features"))
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "No CPU flags found in /proc/cpuinfo",
+                )
+            })?;
+        Ok(flags_line
+            .splitn(2, ':')
+            .nth(1)
+            .unwrap_or("")
+            .split_whitespace()
+            .map(str::to_owned)
+            .collect())
+    }
+
+    fn cpu_idle_states() -> io::Result<Vec<Vec<IdleState>>> {
+        // Sysfs attributes are newline-terminated
+        let read_attribute =
+            |path: &Path| fs::read_to_string(path).map(|s| s.trim_end().to_owned());
+        let read_micros = |path: &Path| {
+            read_attribute(path)?
+                .parse::<u64>()
+                .map(Duration::from_micros)
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+        };
+
+        // Enumerate the CPU cores in order, stopping at the first core without
+        // cpuidle information (cpuidle may be disabled altogether)
+        let mut result = Vec::new();
+        for cpu in 0.. {
+            let cpuidle_path = PathBuf::from(format!("/sys/devices/system/cpu/cpu{}/cpuidle", cpu));
+            if !cpuidle_path.is_dir() {
+                break;
+            }
+            let mut states = Vec::new();
+            for state in 0.. {
+                let state_path = cpuidle_path.join(format!("state{}", state));
+                if !state_path.is_dir() {
+                    break;
+                }
+                states.push(IdleState {
+                    name: read_attribute(&state_path.join("name"))?,
+                    exit_latency: read_micros(&state_path.join("latency"))?,
+                    residency: read_micros(&state_path.join("time"))?,
+                    disabled: read_attribute(&state_path.join("disable"))? != "0",
+                });
+            }
+            result.push(states);
+        }
+        Ok(result)
+    }
+
+    fn isolated_cpus() -> io::Result<BTreeSet<usize>> {
+        read_cpu_list("/sys/devices/system/cpu/isolated")
+    }
+
+    fn tickless_cpus() -> io::Result<BTreeSet<usize>> {
+        // This file only exists if the kernel supports tickless operation
+        read_cpu_list("/sys/devices/system/cpu/nohz_full")
+    }
+
+    fn socket_count() -> Option<u64> {
+        // We can count the distinct package IDs of the CPU topology
+        let package_ids = fs::read_dir("/sys/devices/system/cpu/")
+            .ok()?
+            .filter_map(|entry| {
+                let path = entry.ok()?.path().join("topology/physical_package_id");
+                fs::read_to_string(path).ok()
+            })
+            .map(|package_id| package_id.trim().to_owned())
+            .collect::<BTreeSet<_>>();
+        if package_ids.is_empty() {
+            None
+        } else {
+            Some(package_ids.len() as u64)
+        }
+    }
+
+    fn thermal_zones() -> io::Result<Vec<ThermalZone>> {
+        // Read the contents of a sysfs file, without the trailing newline
+        let read_attribute =
+            |path: &Path| fs::read_to_string(path).map(|s| s.trim_end().to_owned());
+
+        // Systems without thermal zones do not have the sysfs directory
+        let entries = match fs::read_dir("/sys/class/thermal/") {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(err),
+        };
+
+        let mut result = Vec::new();
+        for entry in entries {
+            let entry = entry?;
+            if !entry
+                .file_name()
+                .to_string_lossy()
+                .starts_with("thermal_zone")
+            {
+                continue;
+            }
+            let zone_path = entry.path();
+            let mut zone = ThermalZone {
+                unit: read_attribute(&zone_path.join("type"))?,
+                high_trip_point: None,
+                critical_trip_point: None,
+            };
+
+            // Trip point temperatures are provided in millidegrees Celsius
+            for trip_point in 0.. {
+                let type_path = zone_path.join(format!("trip_point_{}_type", trip_point));
+                let temp_path = zone_path.join(format!("trip_point_{}_temp", trip_point));
+                let trip_type = match read_attribute(&type_path) {
+                    Ok(trip_type) => trip_type,
+                    Err(err) if err.kind() == io::ErrorKind::NotFound => break,
+                    Err(err) => return Err(err),
+                };
+                let trip_temp = read_attribute(&temp_path)?
+                    .parse::<f32>()
+                    .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+                let trip_temp = Temperature::new::<degree_celsius>(trip_temp / 1000.0);
+                match trip_type.as_str() {
+                    "hot" => zone.high_trip_point = zone.high_trip_point.or(Some(trip_temp)),
+                    "critical" => {
+                        zone.critical_trip_point = zone.critical_trip_point.or(Some(trip_temp))
+                    }
+                    _ => {}
+                }
+            }
+            result.push(zone);
+        }
+        Ok(result)
+    }
+
+    fn dmi_table() -> io::Result<Vec<u8>> {
+        std::fs::read("/sys/firmware/dmi/tables/DMI")
+    }
+
+    fn system_identity() -> io::Result<SystemIdentity> {
+        // Some of these files are only readable by root (e.g. the serial
+        // number), and unknown fields are often filled with placeholders like
+        // "To Be Filled By O.E.M.", which we leave to the user to interpret.
+        let dmi_dir = Path::new("/sys/class/dmi/id");
+        if !dmi_dir.is_dir() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "DMI information is not exposed by the kernel",
+            ));
+        }
+        let read_field = |name: &str| {
+            fs::read_to_string(dmi_dir.join(name))
+                .ok()
+                .map(|value| value.trim().to_owned())
+                .filter(|value| !value.is_empty())
+        };
+        Ok(SystemIdentity {
+            vendor: read_field("sys_vendor"),
+            product: read_field("product_name"),
+            serial: read_field("product_serial"),
+            bios_version: read_field("bios_version"),
+            bios_date: read_field("bios_date"),
+        })
+    }
+
+    fn swap_areas() -> io::Result<Vec<SwapArea>> {
+        // /proc/swaps has a header line, followed by one line per swap area of
+        // the form "<path> <type> <size KiB> <used KiB> <priority>"
+        let swaps = fs::read_to_string("/proc/swaps")?;
+        let mut result = Vec::new();
+        for line in swaps.lines().skip(1) {
+            let fields = line.split_whitespace().collect::<Vec<_>>();
+            let parse_kib = |field: Option<&&str>| -> io::Result<Information> {
+                field
+                    .and_then(|field| field.parse::<u64>().ok())
+                    .map(Information::new::<kibibyte>)
+                    .ok_or_else(|| {
+                        io::Error::new(io::ErrorKind::InvalidData, "Bad /proc/swaps format")
+                    })
+            };
+            let path = match fields.first() {
+                Some(path) => path.replace("\\040", " "),
+                None => continue,
+            };
+            let rotational =
+                backing_device_is_rotational(Path::new(&path), fields.get(1) == Some(&"partition"));
+            result.push(SwapArea {
+                size: parse_kib(fields.get(2))?,
+                used: parse_kib(fields.get(3))?,
+                rotational,
+                path,
+            });
+        }
+        Ok(result)
+    }
+
+    fn memory_controllers() -> io::Result<Vec<MemoryController>> {
+        // Sysfs numbers are newline-terminated
+        let read_number = |path: &Path| {
+            fs::read_to_string(path)?
+                .trim()
+                .parse::<u64>()
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+        };
+
+        // Systems without EDAC support do not have the sysfs directory
+        let entries = match fs::read_dir("/sys/devices/system/edac/mc/") {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(err),
+        };
+
+        let mut result = Vec::new();
+        for entry in entries {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if !name.starts_with("mc") {
+                continue;
+            }
+            let controller_path = entry.path();
+            result.push(MemoryController {
+                correctable_errors: read_number(&controller_path.join("ce_count"))?,
+                uncorrectable_errors: read_number(&controller_path.join("ue_count"))?,
+                name,
+            });
+        }
+        result.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(result)
+    }
+
+    fn kernel_command_line() -> io::Result<String> {
+        fs::read_to_string("/proc/cmdline").map(|cmdline| cmdline.trim_end().to_owned())
+    }
+
+    fn huge_page_pools() -> io::Result<Vec<HugePagePool>> {
+        // Sysfs numbers are newline-terminated
+        let read_number = |path: &Path| {
+            fs::read_to_string(path)?
+                .trim()
+                .parse::<u64>()
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+        };
+
+        // The default huge page size is only reported by /proc/meminfo, as a
+        // line of the form "Hugepagesize:    2048 kB"
+        let default_size_kib = fs::read_to_string("/proc/meminfo")?
+            .lines()
+            .find(|line| line.starts_with("Hugepagesize:"))
+            .and_then(|line| line.split_whitespace().nth(1)?.parse::<u64>().ok());
+
+        // Kernels without huge page support do not have the sysfs directory
+        let entries = match fs::read_dir("/sys/kernel/mm/hugepages/") {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(err),
+        };
+
+        // Pool directories are named after their page size, e.g.
+        // "hugepages-2048kB"
+        let mut result = Vec::new();
+        for entry in entries {
+            let entry = entry?;
+            let file_name = entry.file_name().to_string_lossy().into_owned();
+            const PREFIX: &str = "hugepages-";
+            const SUFFIX: &str = "kB";
+            if !file_name.starts_with(PREFIX) || !file_name.ends_with(SUFFIX) {
+                continue;
+            }
+            let size_kib = match file_name[PREFIX.len()..file_name.len() - SUFFIX.len()].parse() {
+                Ok(size_kib) => size_kib,
+                Err(_) => continue,
+            };
+            let pool_path = entry.path();
+            result.push(HugePagePool {
+                size: Information::new::<kibibyte>(size_kib),
+                is_default: default_size_kib == Some(size_kib),
+                total: read_number(&pool_path.join("nr_hugepages"))?,
+                free: read_number(&pool_path.join("free_hugepages"))?,
+            });
+        }
+        result.sort_by_key(|pool| pool.size.get::<kibibyte>());
+        Ok(result)
+    }
+
+    fn block_queue(device: &Path) -> io::Result<BlockQueue> {
+        let device_dir = physical_block_device_dir(device, true)?;
+        let queue_dir = device_dir.join("queue");
+        let read_attribute =
+            |name: &str| fs::read_to_string(queue_dir.join(name)).map(|s| s.trim().to_owned());
+        let read_number = |name: &str| read_attribute(name).ok()?.parse::<u64>().ok();
+
+        // The scheduler file lists available schedulers, with the active one
+        // in brackets, e.g. "[mq-deadline] kyber bfq none"
+        let scheduler = read_attribute("scheduler").ok().and_then(|schedulers| {
+            schedulers
+                .split_whitespace()
+                .find(|scheduler| scheduler.starts_with('['))
+                .map(|scheduler| scheduler.trim_matches(|c| c == '[' || c == ']').to_owned())
+        });
+
+        Ok(BlockQueue {
+            name: device_dir
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            scheduler,
+            nr_requests: read_number("nr_requests"),
+            rotational: read_number("rotational").map(|rotational| rotational != 0),
+            read_ahead: read_number("read_ahead_kb").map(Information::new::<kibibyte>),
+        })
+    }
+
+    fn is_physical_disk(name: &str) -> Option<bool> {
+        // Partitions are not listed in /sys/block, and virtual block devices
+        // (loop, device-mapper, md...) are not backed by a hardware device.
+        // Slashes in device names (e.g. "cciss/c0d0") become "!" in sysfs.
+        let device_dir = Path::new("/sys/block").join(name.replace('/', "!"));
+        Some(device_dir.join("device").exists())
+    }
+
+    fn mount_options(mount_point: &Path) -> io::Result<Vec<String>> {
+        // /proc/mounts lines are of the form "<device> <mount point> <fs type>
+        // <options> 0 0", where whitespace and backslashes in paths are escaped
+        // as octal sequences (e.g. "\040" for a space). When several mounts
+        // are stacked on the same mount point, the last one is visible.
+        let unescape = |path: &str| {
+            path.replace("\\040", " ")
+                .replace("\\011", "\t")
+                .replace("\\012", "\n")
+                .replace("\\134", "\\")
+        };
+        fs::read_to_string("/proc/mounts")?
+            .lines()
+            .filter_map(|line| {
+                let mut fields = line.split_whitespace().skip(1);
+                let line_mount_point = unescape(fields.next()?);
+                let options = fields.nth(1)?;
+                Some((line_mount_point, options))
+            })
+            .filter(|(line_mount_point, _options)| Path::new(line_mount_point) == mount_point)
+            .last()
+            .map(|(_mount_point, options)| options.split(',').map(String::from).collect())
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "Mount point not found"))
+    }
+
+    fn process_context_switches(pid: u32) -> io::Result<ContextSwitches> {
+        // /proc/<pid>/status only accounts for the main thread, so we need to
+        // sum over the /proc/<pid>/task/<tid>/status of every thread. Threads
+        // which already exited are not accounted for.
+        let mut result = ContextSwitches::default();
+        for entry in fs::read_dir(format!("/proc/{}/task", pid))? {
+            let status = match fs::read_to_string(entry?.path().join("status")) {
+                Ok(status) => status,
+                // Thread exited while we were enumerating them
+                Err(err) if err.kind() == io::ErrorKind::NotFound => continue,
+                Err(err) => return Err(err),
+            };
+            for line in status.lines() {
+                let mut key_value = line.splitn(2, ':');
+                let key = key_value.next().unwrap_or_default();
+                let value = key_value
+                    .next()
+                    .and_then(|value| value.trim().parse::<u64>().ok());
+                match (key, value) {
+                    ("voluntary_ctxt_switches", Some(value)) => result.voluntary += value,
+                    ("nonvoluntary_ctxt_switches", Some(value)) => result.involuntary += value,
+                    _ => {}
+                }
+            }
+        }
+        Ok(result)
+    }
+
+    fn load_average() -> io::Result<LoadAverage> {
+        // /proc/loadavg starts with the 1, 5 and 15-minute load averages,
+        // followed by task counts that we do not need
+        let loadavg = fs::read_to_string("/proc/loadavg")?;
+        let mut fields = loadavg.split_whitespace().map(|field| {
+            field
+                .parse::<f64>()
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+        });
+        let mut next_field = || {
+            fields.next().unwrap_or_else(|| {
+                Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "Missing load average in /proc/loadavg",
+                ))
+            })
+        };
+        Ok(LoadAverage {
+            one: next_field()?,
+            five: next_field()?,
+            fifteen: next_field()?,
+        })
+    }
+
+    fn numa_node_memory() -> io::Result<Vec<NodeMemory>> {
+        // Kernels without NUMA support do not have the sysfs directory
+        let entries = match fs::read_dir("/sys/devices/system/node/") {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(err),
+        };
+
+        let mut result = Vec::new();
+        for entry in entries {
+            let entry = entry?;
+            let file_name = entry.file_name().to_string_lossy().into_owned();
+            const PREFIX: &str = "node";
+            if !file_name.starts_with(PREFIX) {
+                continue;
+            }
+            let node = match file_name[PREFIX.len()..].parse() {
+                Ok(node) => node,
+                Err(_) => continue,
+            };
+
+            // Node meminfo lines are of the form "Node 0 MemTotal:  1234 kB"
+            let meminfo = fs::read_to_string(entry.path().join("meminfo"))?;
+            let read_kib = |key: &str| {
+                meminfo
+                    .lines()
+                    .filter_map(|line| {
+                        let mut fields = line.split_whitespace().skip(2);
+                        Some((fields.next()?, fields.next()?))
+                    })
+                    .find(|&(line_key, _)| line_key == key)
+                    .and_then(|(_, value)| value.parse::<u64>().ok())
+                    .ok_or_else(|| {
+                        io::Error::new(io::ErrorKind::InvalidData, "Bad node meminfo format")
+                    })
+            };
+            let total_kib = read_kib("MemTotal:")?;
+            let free_kib = read_kib("MemFree:")?;
+            result.push(NodeMemory {
+                node,
+                total: Information::new::<kibibyte>(total_kib),
+                used: Information::new::<kibibyte>(total_kib.saturating_sub(free_kib)),
+            });
+        }
+        result.sort_by_key(|node| node.node);
+        Ok(result)
+    }
+}
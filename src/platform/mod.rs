@@ -0,0 +1,119 @@
+//! Platform-specific system queries
+//!
+//! heim provides a cross-platform view of most system properties, but some
+//! data can only be queried through OS-specific interfaces. These queries are
+//! centralized behind the `Platform` trait, which has an implementation for
+//! each supported OS plus a generic one that only relies on cross-platform
+//! heim APIs (and thus reports less data), so that benchmon can run everywhere
+//! heim does. The implementation for the host OS is exposed as `Host`.
+
+#[cfg(not(target_os = "linux"))]
+mod generic;
+#[cfg(target_os = "linux")]
+mod linux;
+#[cfg(windows)]
+mod windows;
+
+#[cfg(not(any(target_os = "linux", windows)))]
+pub use self::generic::Generic as Host;
+#[cfg(target_os = "linux")]
+pub use self::linux::Linux as Host;
+#[cfg(windows)]
+pub use self::windows::Windows as Host;
+
+use crate::{
+    cpu::{idle::IdleState, ContextSwitches},
+    filesystem::BlockQueue,
+    memory::{HugePagePool, MemoryController, SwapArea},
+    numa::NodeMemory,
+    os::{LoadAverage, SystemIdentity},
+    sensors::ThermalZone,
+};
+
+use futures_util::future::BoxFuture;
+
+use heim::{cpu::CpuFrequency, units::Frequency};
+
+use std::{collections::BTreeSet, io, path::Path};
+
+/// OS-specific system queries
+// TODO: Add a macOS implementation, and move more of the scattered
+//       OS-specific code (e.g. network link types) behind this trait.
+pub trait Platform {
+    /// Query the frequency of each logical CPU core, if possible
+    fn per_cpu_frequencies() -> BoxFuture<'static, heim::Result<Option<Vec<CpuFrequency>>>>;
+
+    /// Measure the current frequency of each logical CPU core, if possible
+    fn current_frequencies() -> BoxFuture<'static, heim::Result<Option<Vec<Frequency>>>>;
+
+    /// Query the model name of the host's CPU (e.g. "AMD Ryzen 5 3600")
+    fn cpu_model() -> io::Result<String>;
+
+    /// Query the feature flags of the host's CPU, named as in /proc/cpuinfo
+    fn cpu_flags() -> io::Result<BTreeSet<String>>;
+
+    /// Enumerate the idle states (aka C-states) of each logical CPU core (this
+    /// list is empty if the OS does not let us query them)
+    fn cpu_idle_states() -> io::Result<Vec<Vec<IdleState>>>;
+
+    /// Enumerate the logical CPU cores which are isolated from the scheduler's
+    /// load balancing (e.g. via the isolcpus kernel boot parameter)
+    fn isolated_cpus() -> io::Result<BTreeSet<usize>>;
+
+    /// Enumerate the logical CPU cores on which the scheduler tick is stopped
+    /// when a single task is running (e.g. via the nohz_full boot parameter)
+    fn tickless_cpus() -> io::Result<BTreeSet<usize>>;
+
+    /// Count the host's CPU sockets (aka packages), if possible
+    fn socket_count() -> Option<u64>;
+
+    /// Enumerate the host's thermal zones (see `sensors::ThermalZone`)
+    fn thermal_zones() -> io::Result<Vec<ThermalZone>>;
+
+    /// Read the raw SMBIOS/DMI tables
+    fn dmi_table() -> io::Result<Vec<u8>>;
+
+    /// Query the hardware identity of the machine, as reported by the firmware
+    /// (this commonly fails in containers)
+    fn system_identity() -> io::Result<SystemIdentity>;
+
+    /// Enumerate the swap areas (this list is empty if the OS does not let us
+    /// query them)
+    fn swap_areas() -> io::Result<Vec<SwapArea>>;
+
+    /// Enumerate the memory controllers which report memory errors, i.e. which
+    /// have ECC enabled (this list is empty if the OS does not let us query
+    /// them)
+    fn memory_controllers() -> io::Result<Vec<MemoryController>>;
+
+    /// Read the command line that the kernel was booted with
+    fn kernel_command_line() -> io::Result<String>;
+
+    /// Enumerate the pools of huge pages (this list is empty if the OS does not
+    /// support huge pages, or does not let us query them)
+    fn huge_page_pools() -> io::Result<Vec<HugePagePool>>;
+
+    /// Query the request queue settings of the physical block device which
+    /// backs a certain device file (e.g. a partition)
+    fn block_queue(device: &Path) -> io::Result<BlockQueue>;
+
+    /// Tell whether a block device, named as in heim's disk I/O counters, is a
+    /// physical disk rather than a partition or a virtual block device, if the
+    /// OS lets us tell them apart
+    fn is_physical_disk(name: &str) -> Option<bool>;
+
+    /// Query the options of the filesystem mounted at a certain mount point
+    /// (e.g. "rw", "noatime" or "data=ordered")
+    fn mount_options(mount_point: &Path) -> io::Result<Vec<String>>;
+
+    /// Query the memory of each NUMA node (this list is empty if the OS does
+    /// not let us query it)
+    fn numa_node_memory() -> io::Result<Vec<NodeMemory>>;
+
+    /// Count the context switches of every thread of a process since it was
+    /// started, broken down by cause
+    fn process_context_switches(pid: u32) -> io::Result<ContextSwitches>;
+
+    /// Query the 1, 5 and 15-minute system load averages
+    fn load_average() -> io::Result<LoadAverage>;
+}
@@ -0,0 +1,173 @@
+//! Windows-specific system queries
+
+use super::{generic::Generic, Platform};
+
+use crate::{
+    cpu::{idle::IdleState, ContextSwitches},
+    filesystem::BlockQueue,
+    memory::{HugePagePool, MemoryController, SwapArea},
+    numa::NodeMemory,
+    os::{LoadAverage, SystemIdentity},
+    sensors::ThermalZone,
+};
+
+use futures_util::future::{BoxFuture, FutureExt};
+
+use heim::{
+    cpu::CpuFrequency,
+    units::{frequency::megahertz, Frequency},
+};
+
+use std::{collections::BTreeSet, ffi::c_void, io, mem, path::Path};
+
+/// Per-processor power information, as reported by CallNtPowerInformation
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+struct ProcessorPowerInformation {
+    number: u32,
+    max_mhz: u32,
+    current_mhz: u32,
+    mhz_limit: u32,
+    max_idle_state: u32,
+    current_idle_state: u32,
+}
+
+/// Value of the POWER_INFORMATION_LEVEL enum which queries per-processor
+/// power information
+const PROCESSOR_INFORMATION: i32 = 11;
+
+#[link(name = "powrprof")]
+extern "system" {
+    fn CallNtPowerInformation(
+        information_level: i32,
+        input_buffer: *mut c_void,
+        input_buffer_length: u32,
+        output_buffer: *mut c_void,
+        output_buffer_length: u32,
+    ) -> i32;
+}
+
+/// Windows implementation of `Platform`, which complements cross-platform heim
+/// APIs with Win32 power management queries
+pub struct Windows;
+
+impl Platform for Windows {
+    fn per_cpu_frequencies() -> BoxFuture<'static, heim::Result<Option<Vec<CpuFrequency>>>> {
+        Generic::per_cpu_frequencies()
+    }
+
+    // NOTE: CallNtPowerInformation only reports on the processors of the
+    //       calling thread's processor group, i.e. at most 64 logical CPUs.
+    //       On bigger systems, we report nothing rather than partial data.
+    fn current_frequencies() -> BoxFuture<'static, heim::Result<Option<Vec<Frequency>>>> {
+        async {
+            const MAX_GROUP_SIZE: usize = 64;
+            let logical_count = heim::cpu::logical_count().await? as usize;
+            if logical_count > MAX_GROUP_SIZE {
+                return Ok(None);
+            }
+            let mut infos = vec![ProcessorPowerInformation::default(); logical_count];
+            let output_length = mem::size_of_val(&infos[..]) as u32;
+            // This is safe because the output buffer is valid for writes of
+            // the specified length, and the input buffer is unused.
+            let status = unsafe {
+                CallNtPowerInformation(
+                    PROCESSOR_INFORMATION,
+                    std::ptr::null_mut(),
+                    0,
+                    infos.as_mut_ptr() as *mut c_void,
+                    output_length,
+                )
+            };
+
+            // If the query failed, callers will fall back to the static
+            // frequency range reported by heim
+            if status != 0 {
+                return Ok(None);
+            }
+            Ok(Some(
+                infos
+                    .into_iter()
+                    .map(|info| Frequency::new::<megahertz>(info.current_mhz.into()))
+                    .collect(),
+            ))
+        }
+        .boxed()
+    }
+
+    fn cpu_model() -> io::Result<String> {
+        Generic::cpu_model()
+    }
+
+    fn cpu_flags() -> io::Result<BTreeSet<String>> {
+        Generic::cpu_flags()
+    }
+
+    fn cpu_idle_states() -> io::Result<Vec<Vec<IdleState>>> {
+        Generic::cpu_idle_states()
+    }
+
+    fn isolated_cpus() -> io::Result<BTreeSet<usize>> {
+        Generic::isolated_cpus()
+    }
+
+    fn tickless_cpus() -> io::Result<BTreeSet<usize>> {
+        Generic::tickless_cpus()
+    }
+
+    fn socket_count() -> Option<u64> {
+        Generic::socket_count()
+    }
+
+    fn thermal_zones() -> io::Result<Vec<ThermalZone>> {
+        Generic::thermal_zones()
+    }
+
+    fn dmi_table() -> io::Result<Vec<u8>> {
+        Generic::dmi_table()
+    }
+
+    fn system_identity() -> io::Result<SystemIdentity> {
+        Generic::system_identity()
+    }
+
+    fn swap_areas() -> io::Result<Vec<SwapArea>> {
+        Generic::swap_areas()
+    }
+
+    fn memory_controllers() -> io::Result<Vec<MemoryController>> {
+        Generic::memory_controllers()
+    }
+
+    fn kernel_command_line() -> io::Result<String> {
+        Generic::kernel_command_line()
+    }
+
+    fn huge_page_pools() -> io::Result<Vec<HugePagePool>> {
+        Generic::huge_page_pools()
+    }
+
+    fn block_queue(device: &Path) -> io::Result<BlockQueue> {
+        Generic::block_queue(device)
+    }
+
+    fn is_physical_disk(name: &str) -> Option<bool> {
+        Generic::is_physical_disk(name)
+    }
+
+    fn mount_options(mount_point: &Path) -> io::Result<Vec<String>> {
+        Generic::mount_options(mount_point)
+    }
+
+    fn numa_node_memory() -> io::Result<Vec<NodeMemory>> {
+        Generic::numa_node_memory()
+    }
+
+    fn process_context_switches(pid: u32) -> io::Result<ContextSwitches> {
+        Generic::process_context_switches(pid)
+    }
+
+    fn load_average() -> io::Result<LoadAverage> {
+        Generic::load_average()
+    }
+}
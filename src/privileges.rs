@@ -0,0 +1,48 @@
+//! Check the privileges that benchmon is running with
+
+use crate::warning::BenchmonWarning;
+
+use slog::{crit, warn, Logger};
+
+/// Truth that benchmon is running with superuser privileges
+pub fn is_superuser() -> bool {
+    #[cfg(unix)]
+    {
+        // This is safe because geteuid() cannot fail and has no side effect
+        unsafe { libc::geteuid() == 0 }
+    }
+
+    // TODO: Check for administrator privileges on other OSes
+    #[cfg(not(unix))]
+    {
+        false
+    }
+}
+
+/// Check the privileges that benchmon is running with on application startup,
+/// and tell if it should proceed with the monitoring.
+pub fn startup_check(log: &Logger, require_unprivileged: bool) -> bool {
+    if !is_superuser() {
+        return true;
+    }
+
+    if require_unprivileged {
+        crit!(
+            log,
+            "Refusing to run with superuser privileges, as requested. Consider \
+             granting the CAP_SYS_PTRACE and CAP_DAC_READ_SEARCH capabilities \
+             to an unprivileged benchmon instead."
+        );
+        false
+    } else {
+        warn!(
+            log,
+            "Running with superuser privileges. This gives access to all process \
+             info, but any mistake could interfere with the system. Consider \
+             granting the CAP_SYS_PTRACE and CAP_DAC_READ_SEARCH capabilities \
+             to an unprivileged benchmon instead.";
+            "warning" => BenchmonWarning::Superuser
+        );
+        true
+    }
+}
@@ -1,10 +1,15 @@
+use crate::{format, privileges, time::Timezone, warning::BenchmonWarning};
+
 use chrono::{DateTime, Local};
 
+use futures_util::stream::{StreamExt, TryStreamExt};
+
 use heim::{
-    process::{Command, Pid, Process, ProcessError},
+    process::{Command, CpuTime, Memory, Pid, Process, ProcessError},
     units::{
+        information::byte,
         time::{nanosecond, second},
-        Time,
+        Information, Time,
     },
 };
 
@@ -115,17 +120,50 @@ impl FromIterator<(Pid, Result<ProcessInfo, ProcessInfoError>)> for ProcessTree
 
 impl ProcessTree {
     /// Log the contents of the process tree (for the benchmon startup report)
-    pub fn log(&self, log: &Logger) {
+    ///
+    /// If activity thresholds are specified, processes whose resource usage is
+    /// below these thresholds are not logged, and their children are logged
+    /// as children of their nearest logged ancestor.
+    ///
+    /// Creation times are displayed in the specified timezone.
+    ///
+    pub fn log(
+        &self,
+        log: &Logger,
+        activity_thresholds: Option<&ActivityThresholds>,
+        timezone: Timezone,
+    ) {
+        let now = SystemTime::now();
         for &root_pid in &self.roots {
-            self.log_subtree(&log, root_pid);
+            self.log_subtree(&log, root_pid, activity_thresholds, now, timezone);
         }
     }
 
     /// Log a subtree of the process tree
-    fn log_subtree(&self, log: &Logger, current_pid: Pid) {
+    fn log_subtree(
+        &self,
+        log: &Logger,
+        current_pid: Pid,
+        activity_thresholds: Option<&ActivityThresholds>,
+        now: SystemTime,
+        timezone: Timezone,
+    ) {
         // Get the tree node associated with the current process
         let current_node = &self.nodes[&current_pid];
 
+        // Prune idle processes if asked to, keeping their children attached to
+        // the nearest logged ancestor
+        if let (Some(thresholds), Ok(process_info)) =
+            (activity_thresholds, &current_node.process_info)
+        {
+            if !thresholds.is_active(process_info, now) {
+                for &child_pid in &current_node.children {
+                    self.log_subtree(log, child_pid, activity_thresholds, now, timezone);
+                }
+                return;
+            }
+        }
+
         // Log the info from that node
         match &current_node.process_info {
             Ok(process_info) => {
@@ -165,17 +203,30 @@ impl ProcessTree {
                         let nsecs = create_time.get::<nanosecond>() - 1_000_000_000.0 * secs;
                         let duration = Duration::new(secs as u64, nsecs as u32);
                         let system_time = SystemTime::UNIX_EPOCH + duration;
-                        let date_time = DateTime::<Local>::from(system_time);
+                        let date_time = timezone.convert(DateTime::<Local>::from(system_time));
                         format!("{}", date_time).into()
                     }
                     Err(err) => print_err(err),
                 };
+                let process_cpu_time = match &process_info.cpu_time {
+                    Ok(cpu_time) => {
+                        let total_time = cpu_time.user() + cpu_time.system();
+                        format!("{:.2}", total_time.get::<second>()).into()
+                    }
+                    Err(err) => print_err(err),
+                };
+                let process_rss = match &process_info.memory {
+                    Ok(memory) => format!("{}", format::display_information(memory.rss())).into(),
+                    Err(err) => print_err(err),
+                };
                 info!(log, "Found a process";
                       "pid" => current_pid,
                       "name" => %process_name,
                       "executable path" => %process_exe,
                       "command line" => %process_command,
-                      "creation time" => %process_create_time);
+                      "creation time" => %process_create_time,
+                      "CPU time (s)" => %process_cpu_time,
+                      "resident memory" => %process_rss);
             }
 
             Err(ProcessInfoError::AccessDenied) => {
@@ -191,14 +242,15 @@ impl ProcessTree {
 
             Err(ProcessInfoError::ZombieProcess) => {
                 warn!(log, "Found a process in the zombie state";
-                      "pid" => current_pid);
+                      "pid" => current_pid,
+                      "warning" => BenchmonWarning::ZombieProcess);
             }
         }
 
         // Recursively log info about child nodes
         let children_log = log.new(o!("parent pid" => current_pid));
         for &child_pid in &current_node.children {
-            self.log_subtree(&children_log, child_pid);
+            self.log_subtree(&children_log, child_pid, activity_thresholds, now, timezone);
         }
     }
 }
@@ -225,20 +277,81 @@ struct ProcessTreeNode {
 /// Result of a detailed initial process info query.
 pub struct ProcessInfo {
     /// PID of the parent process
-    parent_pid: Result<Pid, ProcessInfoFieldError>,
+    pub parent_pid: Result<Pid, ProcessInfoFieldError>,
 
     /// Name of this process
-    name: Result<String, ProcessInfoFieldError>,
+    pub name: Result<String, ProcessInfoFieldError>,
 
     /// Path to this process' executable
-    exe: Result<PathBuf, ProcessInfoFieldError>,
+    pub exe: Result<PathBuf, ProcessInfoFieldError>,
 
     /// Command line with which the process was invoked
-    command: Result<Command, ProcessInfoFieldError>,
+    pub command: Result<Command, ProcessInfoFieldError>,
 
     /// Time at which the process was created, since Unix epoch
     // FIXME: Ask heim to use SystemTime instead
-    create_time: Result<Time, ProcessInfoFieldError>,
+    pub create_time: Result<Time, ProcessInfoFieldError>,
+
+    /// CPU time consumed by this process since its creation
+    pub cpu_time: Result<CpuTime, ProcessInfoFieldError>,
+
+    /// Memory usage of this process
+    pub memory: Result<Memory, ProcessInfoFieldError>,
+}
+
+impl ProcessInfo {
+    /// Truth that some of this process' info could not be queried because we
+    /// did not have enough permissions
+    fn is_partially_denied(&self) -> bool {
+        let denied =
+            |err: Option<&ProcessInfoFieldError>| err == Some(&ProcessInfoFieldError::AccessDenied);
+        denied(self.parent_pid.as_ref().err())
+            || denied(self.name.as_ref().err())
+            || denied(self.exe.as_ref().err())
+            || denied(self.command.as_ref().err())
+            || denied(self.create_time.as_ref().err())
+            || denied(self.cpu_time.as_ref().err())
+            || denied(self.memory.as_ref().err())
+    }
+}
+
+/// Resource usage thresholds below which a process is considered idle
+#[derive(Clone, Copy, Debug)]
+pub struct ActivityThresholds {
+    /// Minimal fraction of a CPU core that a process must have used on average
+    /// since its creation
+    pub cpu_frac: f64,
+
+    /// Minimal amount of resident memory that a process must be using
+    pub rss: Information,
+}
+
+impl ActivityThresholds {
+    /// Truth that a process' resource usage exceeds one of the thresholds
+    ///
+    /// Processes whose resource usage could not be queried are considered to
+    /// be active, so that they are not hidden from the report.
+    ///
+    fn is_active(&self, process_info: &ProcessInfo, now: SystemTime) -> bool {
+        let (cpu_time, memory, create_time) = match (
+            &process_info.cpu_time,
+            &process_info.memory,
+            &process_info.create_time,
+        ) {
+            (Ok(cpu_time), Ok(memory), Ok(create_time)) => (cpu_time, memory, create_time),
+            _ => return true,
+        };
+        if memory.rss().get::<byte>() >= self.rss.get::<byte>() {
+            return true;
+        }
+        let now_secs = now
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map(|since_epoch| since_epoch.as_secs_f64())
+            .unwrap_or(0.0);
+        let lifetime_secs = now_secs - create_time.get::<second>();
+        let cpu_secs = (cpu_time.user() + cpu_time.system()).get::<second>();
+        lifetime_secs <= 0.0 || cpu_secs / lifetime_secs >= self.cpu_frac
+    }
 }
 
 /// Error which can occur while fetching a specific piece of process
@@ -342,7 +455,15 @@ pub async fn get_process_info(
                     ))
                 }
             }
-            get_info_struct!(parent_pid, name, exe, command, create_time)
+            get_info_struct!(
+                parent_pid,
+                name,
+                exe,
+                command,
+                create_time,
+                cpu_time,
+                memory
+            )
         }
 
         // Process doesn't exist anymore, most likely some kind of race
@@ -372,10 +493,274 @@ pub async fn get_process_info(
     }
 }
 
-/// Report on the host's running processes
-pub fn startup_report(log: &Logger, processes: Vec<(Pid, Result<ProcessInfo, ProcessInfoError>)>) {
+/// Enumerate the host's running processes and collect as much info as possible
+/// about them (see `get_process_info()` for error handling details)
+pub async fn collect() -> heim::Result<Vec<(Pid, Result<ProcessInfo, ProcessInfoError>)>> {
+    heim::process::processes()
+        .then(get_process_info)
+        .try_collect::<Vec<_>>()
+        .await
+}
+
+/// Truth that a process name matches an entry of the noisy process watchlist
+///
+/// Linux truncates process names to 15 bytes, so a truncated name is also
+/// considered to match a longer watchlist entry which it is a prefix of.
+///
+fn is_noisy(name: &str, noisy_processes: &[String]) -> bool {
+    const MAX_LINUX_NAME_LEN: usize = 15;
+    noisy_processes.iter().any(|noisy_name| {
+        name == noisy_name
+            || (cfg!(target_os = "linux")
+                && name.len() == MAX_LINUX_NAME_LEN
+                && noisy_name.starts_with(name))
+    })
+}
+
+/// Report on the host's running processes, warning about those whose name
+/// appears in the watchlist of known-noisy processes
+///
+/// If activity thresholds are specified, processes which use less resources
+/// than that are pruned from the reported process tree. Process creation times
+/// are displayed in the specified timezone.
+///
+pub fn startup_report(
+    log: &Logger,
+    processes: Vec<(Pid, Result<ProcessInfo, ProcessInfoError>)>,
+    noisy_processes: &[String],
+    activity_thresholds: Option<ActivityThresholds>,
+    timezone: Timezone,
+) {
+    // Check how many processes we could not fully query due to a lack of
+    // permissions, as that means that the report will be incomplete.
+    let num_processes = processes.len();
+    let num_access_denied = processes
+        .iter()
+        .filter(|(_pid, process_info)| match process_info {
+            Ok(info) => info.is_partially_denied(),
+            Err(err) => *err == ProcessInfoError::AccessDenied,
+        })
+        .count();
+
+    // Warn about running processes which are known to cause background load
+    for (pid, process_info) in &processes {
+        if let Ok(ProcessInfo { name: Ok(name), .. }) = process_info {
+            if is_noisy(name, noisy_processes) {
+                warn!(log, "Found a process which is known to cause background \
+                            system activity, consider stopping it";
+                      "pid" => pid,
+                      "name" => name,
+                      "warning" => BenchmonWarning::NoisyProcess);
+            }
+        }
+    }
+
     // Build a process tree and log its contents
     debug!(log, "Processing process list...");
     let process_tree = ProcessTree::from_iter(processes);
-    process_tree.log(log);
+    process_tree.log(log, activity_thresholds.as_ref(), timezone);
+
+    // Hint that running with more privileges would yield a fuller report
+    if num_access_denied > num_processes / 10 && !privileges::is_superuser() {
+        warn!(log, "Access to the info of many processes was denied, running \
+                    benchmon with elevated privileges (or the CAP_SYS_PTRACE \
+                    and CAP_DAC_READ_SEARCH capabilities) would yield a fuller \
+                    process report";
+              "access denied" => num_access_denied,
+              "process count" => num_processes,
+              "warning" => BenchmonWarning::ProcessAccessDenied);
+    }
+}
+
+/// Snapshot of the host's running processes, for before/after comparisons
+///
+/// Processes are identified by their PID and creation time, so that a PID
+/// which was reused by a new process is not mistaken for the old process.
+///
+pub struct ProcessSnapshot {
+    /// Info about every process that could be fully queried
+    processes: HashMap<(Pid, Option<u64>), ProcessInfo>,
+}
+
+impl ProcessSnapshot {
+    /// Minimal change in consumed CPU time for a process to be reported
+    const MIN_CPU_TIME_CHANGE: Duration = Duration::from_secs(1);
+
+    /// Minimal change in resident memory for a process to be reported, in bytes
+    const MIN_RSS_CHANGE: u64 = 100 * 1024 * 1024;
+
+    /// Take a snapshot of the running processes
+    pub async fn take() -> heim::Result<Self> {
+        let processes = collect()
+            .await?
+            .into_iter()
+            .filter_map(|(pid, process_info)| {
+                let process_info = process_info.ok()?;
+                let create_time = process_info
+                    .create_time
+                    .as_ref()
+                    .ok()
+                    .map(|create_time| create_time.get::<nanosecond>() as u64);
+                Some(((pid, create_time), process_info))
+            })
+            .collect();
+        Ok(Self { processes })
+    }
+
+    /// Report which processes appeared, vanished, or significantly changed
+    /// their CPU or memory consumption between two snapshots
+    pub fn diff_report(&self, log: &Logger, after: &ProcessSnapshot) {
+        let name = |process_info: &ProcessInfo| {
+            process_info
+                .name
+                .as_ref()
+                .map(String::as_str)
+                .unwrap_or("<unknown>")
+                .to_owned()
+        };
+        let cpu_time = |process_info: &ProcessInfo| {
+            process_info.cpu_time.as_ref().ok().map(|cpu_time| {
+                Duration::from_secs_f64((cpu_time.user() + cpu_time.system()).get::<second>())
+            })
+        };
+        let rss = |process_info: &ProcessInfo| {
+            process_info
+                .memory
+                .as_ref()
+                .ok()
+                .map(|memory| memory.rss().get::<byte>())
+        };
+
+        let mut num_appeared = 0;
+        let mut num_changed = 0;
+        for (key, process_info) in &after.processes {
+            let (pid, _create_time) = key;
+            match self.processes.get(key) {
+                None => {
+                    num_appeared += 1;
+                    let resident_memory = match rss(process_info) {
+                        Some(rss) => {
+                            format!(
+                                "{}",
+                                format::display_information(Information::new::<byte>(rss))
+                            )
+                        }
+                        None => "unknown".to_owned(),
+                    };
+                    info!(log, "Process appeared";
+                          "pid" => pid,
+                          "name" => name(process_info),
+                          "CPU time (s)" => cpu_time(process_info).map(|t| t.as_secs_f64()),
+                          "resident memory" => resident_memory);
+                }
+                Some(old_info) => {
+                    let cpu_change = match (cpu_time(old_info), cpu_time(process_info)) {
+                        (Some(old), Some(new)) => new.checked_sub(old).unwrap_or_default(),
+                        _ => Duration::default(),
+                    };
+                    let rss_change = match (rss(old_info), rss(process_info)) {
+                        (Some(old), Some(new)) => new as i64 - old as i64,
+                        _ => 0,
+                    };
+                    if cpu_change >= Self::MIN_CPU_TIME_CHANGE
+                        || rss_change.abs() as u64 >= Self::MIN_RSS_CHANGE
+                    {
+                        num_changed += 1;
+                        const MIB: f64 = 1024.0 * 1024.0;
+                        info!(log, "Process resource usage changed";
+                              "pid" => pid,
+                              "name" => name(process_info),
+                              "consumed CPU time (s)" => cpu_change.as_secs_f64(),
+                              "resident memory change (MiB)" => rss_change as f64 / MIB);
+                    }
+                }
+            }
+        }
+
+        let mut num_vanished = 0;
+        for (key, process_info) in &self.processes {
+            if !after.processes.contains_key(key) {
+                num_vanished += 1;
+                let (pid, _create_time) = key;
+                info!(log, "Process vanished";
+                      "pid" => pid,
+                      "name" => name(process_info));
+            }
+        }
+
+        info!(log, "Compared process snapshots";
+              "appeared" => num_appeared,
+              "vanished" => num_vanished,
+              "changed" => num_changed);
+    }
+}
+
+/// Report on benchmon's own resource usage, to quantify monitoring overhead
+pub fn self_usage_report(log: &Logger) {
+    usage_report(log, UsageTarget::SelfProcess);
+}
+
+/// Report on the resource usage of the terminated child processes of
+/// benchmon, e.g. a benchmark that was started in runner mode
+pub fn child_usage_report(log: &Logger) {
+    usage_report(log, UsageTarget::Children);
+}
+
+/// Process(es) whose resource usage should be reported
+#[derive(Clone, Copy)]
+enum UsageTarget {
+    /// benchmon itself
+    SelfProcess,
+
+    /// Terminated and waited-for child processes of benchmon
+    Children,
+}
+
+/// Report on the resource usage of benchmon or its children
+// TODO: Query this on other OSes (e.g. via GetProcessTimes and
+//       GetProcessMemoryInfo on Windows)
+fn usage_report(log: &Logger, target: UsageTarget) {
+    let whose = match target {
+        UsageTarget::SelfProcess => "benchmon's own",
+        UsageTarget::Children => "the benchmark's",
+    };
+
+    #[cfg(unix)]
+    {
+        let who = match target {
+            UsageTarget::SelfProcess => libc::RUSAGE_SELF,
+            UsageTarget::Children => libc::RUSAGE_CHILDREN,
+        };
+
+        // This is safe because getrusage only writes to the provided struct
+        let mut usage = std::mem::MaybeUninit::<libc::rusage>::uninit();
+        if unsafe { libc::getrusage(who, usage.as_mut_ptr()) } != 0 {
+            debug!(log, "{} resource usage is unavailable", whose;
+                   "error" => %std::io::Error::last_os_error());
+            return;
+        }
+        // This is safe because getrusage succeeded, so it initialized usage
+        let usage = unsafe { usage.assume_init() };
+        let to_secs = |time: libc::timeval| time.tv_sec as f64 + time.tv_usec as f64 * 1e-6;
+
+        // The peak resident set size is in bytes on macOS, KiB elsewhere
+        //
+        // NOTE: For children, this is the peak RSS of the largest child, not
+        //       the sum of all children's peak RSS.
+        //
+        let max_rss = Information::new::<byte>(if cfg!(target_os = "macos") {
+            usage.ru_maxrss as u64
+        } else {
+            usage.ru_maxrss as u64 * 1024
+        });
+        info!(log, "Measured {} resource usage", whose;
+              "user CPU time (s)" => to_secs(usage.ru_utime),
+              "system CPU time (s)" => to_secs(usage.ru_stime),
+              "peak resident memory" => %format::display_information(max_rss));
+    }
+
+    #[cfg(not(unix))]
+    {
+        debug!(log, "{} resource usage can only be measured on Unix", whose);
+    }
 }
@@ -0,0 +1,226 @@
+//! Linux Pressure Stall Information (PSI)
+//!
+//! PSI tells which fraction of time some tasks were stalled waiting for a given
+//! resource (CPU, memory or I/O), which is the most direct signal of resource
+//! contention. It is available through the /proc/pressure/ directory on Linux
+//! 4.20 and later, if it is enabled in the kernel configuration.
+
+use crate::warning::BenchmonWarning;
+
+use slog::{debug, info, warn, Logger};
+
+use std::{
+    fs, io,
+    time::{Duration, Instant},
+};
+
+/// Resource which tasks can be stalled on
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Resource {
+    Cpu,
+    Memory,
+    Io,
+}
+
+impl Resource {
+    /// Every resource that PSI reports on
+    pub const ALL: [Resource; 3] = [Resource::Cpu, Resource::Memory, Resource::Io];
+
+    /// Name of this resource in the /proc/pressure/ directory
+    pub fn name(self) -> &'static str {
+        match self {
+            Resource::Cpu => "cpu",
+            Resource::Memory => "memory",
+            Resource::Io => "io",
+        }
+    }
+}
+
+/// Stall statistics for a given resource
+///
+/// Averages are fractions of time over the last 10, 60 and 300 seconds, and
+/// the total is the overall stall time since boot.
+///
+struct StallStats {
+    avg10: f32,
+    avg60: f32,
+    avg300: f32,
+    total: Duration,
+}
+
+impl StallStats {
+    /// Parse a line of a /proc/pressure/ file, which looks like
+    /// "some avg10=0.00 avg60=0.00 avg300=0.00 total=0"
+    fn parse(line: &str) -> Option<Self> {
+        let mut avgs = [None; 3];
+        let mut total = None;
+        for field in line.split_whitespace().skip(1) {
+            let mut key_value = field.splitn(2, '=');
+            let (key, value) = (key_value.next()?, key_value.next()?);
+            match key {
+                "avg10" => avgs[0] = Some(value.parse::<f32>().ok()? / 100.0),
+                "avg60" => avgs[1] = Some(value.parse::<f32>().ok()? / 100.0),
+                "avg300" => avgs[2] = Some(value.parse::<f32>().ok()? / 100.0),
+                "total" => total = Some(Duration::from_micros(value.parse().ok()?)),
+                _ => {}
+            }
+        }
+        Some(Self {
+            avg10: avgs[0]?,
+            avg60: avgs[1]?,
+            avg300: avgs[2]?,
+            total: total?,
+        })
+    }
+}
+
+/// Read the "some" stall statistics of a resource, i.e. those which account
+/// for the time during which at least one task was stalled on it
+fn read_some(resource: Resource) -> io::Result<StallStats> {
+    let contents = fs::read_to_string(format!("/proc/pressure/{}", resource.name()))?;
+    contents
+        .lines()
+        .find(|line| line.starts_with("some "))
+        .and_then(StallStats::parse)
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Unexpected /proc/pressure/ file format",
+            )
+        })
+}
+
+/// Truth that PSI is available on this system
+pub fn is_supported() -> bool {
+    read_some(Resource::Cpu).is_ok()
+}
+
+/// Report on the resource pressure of the host system at startup time
+pub fn startup_report(log: &Logger) {
+    for &resource in &Resource::ALL {
+        match read_some(resource) {
+            Ok(stats) => {
+                info!(log, "Received resource pressure information";
+                      "resource" => resource.name(),
+                      "some avg10 (%)" => stats.avg10 * 100.0,
+                      "some avg60 (%)" => stats.avg60 * 100.0,
+                      "some avg300 (%)" => stats.avg300 * 100.0);
+            }
+            Err(err) => {
+                debug!(log, "Resource pressure information is unavailable \
+                             (needs Linux 4.20+ with PSI enabled)";
+                       "resource" => resource.name(),
+                       "error" => %err);
+                return;
+            }
+        }
+    }
+}
+
+/// Fraction of time during which some tasks were stalled on each resource
+#[derive(Clone, Copy, Debug)]
+pub struct SomePressure {
+    pub cpu: f32,
+    pub memory: f32,
+    pub io: f32,
+}
+
+impl SomePressure {
+    /// Fraction of time during which some tasks were stalled on a resource
+    pub fn get(&self, resource: Resource) -> f32 {
+        match resource {
+            Resource::Cpu => self.cpu,
+            Resource::Memory => self.memory,
+            Resource::Io => self.io,
+        }
+    }
+}
+
+/// Resource pressure monitoring mechanism
+pub struct Monitor {
+    /// Last measured total stall times (in `Resource::ALL` order) + associated
+    /// timestamp
+    totals: ([Duration; 3], Instant),
+
+    /// Pressure over the last minute before monitoring started, which sharp
+    /// pressure rises are detected against
+    baselines: [f32; 3],
+
+    /// Truth that a sharp pressure rise was already reported for a resource
+    reported_rises: [bool; 3],
+}
+
+impl Monitor {
+    /// Increase of the pressure above its baseline, beyond which interference
+    /// with the benchmark is considered likely
+    const RISE_THRESHOLD: f32 = 0.1;
+
+    /// Set up resource pressure monitoring
+    pub fn new() -> io::Result<Self> {
+        let mut totals = [Duration::default(); 3];
+        let mut baselines = [0.0; 3];
+        for (idx, &resource) in Resource::ALL.iter().enumerate() {
+            let stats = read_some(resource)?;
+            totals[idx] = stats.total;
+            baselines[idx] = stats.avg60;
+        }
+        Ok(Self {
+            totals: (totals, Instant::now()),
+            baselines,
+            reported_rises: [false; 3],
+        })
+    }
+
+    /// Report the resource pressure since the last measurement
+    pub fn pressure_change(&mut self) -> io::Result<SomePressure> {
+        let mut totals = [Duration::default(); 3];
+        for (idx, &resource) in Resource::ALL.iter().enumerate() {
+            totals[idx] = read_some(resource)?.total;
+        }
+        let timestamp = Instant::now();
+        let (old_totals, old_timestamp) = &self.totals;
+
+        let elapsed_secs = (timestamp - *old_timestamp).as_secs_f32();
+        let to_frac = |idx: usize| {
+            let stall_secs = totals[idx]
+                .checked_sub(old_totals[idx])
+                .unwrap_or_default()
+                .as_secs_f32();
+            if elapsed_secs > 0.0 {
+                (stall_secs / elapsed_secs).min(1.0)
+            } else {
+                0.0
+            }
+        };
+        let result = SomePressure {
+            cpu: to_frac(0),
+            memory: to_frac(1),
+            io: to_frac(2),
+        };
+
+        self.totals = (totals, timestamp);
+        Ok(result)
+    }
+
+    /// Warn about resources whose pressure rose sharply above its baseline
+    ///
+    /// Each resource is only reported once, to avoid flooding the logs when the
+    /// system remains under pressure.
+    ///
+    pub fn check_rise(&mut self, log: &Logger, pressure: &SomePressure) {
+        for (idx, &resource) in Resource::ALL.iter().enumerate() {
+            let current = pressure.get(resource);
+            let baseline = self.baselines[idx];
+            if self.reported_rises[idx] || current <= baseline + Self::RISE_THRESHOLD {
+                continue;
+            }
+            self.reported_rises[idx] = true;
+            warn!(log, "Resource pressure rose sharply during monitoring, \
+                        something is likely interfering with the benchmark";
+                  "resource" => resource.name(),
+                  "baseline (%)" => baseline * 100.0,
+                  "current (%)" => current * 100.0,
+                  "warning" => BenchmonWarning::PressureRise);
+        }
+    }
+}
@@ -0,0 +1,417 @@
+//! One-shot report on the host system's configuration
+//!
+//! The startup report is produced in two steps: `system_report()` queries the
+//! host system's configuration without logging anything, then the
+//! `startup_report()` function of each module logs a section of the result.
+//! This allows the configuration data to be used for other purposes than
+//! logging, e.g. comparing the configuration of two machines.
+
+use crate::{
+    cpu,
+    fingerprint::{self, MachineSpec},
+    memory::{self, MemoryModule},
+    os::SystemIdentity,
+    platform::{Host, Platform},
+    process,
+    process::{ProcessInfo, ProcessInfoError},
+    sensors::{self, ThermalZone},
+};
+
+use async_std::{future, task};
+
+use futures_util::{
+    future::{FutureExt, TryFutureExt},
+    stream::TryStreamExt,
+    try_join,
+};
+
+use heim::{
+    cpu::CpuFrequency,
+    disk::{Partition, Usage},
+    host::{Platform as HostPlatform, User},
+    memory::{Memory, Swap},
+    net::Nic,
+    process::Pid,
+    sensors::TemperatureSensor,
+    units::{frequency::megahertz, information::byte, Information},
+    virt::Virtualization,
+};
+
+use serde::{Deserialize, Serialize};
+
+use std::{io, time::Duration};
+
+/// Tuning knobs of the system report
+pub struct ReportOptions {
+    /// Delay after which a filesystem's usage query is abandoned, in order to
+    /// avoid stalling the whole report on a stuck network mount
+    pub disk_usage_timeout: Duration,
+
+    /// Query the installed memory modules (this usually requires superuser
+    /// privileges on Linux)
+    pub dmi: bool,
+}
+
+impl Default for ReportOptions {
+    fn default() -> Self {
+        Self {
+            disk_usage_timeout: Duration::from_secs(1),
+            dmi: false,
+        }
+    }
+}
+
+/// Configuration of the host system
+///
+/// CPU and memory configuration are considered critical, so failure to query
+/// them makes the whole report fail. Other sections are queried on a
+/// best-effort basis, as some of them are commonly unavailable in containers
+/// and other minimal environments, so they carry their own query result.
+///
+pub struct SystemReport {
+    /// Number of logical CPU cores
+    pub logical_cpus: u64,
+
+    /// Number of physical CPU cores, if known
+    pub physical_cpus: Option<u64>,
+
+    /// Number of CPU sockets, if known
+    pub cpu_sockets: Option<u64>,
+
+    /// System-wide CPU frequency range
+    pub global_cpu_freq: CpuFrequency,
+
+    /// Frequency range of each logical CPU core, if known
+    pub per_cpu_freqs: Option<Vec<CpuFrequency>>,
+
+    /// RAM configuration
+    pub memory: Memory,
+
+    /// Swap configuration
+    pub swap: Swap,
+
+    /// Installed memory modules, if they were queried
+    pub memory_modules: Option<io::Result<Vec<MemoryModule>>>,
+
+    /// Mounted filesystems and their usage
+    pub disk_partitions_and_usage: heim::Result<Vec<(Partition, heim::Result<Usage>)>>,
+
+    /// Network interfaces
+    pub network_interfaces: heim::Result<Vec<Nic>>,
+
+    /// Temperature sensors and thermal zones
+    pub temperatures_and_zones: heim::Result<(Vec<TemperatureSensor>, Vec<ThermalZone>)>,
+
+    /// Operating system and virtualization layer
+    pub platform_and_virt: heim::Result<(HostPlatform, Option<Virtualization>)>,
+
+    /// Hardware identity of the machine
+    pub system_identity: io::Result<SystemIdentity>,
+
+    /// Open user sessions
+    pub user_connections: heim::Result<Vec<User>>,
+
+    /// Running processes
+    pub processes: heim::Result<Vec<(Pid, Result<ProcessInfo, ProcessInfoError>)>>,
+
+    /// Key characteristics of the machine, used for fingerprinting
+    pub machine_spec: MachineSpec,
+}
+
+/// Query the configuration of the host system
+pub async fn system_report(options: &ReportOptions) -> heim::Result<SystemReport> {
+    // Ask heim to start fetching all the system info we need...
+    // (with a bit of future boxing here and there to reduce type complexity)
+    // - CPU info
+    let global_cpu_freq = heim::cpu::frequency().boxed();
+    let per_cpu_freqs = Host::per_cpu_frequencies();
+    let logical_cpus = heim::cpu::logical_count();
+    let physical_cpus = heim::cpu::physical_count();
+    // - Platform info (= OS info + CPU architecture)
+    let platform = heim::host::platform();
+    // - Memory info
+    let memory = heim::memory::memory();
+    let swap = heim::memory::swap();
+    // - Filesystem info
+    //
+    // NOTE: heim queries disk usage synchronously, so we move these queries to
+    //       blocking threads, with a timeout, in order to avoid stalling the
+    //       whole report when a network mount is stuck.
+    //
+    const MAX_CONCURRENT_DISK_USAGE_QUERIES: usize = 8;
+    let disk_usage_timeout = options.disk_usage_timeout;
+    let disk_partitions_and_usage = heim::disk::partitions()
+        .map_ok(|partition| async move {
+            // NOTE: Failure to stat a partition is purposely treated as a
+            //       non-fatal event, unlike all other failures, as it happens
+            //       on random pseudo-filesystems that no one cares about.
+            let mount_point = partition.mount_point().to_owned();
+            let usage_query =
+                task::spawn_blocking(move || task::block_on(heim::disk::usage(mount_point)));
+            let usage_result = future::timeout(disk_usage_timeout, usage_query)
+                .await
+                .unwrap_or_else(|_timeout| {
+                    Err(
+                        io::Error::new(io::ErrorKind::TimedOut, "Disk usage query timed out")
+                            .into(),
+                    )
+                });
+            Ok::<_, heim::Error>((partition, usage_result))
+        })
+        .try_buffer_unordered(MAX_CONCURRENT_DISK_USAGE_QUERIES)
+        .try_collect::<Vec<_>>();
+    // - Network info
+    let network_interfaces = heim::net::nic().try_collect::<Vec<_>>();
+    // - Sensor info
+    //
+    // FIXME: This stream is where 80% of the type complexity lies (crate max
+    //        type length goes from ~230000 to ~47000 upon commenting sensor
+    //        reporting out), but I cannot box it because that causes a weird
+    //        E0308 "one type is more general than the other" error.
+    //
+    //        There are multiple reports of similar confusing errors on the
+    //        rustc bugtracker, I subscribed to those for now and will try again
+    //        after they are fixed.
+    //
+    let temperatures = heim::sensors::temperatures().try_collect::<Vec<_>>();
+    // - Virtualization info
+    let virt = heim::virt::detect().map(Ok).boxed();
+    // - User connexion info
+    let user_connections = heim::host::users().try_collect::<Vec<_>>();
+    // - Initial processes info
+    let processes = process::collect();
+
+    // Query the critical CPU and memory configuration
+    let (logical_cpus, physical_cpus, global_cpu_freq, per_cpu_freqs) =
+        try_join!(logical_cpus, physical_cpus, global_cpu_freq, per_cpu_freqs)?;
+    let (memory, swap) = try_join!(memory, swap)?;
+    let memory_modules = if options.dmi {
+        Some(memory::memory_modules())
+    } else {
+        None
+    };
+
+    // Query the other sections on a best-effort basis
+    let disk_partitions_and_usage = disk_partitions_and_usage.await;
+    let network_interfaces = network_interfaces.await;
+    let temperatures_and_zones = temperatures
+        .await
+        .and_then(|temperatures| Ok((temperatures, sensors::thermal_zones()?)));
+    let platform_and_virt = try_join!(platform, virt);
+    let system_identity = Host::system_identity();
+    let user_connections = user_connections.await;
+    let processes = processes.await;
+
+    // Summarize the key machine characteristics
+    let machine_spec = MachineSpec {
+        cpu_model: Host::cpu_model().ok(),
+        logical_cpus,
+        physical_cpus,
+        cpu_sockets: cpu::socket_count(),
+        min_cpu_freq: global_cpu_freq.min(),
+        max_cpu_freq: global_cpu_freq.max(),
+        ram_size: memory.total(),
+        kernel_release: platform_and_virt
+            .as_ref()
+            .ok()
+            .map(|(platform, _virt)| platform.release().to_owned()),
+    };
+
+    Ok(SystemReport {
+        logical_cpus,
+        physical_cpus,
+        cpu_sockets: machine_spec.cpu_sockets,
+        global_cpu_freq,
+        per_cpu_freqs,
+        memory,
+        swap,
+        memory_modules,
+        disk_partitions_and_usage,
+        network_interfaces,
+        temperatures_and_zones,
+        platform_and_virt,
+        system_identity,
+        user_connections,
+        processes,
+        machine_spec,
+    })
+}
+
+/// Version of the field set of `JsonReport`
+///
+/// Consumers of JSON reports can rely on the set of fields and their meaning
+/// as long as this version does not change. It must be bumped whenever a field
+/// is added, removed or changes meaning.
+///
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Machine-readable summary of the system report, which can be saved as JSON
+/// and compared across machines
+///
+/// heim's types are opaque, so this only contains plain data: integers,
+/// strings, and `null` for unknown values.
+///
+#[derive(Debug, Deserialize, PartialEq, Serialize)]
+pub struct JsonReport {
+    /// Version of this field set (see `SCHEMA_VERSION`)
+    pub benchmon_schema_version: u32,
+
+    /// Version of benchmon which produced the report
+    pub benchmon_version: String,
+
+    /// Fingerprint of the machine configuration (see `fingerprint`)
+    pub fingerprint: String,
+
+    /// Version of the fingerprint's field set
+    pub fingerprint_schema_version: u32,
+
+    /// CPU model name, if known
+    pub cpu_model: Option<String>,
+
+    /// Number of logical CPU cores
+    pub logical_cpus: u64,
+
+    /// Number of physical CPU cores, if known
+    pub physical_cpus: Option<u64>,
+
+    /// Number of CPU sockets, if known
+    pub cpu_sockets: Option<u64>,
+
+    /// Minimal CPU frequency in MHz, if known
+    pub min_cpu_freq_mhz: Option<u64>,
+
+    /// Maximal CPU frequency in MHz, if known
+    pub max_cpu_freq_mhz: Option<u64>,
+
+    /// Total amount of RAM, in bytes
+    pub ram_bytes: u64,
+
+    /// Total amount of swap, in bytes
+    pub swap_bytes: u64,
+
+    /// Operating system name (e.g. "Linux"), if known
+    pub os_name: Option<String>,
+
+    /// OS kernel release, if known
+    pub kernel_release: Option<String>,
+
+    /// Virtualization scheme that the system runs on, if any was detected
+    pub virtualization: Option<String>,
+}
+
+impl JsonReport {
+    /// Summarize a system report
+    pub fn new(report: &SystemReport) -> Self {
+        let (os_name, virtualization) = match &report.platform_and_virt {
+            Ok((platform, virt)) => (
+                Some(platform.system().to_owned()),
+                virt.as_ref().map(|virt| format!("{:?}", virt)),
+            ),
+            Err(_) => (None, None),
+        };
+        Self::from_parts(
+            &report.machine_spec,
+            report.swap.total(),
+            os_name,
+            virtualization,
+        )
+    }
+
+    /// Build a report from the machine characteristics and other plain data
+    fn from_parts(
+        spec: &MachineSpec,
+        swap_size: Information,
+        os_name: Option<String>,
+        virtualization: Option<String>,
+    ) -> Self {
+        Self {
+            benchmon_schema_version: SCHEMA_VERSION,
+            benchmon_version: env!("CARGO_PKG_VERSION").to_owned(),
+            fingerprint: spec.fingerprint(),
+            fingerprint_schema_version: fingerprint::SCHEMA_VERSION,
+            cpu_model: spec.cpu_model.clone(),
+            logical_cpus: spec.logical_cpus,
+            physical_cpus: spec.physical_cpus,
+            cpu_sockets: spec.cpu_sockets,
+            min_cpu_freq_mhz: spec.min_cpu_freq.map(|freq| freq.get::<megahertz>()),
+            max_cpu_freq_mhz: spec.max_cpu_freq.map(|freq| freq.get::<megahertz>()),
+            ram_bytes: spec.ram_size.get::<byte>(),
+            swap_bytes: swap_size.get::<byte>(),
+            os_name,
+            kernel_release: spec.kernel_release.clone(),
+            virtualization,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use heim::units::Frequency;
+
+    #[test]
+    fn json_report_versions() {
+        let spec = MachineSpec {
+            cpu_model: Some("Test CPU".to_owned()),
+            logical_cpus: 8,
+            physical_cpus: Some(4),
+            cpu_sockets: None,
+            min_cpu_freq: Some(Frequency::new::<megahertz>(800)),
+            max_cpu_freq: None,
+            ram_size: Information::new::<byte>(16 << 30),
+            kernel_release: Some("5.6.0".to_owned()),
+        };
+        let report = JsonReport::from_parts(
+            &spec,
+            Information::new::<byte>(0),
+            Some("Linux".to_owned()),
+            None,
+        );
+        let json = serde_json::to_string(&report).unwrap();
+
+        // The versions must be top-level fields with the expected types
+        let value = serde_json::from_str::<serde_json::Value>(&json).unwrap();
+        assert_eq!(
+            value["benchmon_schema_version"].as_u64(),
+            Some(u64::from(SCHEMA_VERSION))
+        );
+        assert_eq!(
+            value["benchmon_version"].as_str(),
+            Some(env!("CARGO_PKG_VERSION"))
+        );
+        assert!(value["max_cpu_freq_mhz"].is_null());
+
+        // The report must round-trip through JSON
+        assert_eq!(serde_json::from_str::<JsonReport>(&json).unwrap(), report);
+    }
+
+    #[async_std::test]
+    async fn plausible_system_report() {
+        let report = system_report(&ReportOptions::default()).await.unwrap();
+
+        // Every machine has at least one CPU core, and cannot have more
+        // physical cores or sockets than logical cores
+        assert!(report.logical_cpus >= 1);
+        if let Some(physical_cpus) = report.physical_cpus {
+            assert!(physical_cpus >= 1);
+            assert!(physical_cpus <= report.logical_cpus);
+            if let Some(cpu_sockets) = report.cpu_sockets {
+                assert!(cpu_sockets >= 1);
+                assert!(cpu_sockets <= physical_cpus);
+            }
+        }
+
+        // Anything that runs this test has more than a MiB of RAM, and cannot
+        // have more RAM available than it has in total
+        let ram_size = report.memory.total();
+        assert!(ram_size.get::<byte>() > 1 << 20);
+        assert!(report.memory.available() <= ram_size);
+        assert!(report.swap.used() <= report.swap.total());
+
+        // The machine specification must agree with the detailed report
+        assert_eq!(report.machine_spec.logical_cpus, report.logical_cpus);
+        assert_eq!(report.machine_spec.physical_cpus, report.physical_cpus);
+        assert_eq!(report.machine_spec.ram_size, ram_size);
+    }
+}
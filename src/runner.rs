@@ -0,0 +1,196 @@
+//! Execution of a benchmark under monitoring
+//!
+//! In runner mode, benchmon measures the system's baseline activity for a few
+//! samples, then starts the benchmark command, keeps monitoring until the
+//! benchmark exits, and finally reports its wall-clock execution time and
+//! resource usage.
+//!
+//! The benchmark's resource usage is measured using its cgroup when it runs in
+//! a cgroup v2 that it does not share with benchmon, and using getrusage()
+//! otherwise. The latter misses the benchmark's detached descendants.
+
+use crate::{cgroup::Cgroup, format, process};
+
+use heim::units::Information;
+
+use slog::{debug, info, Logger};
+
+use std::{
+    io,
+    process::{Child, Command},
+    time::{Duration, Instant},
+};
+
+/// Running benchmark
+pub struct Benchmark {
+    /// Benchmark process
+    child: Child,
+
+    /// Time at which the benchmark was started
+    start: Instant,
+
+    /// Resource accounting of the benchmark's cgroup, if available
+    cgroup: Option<CgroupTracker>,
+}
+
+/// Resource accounting of a benchmark's cgroup
+struct CgroupTracker {
+    /// Cgroup of the benchmark
+    cgroup: Cgroup,
+
+    /// CPU time consumed by the cgroup before the benchmark started
+    initial_cpu_time: Duration,
+
+    /// Highest memory usage of the cgroup that was observed so far, which is
+    /// used when the kernel does not track peak memory usage (before 5.19)
+    max_memory: Information,
+}
+
+impl CgroupTracker {
+    /// Start tracking the resource usage of a benchmark's cgroup, if it has
+    /// one which it does not share with benchmon
+    fn new(log: &Logger, pid: u32) -> Option<Self> {
+        let cgroup = match Cgroup::of_process(pid) {
+            Ok(cgroup) => cgroup,
+            Err(err) => {
+                debug!(log, "The benchmark's cgroup v2 is unavailable, its resource \
+                             usage will be measured via getrusage()";
+                       "error" => %err);
+                return None;
+            }
+        };
+        if Cgroup::of_process(std::process::id()).ok().as_ref() == Some(&cgroup) {
+            debug!(log, "The benchmark shares benchmon's cgroup, its resource usage \
+                         will be measured via getrusage()";
+                   "cgroup" => %cgroup.path().display());
+            return None;
+        }
+        let usage = match cgroup.usage() {
+            Ok(usage) => usage,
+            Err(err) => {
+                debug!(log, "Failed to query the benchmark's cgroup, its resource \
+                             usage will be measured via getrusage()";
+                       "cgroup" => %cgroup.path().display(),
+                       "error" => %err);
+                return None;
+            }
+        };
+        info!(log, "Measuring the benchmark's resource usage via its cgroup";
+              "cgroup" => %cgroup.path().display());
+        Some(Self {
+            cgroup,
+            initial_cpu_time: usage.cpu_time,
+            max_memory: usage.memory,
+        })
+    }
+
+    /// Update the highest observed memory usage
+    fn poll(&mut self) {
+        if let Ok(usage) = self.cgroup.usage() {
+            if usage.memory > self.max_memory {
+                self.max_memory = usage.memory;
+            }
+        }
+    }
+
+    /// Report the resource usage of the cgroup since the benchmark started,
+    /// or fail if the cgroup cannot be queried anymore
+    fn report(&self, log: &Logger) -> io::Result<()> {
+        let usage = self.cgroup.usage()?;
+        let peak_memory = usage.peak_memory.unwrap_or_else(|| {
+            if usage.memory > self.max_memory {
+                usage.memory
+            } else {
+                self.max_memory
+            }
+        });
+        let cpu_time = usage
+            .cpu_time
+            .checked_sub(self.initial_cpu_time)
+            .unwrap_or_default();
+        info!(log, "Measured the benchmark's resource usage via its cgroup";
+              "CPU time (s)" => cpu_time.as_secs_f64(),
+              "peak memory" => %format::display_information(peak_memory));
+        Ok(())
+    }
+}
+
+impl Benchmark {
+    /// Start a benchmark, given as a program name followed by its arguments
+    pub fn start(log: &Logger, command: &[String]) -> io::Result<Self> {
+        let (program, args) = command
+            .split_first()
+            .expect("The benchmark command should not be empty");
+        info!(log, "Starting the benchmark"; "command" => ?command);
+        let child = Command::new(program).args(args).spawn()?;
+        let start = Instant::now();
+        let cgroup = CgroupTracker::new(log, child.id());
+        Ok(Self {
+            child,
+            start,
+            cgroup,
+        })
+    }
+
+    /// PID of the benchmark process
+    pub fn pid(&self) -> u32 {
+        self.child.id()
+    }
+
+    /// Terminate the benchmark prematurely and wait for it to exit
+    pub fn kill(&mut self, log: &Logger) -> io::Result<()> {
+        info!(log, "Killing the benchmark"; "pid" => self.pid());
+        self.child.kill()?;
+        self.child.wait()?;
+        Ok(())
+    }
+
+    /// Check if the benchmark has exited, and if so report its wall-clock
+    /// execution time and resource usage
+    ///
+    /// Since the benchmark's exit is only noticed when this is called, the
+    /// reported wall-clock time is overestimated by up to the interval between
+    /// two calls (i.e. one sampling period).
+    ///
+    pub fn try_finish(&mut self, log: &Logger) -> io::Result<bool> {
+        let status = match self.child.try_wait()? {
+            Some(status) => status,
+            None => {
+                if let Some(cgroup) = &mut self.cgroup {
+                    cgroup.poll();
+                }
+                return Ok(false);
+            }
+        };
+        info!(log, "The benchmark has exited";
+              "status" => %status,
+              "wall-clock time (s)" => self.start.elapsed().as_secs_f64());
+        let cgroup_reported = match &self.cgroup {
+            Some(cgroup) => match cgroup.report(log) {
+                Ok(()) => true,
+                Err(err) => {
+                    debug!(log, "Failed to query the benchmark's cgroup, its resource \
+                                 usage will be measured via getrusage()";
+                           "error" => %err);
+                    false
+                }
+            },
+            None => false,
+        };
+        if !cgroup_reported {
+            process::child_usage_report(log);
+        }
+        Ok(true)
+    }
+}
+
+impl Drop for Benchmark {
+    /// Make sure that the benchmark does not outlive monitoring, e.g. when
+    /// benchmon stops early because of an error or an interruption
+    fn drop(&mut self) {
+        if let Ok(None) = self.child.try_wait() {
+            let _ = self.child.kill();
+            let _ = self.child.wait();
+        }
+    }
+}
@@ -1,3 +1,7 @@
+use crate::platform::{Host, Platform};
+
+use futures_util::stream::TryStreamExt;
+
 use heim::{
     sensors::TemperatureSensor,
     units::{thermodynamic_temperature::degree_celsius, ThermodynamicTemperature as Temperature},
@@ -5,7 +9,61 @@ use heim::{
 
 use slog::{debug, info, o, Logger};
 
-use std::collections::BTreeMap;
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fmt, io,
+};
+
+/// Temperature, rounded to a fixed resolution
+///
+/// Sensors report temperatures as floating-point numbers, whose conversions
+/// introduce noise (e.g. 48°C may become 47.99997°C). Rounding them to a fixed
+/// resolution ensures that a stable temperature is displayed as a stable
+/// value, and that comparisons between temperatures do not flap.
+///
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub struct RoundedTemperature {
+    /// Temperature in thousandths of degree Celsius
+    millicelsius: i32,
+
+    /// Resolution in thousandths of degree Celsius
+    resolution: u32,
+}
+
+impl RoundedTemperature {
+    /// Round a temperature to a certain resolution in degrees Celsius, which
+    /// is itself rounded to a multiple of 0.001°C (with a minimum of 0.001°C)
+    pub fn new(temperature: Temperature, resolution_celsius: f32) -> Self {
+        let resolution = ((resolution_celsius * 1000.0).round() as u32).max(1);
+        let steps = (temperature.get::<degree_celsius>() * 1000.0 / resolution as f32).round();
+        Self {
+            millicelsius: steps as i32 * resolution as i32,
+            resolution,
+        }
+    }
+
+    /// Number of decimal places that are needed to display this temperature
+    fn decimals(self) -> usize {
+        let mut decimals = 3;
+        let mut resolution = self.resolution;
+        while decimals > 0 && resolution % 10 == 0 {
+            decimals -= 1;
+            resolution /= 10;
+        }
+        decimals
+    }
+}
+
+impl fmt::Display for RoundedTemperature {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:.*}",
+            self.decimals(),
+            f64::from(self.millicelsius) / 1000.0
+        )
+    }
+}
 
 /// Properties of a given sensor, within a sensor unit
 struct SensorProperties {
@@ -14,8 +72,148 @@ struct SensorProperties {
     critical_trip_point: Option<Temperature>,
 }
 
-/// Report on the host's sensors
-pub fn startup_report(log: &Logger, temperatures: Vec<TemperatureSensor>) {
+/// Identifier of a sensor, in `unit` or `unit:label` format
+fn sensor_id(sensor: &TemperatureSensor) -> String {
+    match sensor.label() {
+        Some(label) => format!("{}:{}", sensor.unit(), label),
+        None => sensor.unit().to_owned(),
+    }
+}
+
+/// Current temperature of a sensor, along with its trip points
+pub struct SensorReading {
+    /// Identifier of the sensor, in `unit` or `unit:label` format
+    pub id: String,
+
+    /// Current temperature
+    pub current: Temperature,
+
+    /// "High" trip point, if any
+    pub high_trip_point: Option<Temperature>,
+
+    /// "Critical" trip point, if any
+    pub critical_trip_point: Option<Temperature>,
+}
+
+impl SensorReading {
+    /// Truth that the sensor's temperature is within `margin_celsius` degrees
+    /// Celsius of its critical trip point (false if that trip point is unknown)
+    pub fn is_near_critical(&self, margin_celsius: f32) -> bool {
+        self.critical_trip_point.map_or(false, |critical| {
+            self.current.get::<degree_celsius>()
+                >= critical.get::<degree_celsius>() - margin_celsius
+        })
+    }
+
+    /// Thermal headroom used, i.e. how far the sensor's temperature went into
+    /// the range between its high and critical trip points, from 0 (at or
+    /// below the high trip point) to 1 (at or above the critical trip point)
+    ///
+    /// Unlike absolute temperatures, this can be compared across sensors with
+    /// different operating ranges. It is unknown if either trip point is.
+    ///
+    pub fn headroom_used(&self) -> Option<f32> {
+        let high = self.high_trip_point?.get::<degree_celsius>();
+        let critical = self.critical_trip_point?.get::<degree_celsius>();
+        if critical <= high {
+            return None;
+        }
+        let used = (self.current.get::<degree_celsius>() - high) / (critical - high);
+        Some(used.max(0.0).min(1.0))
+    }
+}
+
+/// Highest thermal headroom used across a set of sensors (see
+/// `SensorReading::headroom_used()`), if known for at least one of them
+pub fn max_headroom_used(readings: &[SensorReading]) -> Option<f32> {
+    readings
+        .iter()
+        .filter_map(SensorReading::headroom_used)
+        .fold(None, |max, used| {
+            Some(max.map_or(used, |max: f32| max.max(used)))
+        })
+}
+
+/// Sampler of the current temperature of the host's sensors
+///
+/// Trip points are captured when monitoring starts, since some drivers
+/// recompute them dynamically, which would make temperatures that are compared
+/// with them harder to interpret.
+///
+pub struct Monitor {
+    /// High and critical trip points of each sensor, by sensor identifier
+    trip_points: HashMap<String, (Option<Temperature>, Option<Temperature>)>,
+}
+
+impl Monitor {
+    /// Set up temperature monitoring
+    pub async fn new() -> heim::Result<Self> {
+        let trip_points = heim::sensors::temperatures()
+            .map_ok(|sensor| (sensor_id(&sensor), (sensor.high(), sensor.critical())))
+            .try_collect::<HashMap<_, _>>()
+            .await?;
+        Ok(Self { trip_points })
+    }
+
+    /// Truth that at least one sensor has a critical trip point
+    pub fn has_critical_trip_points(&self) -> bool {
+        self.trip_points
+            .values()
+            .any(|(_high, critical)| critical.is_some())
+    }
+
+    /// Measure the current temperature of every sensor
+    pub async fn readings(&self) -> heim::Result<Vec<SensorReading>> {
+        heim::sensors::temperatures()
+            .map_ok(|sensor| {
+                let id = sensor_id(&sensor);
+                let (high_trip_point, critical_trip_point) = self
+                    .trip_points
+                    .get(&id)
+                    .copied()
+                    .unwrap_or((sensor.high(), sensor.critical()));
+                SensorReading {
+                    id,
+                    current: sensor.current(),
+                    high_trip_point,
+                    critical_trip_point,
+                }
+            })
+            .try_collect()
+            .await
+    }
+}
+
+/// Temperature sensor from the Linux thermal zone sysfs interface
+///
+/// heim only looks up thermal zones when no hwmon sensor is available, but on
+/// some embedded boards (e.g. ARM SoCs), thermal zones provide sensors that
+/// hwmon does not know about.
+///
+pub struct ThermalZone {
+    /// Type of thermal zone (e.g. "cpu-thermal"), used as a sensor unit name
+    pub unit: String,
+
+    /// "Hot" trip point, if any
+    pub high_trip_point: Option<Temperature>,
+
+    /// "Critical" trip point, if any
+    pub critical_trip_point: Option<Temperature>,
+}
+
+/// Enumerate the host's thermal zones (this list is empty on non-Linux OSes)
+pub fn thermal_zones() -> io::Result<Vec<ThermalZone>> {
+    Host::thermal_zones()
+}
+
+/// Report on the host's sensors, with temperatures rounded to a certain
+/// resolution in degrees Celsius (see `RoundedTemperature`)
+pub fn startup_report(
+    log: &Logger,
+    temperatures: Vec<TemperatureSensor>,
+    thermal_zones: Vec<ThermalZone>,
+    resolution_celsius: f32,
+) {
     // Group sensors by sensor unit
     debug!(log, "Processing temperature sensor list...");
     let mut unit_to_sensors = BTreeMap::<String, Vec<_>>::new();
@@ -28,12 +226,31 @@ pub fn startup_report(log: &Logger, temperatures: Vec<TemperatureSensor>) {
         });
     }
 
+    // Add thermal zones that heim did not report, if any
+    for zone in thermal_zones {
+        if unit_to_sensors.contains_key(&zone.unit) {
+            debug!(log, "Thermal zone was already reported by heim";
+                   "sensor unit" => zone.unit);
+            continue;
+        }
+        unit_to_sensors.insert(
+            zone.unit,
+            vec![SensorProperties {
+                label: None,
+                high_trip_point: zone.high_trip_point,
+                critical_trip_point: zone.critical_trip_point,
+            }],
+        );
+    }
+
     // Report on sensor units and their inner sensors
     for (unit, mut sensor_list) in unit_to_sensors {
         let unit_log = log.new(o!("sensor unit" => unit));
         sensor_list.sort_by_cached_key(|sensor| sensor.label.clone());
         for sensor in sensor_list {
-            let to_celsius = |t_opt: Option<Temperature>| t_opt.map(|t| t.get::<degree_celsius>());
+            let to_celsius = |t_opt: Option<Temperature>| {
+                t_opt.map(|t| RoundedTemperature::new(t, resolution_celsius).to_string())
+            };
             info!(unit_log, "Found a temperature sensor";
                   "label" => sensor.label,
                   "high trip point (°C)" => to_celsius(sensor.high_trip_point),
@@ -41,3 +258,88 @@ pub fn startup_report(log: &Logger, temperatures: Vec<TemperatureSensor>) {
         }
     }
 }
+
+/// List the host's sensors, as `unit` or `unit:label` identifiers
+pub fn list(temperatures: Vec<TemperatureSensor>, thermal_zones: Vec<ThermalZone>) {
+    let heim_units = temperatures
+        .iter()
+        .map(|sensor| sensor.unit().to_owned())
+        .collect::<BTreeSet<_>>();
+    let mut identifiers = temperatures.iter().map(sensor_id).collect::<BTreeSet<_>>();
+    identifiers.extend(
+        thermal_zones
+            .into_iter()
+            .map(|zone| zone.unit)
+            .filter(|unit| !heim_units.contains(unit)),
+    );
+    for identifier in identifiers {
+        println!("{}", identifier);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn steady_temperature() {
+        // A steady temperature, with some float noise, is displayed as a
+        // steady value and compares steadily against thresholds
+        let readings = [48.0, 47.99997, 48.00002, 47.96, 48.04];
+        for &(resolution, display) in &[(0.1, "48.0"), (0.5, "48.0"), (1.0, "48")] {
+            let threshold =
+                RoundedTemperature::new(Temperature::new::<degree_celsius>(48.0), resolution);
+            for &reading in &readings {
+                let rounded = RoundedTemperature::new(
+                    Temperature::new::<degree_celsius>(reading),
+                    resolution,
+                );
+                assert_eq!(rounded.to_string(), display);
+                assert_eq!(rounded, threshold);
+            }
+        }
+    }
+
+    #[test]
+    fn thermal_headroom() {
+        let celsius = Temperature::new::<degree_celsius>;
+        let reading = |current: f32, high: Option<f32>, critical: Option<f32>| SensorReading {
+            id: "coretemp:Core 0".to_owned(),
+            current: celsius(current),
+            high_trip_point: high.map(celsius),
+            critical_trip_point: critical.map(celsius),
+        };
+
+        // Headroom is measured from the high to the critical trip point
+        assert_eq!(
+            reading(50.0, Some(80.0), Some(100.0)).headroom_used(),
+            Some(0.0)
+        );
+        assert_eq!(
+            reading(90.0, Some(80.0), Some(100.0)).headroom_used(),
+            Some(0.5)
+        );
+        assert_eq!(
+            reading(105.0, Some(80.0), Some(100.0)).headroom_used(),
+            Some(1.0)
+        );
+
+        // It is unknown without a valid pair of trip points
+        assert_eq!(reading(90.0, None, Some(100.0)).headroom_used(), None);
+        assert_eq!(reading(90.0, Some(80.0), None).headroom_used(), None);
+        assert_eq!(
+            reading(90.0, Some(100.0), Some(100.0)).headroom_used(),
+            None
+        );
+
+        // Sensors with unknown headroom are ignored when looking for the
+        // hottest one, unless no sensor has a known headroom
+        let readings = vec![
+            reading(95.0, None, None),
+            reading(85.0, Some(80.0), Some(100.0)),
+            reading(70.0, Some(60.0), Some(80.0)),
+        ];
+        assert_eq!(max_headroom_used(&readings), Some(0.5));
+        assert_eq!(max_headroom_used(&readings[..1]), None);
+    }
+}
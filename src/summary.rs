@@ -0,0 +1,531 @@
+//! End-of-run summary of the numeric measurements
+//!
+//! Every numeric measurement of every sample is fed into a running
+//! accumulator, which tracks its minimum, mean and maximum along with a coarse
+//! histogram of its distribution. This takes a constant amount of memory no
+//! matter how long the run is, and reveals multimodal behavior (e.g. a CPU
+//! which alternates between boosting and throttling) that the mean hides.
+//!
+//! The summary also draws a timeline of each measurement over the run, which
+//! does require retaining measurements in memory. Their number is capped, and
+//! once the cap is reached, the retained measurements are decimated according
+//! to a configurable `Decimation` strategy. The minimum, mean and maximum keep
+//! being computed from every sample, so decimation does not affect them.
+
+use crate::{
+    format::{self, TableStyle},
+    output::{Sample, SampleRecord},
+};
+
+use slog::{info, Logger};
+
+use std::{str::FromStr, time::SystemTime};
+
+/// Number of bins of the internal histograms
+///
+/// This is a multiple of the number of displayed bins, so that the displayed
+/// histogram remains accurate after its bins are regrouped.
+///
+const FINE_BINS: usize = 64;
+
+/// Number of bins of the displayed histograms
+const DISPLAY_BINS: usize = 16;
+
+/// Number of time slices of the displayed timelines
+const TIMELINE_WIDTH: usize = 32;
+
+/// Histogram whose bins are widened as the range of values grows, so that it
+/// covers every value seen so far with a fixed number of bins
+#[derive(Clone, Debug)]
+struct Histogram {
+    /// Lower bound of the first bin
+    origin: f64,
+
+    /// Width of each bin, or 0.0 as long as a single distinct value was seen
+    bin_width: f64,
+
+    /// Number of values which fell into each bin
+    counts: [u64; FINE_BINS],
+}
+
+impl Histogram {
+    /// Start a histogram with a first value
+    fn new(value: f64) -> Self {
+        let mut counts = [0; FINE_BINS];
+        counts[0] = 1;
+        Self {
+            origin: value,
+            bin_width: 0.0,
+            counts,
+        }
+    }
+
+    /// Account for a new value
+    fn record(&mut self, value: f64) {
+        // Until two distinct values are seen, there is no range to bin over
+        if self.bin_width == 0.0 {
+            if value == self.origin {
+                self.counts[0] += 1;
+                return;
+            }
+            let last_bin = (FINE_BINS - 1) as f64;
+            if value > self.origin {
+                self.bin_width = (value - self.origin) / last_bin;
+            } else {
+                self.bin_width = (self.origin - value) / last_bin;
+                self.origin = value;
+                self.counts.swap(0, FINE_BINS - 1);
+            }
+        }
+
+        // Widen the bins until the value falls into one of them
+        loop {
+            let index = ((value - self.origin) / self.bin_width).floor();
+            if index < 0.0 {
+                self.grow(false);
+            } else if index >= FINE_BINS as f64 {
+                self.grow(true);
+            } else {
+                self.counts[index as usize] += 1;
+                return;
+            }
+        }
+    }
+
+    /// Double the width of the bins by merging pairs of neighboring bins,
+    /// extending the histogram's range upwards or downwards
+    fn grow(&mut self, upwards: bool) {
+        let mut merged = [0; FINE_BINS];
+        let offset = if upwards { 0 } else { FINE_BINS / 2 };
+        for (index, pair) in self.counts.chunks(2).enumerate() {
+            merged[offset + index] = pair.iter().sum();
+        }
+        if !upwards {
+            self.origin -= FINE_BINS as f64 * self.bin_width;
+        }
+        self.bin_width *= 2.0;
+        self.counts = merged;
+    }
+
+    /// Regroup the bins into `DISPLAY_BINS` bins spanning the range from `min`
+    /// to `max`, which must be the extremal values that were recorded
+    fn display_counts(&self, min: f64, max: f64) -> [u64; DISPLAY_BINS] {
+        let mut display_counts = [0; DISPLAY_BINS];
+        if max <= min {
+            display_counts[0] = self.counts.iter().sum();
+            return display_counts;
+        }
+        for (index, &count) in self.counts.iter().enumerate() {
+            let center = self.origin + (index as f64 + 0.5) * self.bin_width;
+            let position = ((center - min) / (max - min)).max(0.0).min(1.0);
+            let display_index = ((position * DISPLAY_BINS as f64) as usize).min(DISPLAY_BINS - 1);
+            display_counts[display_index] += count;
+        }
+        display_counts
+    }
+}
+
+/// Strategy for thinning out the measurements that are retained for drawing
+/// timelines, once there are too many of them
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Decimation {
+    /// Keep every k-th sample, doubling k whenever the cap is reached
+    EveryKth,
+
+    /// Keep a uniformly random subset of the samples (reservoir sampling)
+    Reservoir,
+
+    /// Merge pairs of neighboring samples into their minimum and maximum,
+    /// which preserves short spikes that the other strategies may drop
+    MinMax,
+}
+
+impl FromStr for Decimation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "every-kth" => Ok(Decimation::EveryKth),
+            "reservoir" => Ok(Decimation::Reservoir),
+            "min-max" => Ok(Decimation::MinMax),
+            _ => Err(format!("Unknown decimation strategy \"{}\"", s)),
+        }
+    }
+}
+
+/// Parse a maximal number of samples in memory, which must allow at least two
+/// samples to be retained so that decimation makes progress
+pub fn parse_max_samples(s: &str) -> Result<usize, String> {
+    let max_samples = s.parse::<usize>().map_err(|err| err.to_string())?;
+    if max_samples < 2 {
+        return Err("At least 2 samples must be kept in memory".to_owned());
+    }
+    Ok(max_samples)
+}
+
+/// Minimal xorshift pseudo-random number generator, which is good enough for
+/// reservoir sampling
+struct XorShift(u64);
+
+impl XorShift {
+    /// Seed the generator from the system clock
+    fn new() -> Self {
+        let nanos = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_nanos() as u64)
+            .unwrap_or_default();
+        // The state of a xorshift generator must not be zero
+        Self(nanos | 1)
+    }
+
+    /// Generate a number between 0 and `bound` (excluded)
+    fn below(&mut self, bound: u64) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0 % bound
+    }
+}
+
+/// Evolution of a measurement over the run, retained in bounded memory
+#[derive(Clone, Debug, Default)]
+struct Timeline {
+    /// Retained measurements as (sample index, lowest value, highest value),
+    /// sorted by sample index
+    points: Vec<(u64, f64, f64)>,
+
+    /// Sampling stride of every-kth decimation
+    stride: u64,
+
+    /// Number of values that were offered to the timeline
+    seen: u64,
+}
+
+impl Timeline {
+    /// Account for the value of a measurement in the sample with a certain
+    /// index, retaining at most `max_points` values
+    fn record(
+        &mut self,
+        index: u64,
+        value: f64,
+        max_points: usize,
+        decimation: Decimation,
+        rng: &mut XorShift,
+    ) {
+        self.seen += 1;
+        match decimation {
+            Decimation::EveryKth => {
+                self.stride = self.stride.max(1);
+                if index % self.stride != 0 {
+                    return;
+                }
+                self.points.push((index, value, value));
+                while self.points.len() > max_points {
+                    self.stride *= 2;
+                    let stride = self.stride;
+                    self.points.retain(|&(index, _, _)| index % stride == 0);
+                }
+            }
+            Decimation::Reservoir => {
+                if self.points.len() < max_points {
+                    self.points.push((index, value, value));
+                } else {
+                    let victim = rng.below(self.seen) as usize;
+                    if victim < max_points {
+                        // The new point comes last, so this keeps points sorted
+                        self.points.remove(victim);
+                        self.points.push((index, value, value));
+                    }
+                }
+            }
+            Decimation::MinMax => {
+                self.points.push((index, value, value));
+                if self.points.len() > max_points {
+                    self.points = self
+                        .points
+                        .chunks(2)
+                        .map(|pair| {
+                            pair.iter().fold(pair[0], |(index, low, high), point| {
+                                (index, low.min(point.1), high.max(point.2))
+                            })
+                        })
+                        .collect();
+                }
+            }
+        }
+    }
+
+    /// Highest retained value in each of `TIMELINE_WIDTH` time slices,
+    /// normalized between `min` (0.0) and `max` (1.0), or None for time slices
+    /// which have no retained value
+    fn peaks(&self, min: f64, max: f64) -> [Option<f64>; TIMELINE_WIDTH] {
+        let mut peaks = [None; TIMELINE_WIDTH];
+        let (first, last) = match (self.points.first(), self.points.last()) {
+            (Some(first), Some(last)) => (first.0, last.0),
+            _ => return peaks,
+        };
+        let duration = (last - first + 1) as f64;
+        for &(index, _low, high) in &self.points {
+            let slice = ((index - first) as f64 / duration * TIMELINE_WIDTH as f64) as usize;
+            let height = if max > min {
+                (high - min) / (max - min)
+            } else {
+                1.0
+            };
+            let peak = &mut peaks[slice.min(TIMELINE_WIDTH - 1)];
+            *peak = Some(peak.map_or(height, |peak: f64| peak.max(height)));
+        }
+        peaks
+    }
+}
+
+/// Running statistics of a numeric measurement
+#[derive(Clone, Debug)]
+pub struct ColumnStats {
+    /// Number of values that were recorded
+    count: u64,
+
+    /// Sum of the recorded values
+    sum: f64,
+
+    /// Smallest recorded value
+    min: f64,
+
+    /// Largest recorded value
+    max: f64,
+
+    /// Distribution of the recorded values
+    histogram: Histogram,
+
+    /// Evolution of the recorded values over time
+    timeline: Timeline,
+}
+
+impl ColumnStats {
+    /// Start accumulating statistics with a first value
+    fn new(value: f64) -> Self {
+        Self {
+            count: 1,
+            sum: value,
+            min: value,
+            max: value,
+            histogram: Histogram::new(value),
+            timeline: Timeline::default(),
+        }
+    }
+
+    /// Account for a new value
+    fn record(&mut self, value: f64) {
+        self.count += 1;
+        self.sum += value;
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+        self.histogram.record(value);
+    }
+
+    /// Mean of the recorded values
+    pub fn mean(&self) -> f64 {
+        self.sum / self.count as f64
+    }
+}
+
+/// Accumulator of the statistics of every numeric measurement
+pub struct Summary {
+    /// Statistics of each field of `SampleRecord`, if it was ever measured
+    columns: Vec<Option<ColumnStats>>,
+
+    /// Number of samples that were recorded
+    samples: u64,
+
+    /// Maximal number of values that each timeline retains
+    max_samples: usize,
+
+    /// Decimation strategy of the timelines
+    decimation: Decimation,
+
+    /// Randomness source for reservoir sampling
+    rng: XorShift,
+}
+
+impl Summary {
+    /// Fields which are not measurements of the run, and are thus not
+    /// summarized (raw counters are cumulative since boot)
+    const IGNORED_FIELDS: &'static [&'static str] = &[
+        "elapsed_ns",
+        "gap_s",
+        "raw_user_s",
+        "raw_system_s",
+        "raw_idle_s",
+        "raw_nice_s",
+        "raw_iowait_s",
+        "raw_steal_s",
+        "raw_ctx_switches",
+        "raw_interrupts",
+        "raw_net_recv_bytes",
+        "raw_net_sent_bytes",
+    ];
+
+    /// Prepare to summarize a run, retaining at most `max_samples` samples in
+    /// memory for the timelines (which must be at least 2)
+    pub fn new(max_samples: usize, decimation: Decimation) -> Self {
+        assert!(max_samples >= 2, "At least 2 samples must be retained");
+        Self {
+            columns: vec![None; SampleRecord::FIELDS.len()],
+            samples: 0,
+            max_samples,
+            decimation,
+            rng: XorShift::new(),
+        }
+    }
+
+    /// Account for the measurements of a new sample
+    pub fn record(&mut self, sample: &Sample) {
+        let record = serde_json::to_value(SampleRecord::new(sample))
+            .expect("Sample records should always be serializable");
+        let index = self.samples;
+        self.samples += 1;
+        for (field, stats) in SampleRecord::FIELDS.iter().zip(&mut self.columns) {
+            if Self::IGNORED_FIELDS.contains(field) {
+                continue;
+            }
+            let value = match record[*field].as_f64() {
+                Some(value) if value.is_finite() => value,
+                _ => continue,
+            };
+            let stats = match stats {
+                Some(stats) => {
+                    stats.record(value);
+                    stats
+                }
+                None => stats.get_or_insert(ColumnStats::new(value)),
+            };
+            stats.timeline.record(
+                index,
+                value,
+                self.max_samples,
+                self.decimation,
+                &mut self.rng,
+            );
+        }
+    }
+
+    /// Report the minimum, mean and maximum of every measurement, along with a
+    /// histogram of its values between the minimum and the maximum, and a
+    /// timeline of its peak value over the run
+    pub fn report(&self, log: &Logger, style: TableStyle) {
+        info!(log, "Summary of the measurements over the run";
+              "samples" => self.samples);
+        for (field, stats) in SampleRecord::FIELDS.iter().zip(&self.columns) {
+            let stats = match stats {
+                Some(stats) => stats,
+                None => continue,
+            };
+            let display_counts = stats.histogram.display_counts(stats.min, stats.max);
+            let peaks = stats.timeline.peaks(stats.min, stats.max);
+            info!(log, "[{}]", format::display_histogram(&display_counts, style);
+                  "measurement" => field,
+                  "min" => stats.min,
+                  "mean" => stats.mean(),
+                  "max" => stats.max,
+                  "timeline" => format!("[{}]", format::display_levels(&peaks, style)));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn histogram_growth() {
+        // Values spread over a range that grows in both directions
+        let values = (0..1000).map(|i| {
+            if i % 2 == 0 {
+                i as f64
+            } else {
+                -(i as f64) / 3.0
+            }
+        });
+        let mut stats: Option<ColumnStats> = None;
+        for value in values {
+            match &mut stats {
+                Some(stats) => stats.record(value),
+                None => stats = Some(ColumnStats::new(value)),
+            }
+        }
+        let stats = stats.unwrap();
+        assert_eq!(stats.histogram.counts.iter().sum::<u64>(), 1000);
+        let histogram = &stats.histogram;
+        assert!(histogram.origin <= stats.min);
+        assert!(histogram.origin + FINE_BINS as f64 * histogram.bin_width > stats.max);
+        let display_counts = histogram.display_counts(stats.min, stats.max);
+        assert_eq!(display_counts.iter().sum::<u64>(), 1000);
+    }
+
+    #[test]
+    fn bimodal_distribution() {
+        // A CPU which alternates between boosting and throttling
+        let mut stats = ColumnStats::new(3000.0);
+        for i in 0..999 {
+            stats.record(if i % 2 == 0 { 1200.0 } else { 3000.0 });
+        }
+        let display_counts = stats.histogram.display_counts(stats.min, stats.max);
+        assert_eq!(display_counts[0], 500);
+        assert_eq!(display_counts[DISPLAY_BINS - 1], 500);
+        assert_eq!(display_counts.iter().sum::<u64>(), 1000);
+        assert_eq!(stats.mean(), 2100.0);
+    }
+
+    #[test]
+    fn constant_values() {
+        let mut stats = ColumnStats::new(42.0);
+        for _ in 0..9 {
+            stats.record(42.0);
+        }
+        assert_eq!(stats.histogram.display_counts(stats.min, stats.max)[0], 10);
+    }
+
+    /// Feed the same values to timelines with every decimation strategy
+    fn decimated_timelines(values: &[f64], max_points: usize) -> Vec<Timeline> {
+        let mut rng = XorShift::new();
+        let strategies = [
+            Decimation::EveryKth,
+            Decimation::Reservoir,
+            Decimation::MinMax,
+        ];
+        strategies
+            .iter()
+            .map(|&decimation| {
+                let mut timeline = Timeline::default();
+                for (index, &value) in values.iter().enumerate() {
+                    timeline.record(index as u64, value, max_points, decimation, &mut rng);
+                }
+                timeline
+            })
+            .collect()
+    }
+
+    #[test]
+    fn bounded_timelines() {
+        let values = (0..10_000).map(f64::from).collect::<Vec<_>>();
+        for timeline in decimated_timelines(&values, 100) {
+            assert!(timeline.points.len() <= 100);
+            assert!(timeline.points.len() >= 50);
+            assert!(timeline.points.windows(2).all(|pair| pair[0].0 < pair[1].0));
+        }
+    }
+
+    #[test]
+    fn min_max_keeps_spikes() {
+        let mut values = vec![0.0; 10_000];
+        values[4321] = 1.0;
+        let timelines = decimated_timelines(&values, 10);
+        let min_max = &timelines[2];
+        assert!(min_max.points.iter().any(|&(_, _, high)| high == 1.0));
+        let peaks = min_max.peaks(0.0, 1.0);
+        assert_eq!(
+            peaks.iter().flatten().filter(|&&peak| peak == 1.0).count(),
+            1
+        );
+    }
+}
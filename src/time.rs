@@ -1,11 +1,17 @@
 use chrono::{
     format::{Item, StrftimeItems},
-    DateTime, Datelike, TimeZone,
+    DateTime, Datelike, FixedOffset, Local, NaiveTime, Offset, TimeZone,
 };
 
 use crate::format;
 
-use std::fmt::Display;
+use slog::{info, Logger};
+
+use std::{
+    fmt::Display,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 /// Maximum year that we allow ourselves to support in date formatting
 ///
@@ -20,18 +26,31 @@ use std::fmt::Display;
 ///
 const MAX_SUPPORTED_YEAR: i32 = 9999;
 
-/// Strftime-style time column formatting
-pub struct Formatter {
+/// Minimum year that we allow ourselves to support in date formatting
+///
+/// Years before 1 CE can be printed with a minus sign (in the case of ISO 8601
+/// week-based years, that's even true of some days of year 0), which would
+/// needlessly widen our table's date column for the sake of time travelers.
+///
+const MIN_SUPPORTED_YEAR: i32 = 1;
+
+/// Parsed and validated strftime-style date/time format
+///
+/// This can be shared by multiple date/time renderers (e.g. the stdout table
+/// and file outputs) without parsing and validating the format string again.
+///
+#[derive(Clone)]
+pub struct ParsedFormat {
     /// Decoded version of the format string
-    owned_items: Box<[Item<'static>]>,
+    owned_items: Arc<[Item<'static>]>,
 
     /// Cached max output width expected from the format string
     max_output_width: usize,
 }
 
-impl Formatter {
-    /// Construct a time formatter from a format string following `chrono`'s
-    /// flavor of strftime date/time format syntax.
+impl ParsedFormat {
+    /// Parse a format string following `chrono`'s flavor of strftime date/time
+    /// format syntax.
     ///
     /// The input format string must only contain elements which have a maximum
     /// width that can be computed at compile time. This noticeably excludes
@@ -58,16 +77,12 @@ impl Formatter {
                     Item::Error => Item::Error,
                 }
             })
-            .collect::<Box<[_]>>();
+            .collect::<Arc<[_]>>();
 
         // Compute the maximal width of formatted time produced using this
         // format string (in grapheme clusters), panic if there is no maximum or
         // the format string did not parse.
-        let max_output_width = owned_items
-            .iter()
-            .map(max_item_width)
-            .sum::<usize>()
-            .max(format::str_width(Self::TITLE));
+        let max_output_width = owned_items.iter().map(max_item_width).sum::<usize>();
 
         // Return the result
         Self {
@@ -76,12 +91,57 @@ impl Formatter {
         }
     }
 
+    /// Format a time point, without any padding
+    pub fn format<Tz>(&self, date_time: DateTime<Tz>) -> impl Display + '_
+    where
+        Tz: TimeZone,
+        Tz::Offset: Display,
+    {
+        assert!((MIN_SUPPORTED_YEAR..=MAX_SUPPORTED_YEAR).contains(&date_time.year()));
+        date_time.format_with_items(self.owned_items.iter())
+    }
+
+    /// Upper bound on the width of formatted time points in grapheme clusters
+    pub fn max_output_width(&self) -> usize {
+        self.max_output_width
+    }
+}
+
+/// Strftime-style time column formatting
+pub struct Formatter {
+    /// Parsed version of the format string
+    parsed_format: ParsedFormat,
+
+    /// Width of the output column
+    output_width: usize,
+}
+
+impl Formatter {
+    /// Construct a time formatter from a format string following `chrono`'s
+    /// flavor of strftime date/time format syntax (see `ParsedFormat::new()`),
+    /// and an optional maximal column width (see `format::cap_col_width()`).
+    pub fn new(s: &str, max_width: Option<usize>) -> Self {
+        Self::from_parsed(ParsedFormat::new(s), max_width)
+    }
+
+    /// Construct a time formatter from a previously parsed format string
+    pub fn from_parsed(parsed_format: ParsedFormat, max_width: Option<usize>) -> Self {
+        let natural_width = parsed_format
+            .max_output_width()
+            .max(format::str_width(Self::TITLE));
+        let output_width = format::cap_col_width(natural_width, max_width);
+        Self {
+            parsed_format,
+            output_width,
+        }
+    }
+
     /// Title of the column in tabular output
     const TITLE: &'static str = "time";
 
-    /// Display the title of a column of results
-    pub fn display_title(&self) -> impl Display {
-        format::display_col_header(Self::TITLE, self.max_output_width)
+    /// Display the title of a column of results, surrounded by a fill character
+    pub fn display_title(&self, fill: char) -> impl Display {
+        format::display_col_header(Self::TITLE, self.output_width, fill)
     }
 
     /// Display a time point within a column of results
@@ -90,17 +150,221 @@ impl Formatter {
         Tz: TimeZone,
         Tz::Offset: Display,
     {
-        assert!(date_time.year() <= MAX_SUPPORTED_YEAR);
-        format::display_col_data(
-            date_time.format_with_items(self.owned_items.iter()),
-            self.max_output_width,
-        )
+        format::display_col_data(self.parsed_format.format(date_time), self.output_width)
     }
 
     /// Indicate the width of the output column in grapheme clusters
-    #[allow(unused)]
+    #[cfg(test)]
     pub fn output_width(&self) -> usize {
-        self.max_output_width
+        self.output_width
+    }
+}
+
+/// Timezone in which time points are displayed
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Timezone {
+    /// Local timezone of the host
+    Local,
+
+    /// Coordinated Universal Time
+    Utc,
+}
+
+impl Timezone {
+    /// Convert a local time point into this timezone
+    pub fn convert(self, local_time: DateTime<Local>) -> DateTime<FixedOffset> {
+        let offset = match self {
+            Timezone::Local => local_time.offset().fix(),
+            Timezone::Utc => FixedOffset::east(0),
+        };
+        local_time.with_timezone(&offset)
+    }
+}
+
+impl Default for Timezone {
+    fn default() -> Self {
+        Timezone::Local
+    }
+}
+
+/// Parse a time of day in HH:MM format
+pub fn parse_time_of_day(s: &str) -> chrono::ParseResult<NaiveTime> {
+    NaiveTime::parse_from_str(s, "%H:%M")
+}
+
+/// Parse a duration, in (possibly fractional) seconds
+pub fn parse_secs(s: &str) -> Result<Duration, String> {
+    let secs = s
+        .parse::<f64>()
+        .map_err(|_| format!("Invalid number of seconds \"{}\"", s))?;
+    // Duration::from_secs_f64() panics on negative, non-finite or huge inputs
+    if !(secs.is_finite() && secs >= 0.0 && secs < u64::MAX as f64) {
+        return Err(format!(
+            "\"{}\" is not a valid duration, expected a non-negative number of seconds",
+            s
+        ));
+    }
+    Ok(Duration::from_secs_f64(secs))
+}
+
+/// Parse a sampling period, in (possibly fractional) seconds
+pub fn parse_period(s: &str) -> Result<Duration, String> {
+    let period = parse_secs(s)?;
+    // Periods which round down to zero would make benchmon busy-loop
+    if period == Duration::from_secs(0) {
+        return Err("The sampling period must be a positive number of seconds".to_owned());
+    }
+    Ok(period)
+}
+
+/// Parse a timeout, in (possibly fractional) seconds
+pub fn parse_timeout(s: &str) -> Result<Duration, String> {
+    let timeout = parse_secs(s)?;
+    // A zero timeout would make every timed operation fail
+    if timeout == Duration::from_secs(0) {
+        return Err("Timeouts must be a positive number of seconds".to_owned());
+    }
+    Ok(timeout)
+}
+
+/// Shortest accepted heartbeat interval
+const MIN_HEARTBEAT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Parse a heartbeat interval, in (possibly fractional) seconds
+pub fn parse_heartbeat(s: &str) -> Result<Duration, String> {
+    let interval = parse_secs(s)?;
+    // Heartbeats are meant for humans watching long runs, and would flood the
+    // log if they were emitted at a high rate
+    if interval < MIN_HEARTBEAT_INTERVAL {
+        return Err(format!(
+            "The heartbeat interval must be at least {} second(s)",
+            MIN_HEARTBEAT_INTERVAL.as_secs()
+        ));
+    }
+    Ok(interval)
+}
+
+/// Truth that a time of day falls within a daily window, which starts at
+/// `start` (inclusive) and ends at `stop` (exclusive), possibly on the next day
+pub fn is_within_window(time: NaiveTime, start: NaiveTime, stop: NaiveTime) -> bool {
+    if start <= stop {
+        start <= time && time < stop
+    } else {
+        time >= start || time < stop
+    }
+}
+
+/// Find the next local date and time, strictly after `now`, at which the local
+/// time of day will be `time_of_day`
+///
+/// Days where this time of day does not exist (because of a daylight saving
+/// time transition) are skipped.
+///
+pub fn next_occurrence(now: DateTime<Local>, time_of_day: NaiveTime) -> DateTime<Local> {
+    let mut date = now.date().naive_local();
+    loop {
+        let candidate = Local.from_local_datetime(&date.and_time(time_of_day));
+        if let Some(candidate) = candidate.earliest() {
+            if candidate > now {
+                return candidate;
+            }
+        }
+        date = date.succ();
+    }
+}
+
+/// Detector of wall-clock adjustments (NTP steps, manual changes...)
+///
+/// Monitoring timestamps are based on the wall clock, which is what users want
+/// to see, but the wall clock can jump forward or backward during a run. This
+/// detector compares wall-clock time increments with those of the monotonic
+/// clock in order to detect such jumps.
+///
+pub struct ClockJumpDetector {
+    /// Previous wall-clock and monotonic timestamps, if any
+    last_timestamps: Option<(DateTime<Local>, Instant)>,
+
+    /// Discrepancy between the two clocks above which a jump is reported
+    threshold: Duration,
+
+    /// Truth that a clock jump was already reported
+    reported: bool,
+}
+
+impl ClockJumpDetector {
+    /// Set up a clock jump detector with a certain discrepancy threshold
+    pub fn new(threshold: Duration) -> Self {
+        Self {
+            last_timestamps: None,
+            threshold,
+            reported: false,
+        }
+    }
+
+    /// Record a new pair of wall-clock and monotonic timestamps, taken at the
+    /// same time, and return the discrepancy between the two clocks since the
+    /// previous pair if it exceeds the threshold.
+    ///
+    /// Only the first clock jump is reported, to avoid flooding the logs when
+    /// the wall clock is continuously readjusted.
+    ///
+    pub fn check(&mut self, wall_time: DateTime<Local>, instant: Instant) -> Option<Duration> {
+        let last_timestamps = self.last_timestamps.replace((wall_time, instant));
+        let (last_wall_time, last_instant) = last_timestamps?;
+        if self.reported {
+            return None;
+        }
+
+        let wall_elapsed = wall_time - last_wall_time;
+        let monotonic_elapsed = chrono::Duration::from_std(instant - last_instant)
+            .expect("Monotonic time increment is out of range");
+        let discrepancy = (wall_elapsed - monotonic_elapsed)
+            .num_microseconds()
+            .map(|micros| Duration::from_micros(micros.abs() as u64))
+            .unwrap_or(Duration::from_secs(u64::MAX));
+        if discrepancy > self.threshold {
+            self.reported = true;
+            Some(discrepancy)
+        } else {
+            None
+        }
+    }
+}
+
+/// Report on the resolution of the host's clocks
+///
+/// CPU timings are measured in scheduler ticks, and elapsed times are measured
+/// using the monotonic clock, so this tells how precise these measurements are.
+///
+// TODO: Query clock resolution on other OSes
+#[cfg_attr(not(unix), allow(unused_variables))]
+pub fn startup_report(log: &Logger) {
+    #[cfg(unix)]
+    {
+        // This is safe because sysconf has no side effect
+        let clock_ticks = unsafe { libc::sysconf(libc::_SC_CLK_TCK) };
+        let clock_ticks = if clock_ticks > 0 {
+            Some(clock_ticks)
+        } else {
+            None
+        };
+
+        let mut resolution = libc::timespec {
+            tv_sec: 0,
+            tv_nsec: 0,
+        };
+        // This is safe because clock_getres only writes to the timespec that
+        // we are passing it a valid pointer to.
+        let getres_result = unsafe { libc::clock_getres(libc::CLOCK_MONOTONIC, &mut resolution) };
+        let resolution_ns = if getres_result == 0 {
+            Some(resolution.tv_sec as i64 * 1_000_000_000 + resolution.tv_nsec as i64)
+        } else {
+            None
+        };
+
+        info!(log, "Received clock resolution information";
+              "CPU time ticks per second" => clock_ticks,
+              "monotonic clock resolution (ns)" => resolution_ns);
     }
 }
 
@@ -129,14 +393,18 @@ fn max_item_width(item: &Item) -> usize {
         Item::Space(s) => space_width(s),
         Item::OwnedSpace(os) => space_width(&os),
 
+        // NOTE: Padding does not need to be taken into account here, because
+        //       chrono pads numbers to the maximal width of their value range
+        //       (e.g. `%e` and `%d` always print 2 columns) or not at all (e.g.
+        //       `%-d` prints 1 or 2 columns), so it never exceeds our bound.
         Item::Numeric(numeric, _pad) => {
-            let digits = |number: u64| (number as f32).log10().ceil() as usize;
+            let digits = format::decimal_digits;
             let max_supported_year_digits = digits(MAX_SUPPORTED_YEAR as u64);
 
             match numeric {
                 Numeric::Year | Numeric::IsoYear => {
                     // Per RFC 8601, year 10k+ will need an explicit sign
-                    let sign_length = (MAX_SUPPORTED_YEAR > 10_000) as usize;
+                    let sign_length = (MAX_SUPPORTED_YEAR >= 10_000) as usize;
                     max_supported_year_digits + sign_length
                 }
 
@@ -162,9 +430,14 @@ fn max_item_width(item: &Item) -> usize {
                 Numeric::Nanosecond => 9,
 
                 Numeric::Timestamp => {
-                    let max_unix_year = (MAX_SUPPORTED_YEAR - 1970) as f32;
-                    let max_timestamp = max_unix_year * 365.25 * 24.0 * 3600.0;
-                    digits(max_timestamp as u64)
+                    // Timestamps before 1970 are negative and need a sign
+                    let timestamp_width = |unix_years: i32| {
+                        let timestamp = unix_years as f32 * 365.25 * 24.0 * 3600.0;
+                        digits(timestamp.abs() as u64) + (timestamp < 0.0) as usize
+                    };
+                    let max_width = timestamp_width(MAX_SUPPORTED_YEAR + 1 - 1970);
+                    let min_width = timestamp_width(MIN_SUPPORTED_YEAR - 1970);
+                    max_width.max(min_width)
                 }
 
                 // Internal chrono stuff, shouldn't pop up in normal formatting
@@ -248,11 +521,105 @@ fn max_item_width(item: &Item) -> usize {
                     max_format_width(RFC3339)
                 }
 
-                // Internal chrono stuff, shouldn't pop up in normal formatting
-                Fixed::Internal(_internal) => unreachable!(),
+                // The only internal items which strftime can emit are the
+                // undotted %3f, %6f and %9f fractional seconds (and %#z, which
+                // chrono refuses to format anyway), at most 9 digits wide
+                Fixed::Internal(_internal) => 9,
             }
         }
 
         Item::Error => panic!("Input time format string is invalid!"),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use chrono::{FixedOffset, NaiveDate, Utc};
+
+    use proptest::prelude::*;
+
+    /// Building blocks of the randomly generated format strings, covering
+    /// every strftime specifier which is supported in tabular output
+    const FORMAT_PIECES: &[&str] = &[
+        "%Y", "%C", "%y", "%m", "%b", "%B", "%h", "%d", "%e", "%a", "%A", "%w", "%u", "%U", "%W",
+        "%G", "%g", "%V", "%j", "%D", "%x", "%F", "%v", "%H", "%k", "%I", "%l", "%P", "%p", "%M",
+        "%S", "%f", "%.f", "%.3f", "%.6f", "%.9f", "%3f", "%6f", "%9f", "%R", "%T", "%X", "%r",
+        "%z", "%:z", "%c", "%+", "%s", "%t", "%%", "%-d", "%-m", "%_H", "%0e", " ", "-", ":", "T",
+        "é", "∞",
+    ];
+
+    /// Strategy which generates random format strings
+    fn format_string() -> impl Strategy<Value = String> {
+        prop::collection::vec(prop::sample::select(FORMAT_PIECES), 1..10)
+            .prop_map(|pieces| pieces.concat())
+    }
+
+    /// Strategy which generates random date/times within the supported range,
+    /// in random time zones
+    fn date_time() -> impl Strategy<Value = DateTime<FixedOffset>> {
+        // Keep one day of margin, so that no time zone offset can take the
+        // local date/time out of the supported range
+        let min_timestamp = NaiveDate::from_ymd(MIN_SUPPORTED_YEAR, 1, 2)
+            .and_hms(0, 0, 0)
+            .timestamp();
+        let max_timestamp = NaiveDate::from_ymd(MAX_SUPPORTED_YEAR, 12, 30)
+            .and_hms(23, 59, 59)
+            .timestamp();
+        (
+            min_timestamp..=max_timestamp,
+            0..1_000_000_000u32,
+            -86_399..86_400i32,
+        )
+            .prop_map(|(secs, nsecs, offset)| FixedOffset::east(offset).timestamp(secs, nsecs))
+    }
+
+    #[test]
+    fn day_of_month_padding() {
+        for day in 1..=31 {
+            let date_time = Utc.ymd(2020, 1, day).and_hms(12, 0, 0);
+            let expectations = vec![("%e", format!("{:>2}", day)), ("%d", format!("{:02}", day))];
+            for (format, expected) in expectations {
+                let parsed_format = ParsedFormat::new(format);
+                assert_eq!(parsed_format.max_output_width(), 2);
+                assert_eq!(parsed_format.format(date_time).to_string(), expected);
+
+                // Column data must fill the column exactly, whatever the day
+                let formatter = Formatter::from_parsed(parsed_format, None);
+                let data = formatter.display_data(date_time).to_string();
+                assert_eq!(format::str_width(&data), formatter.output_width());
+                assert!(data.starts_with(&expected));
+            }
+        }
+    }
+
+    #[test]
+    fn seconds_parsing() {
+        assert_eq!(parse_secs("0"), Ok(Duration::from_secs(0)));
+        assert_eq!(parse_secs("1.5"), Ok(Duration::from_millis(1500)));
+        for invalid in &["", "one", "-1", "NaN", "inf", "1e30"] {
+            assert!(parse_secs(invalid).is_err(), "{:?} was accepted", invalid);
+        }
+        assert!(parse_period("0").is_err());
+        assert!(parse_period("1e-10").is_err());
+        assert_eq!(parse_period("0.1"), Ok(Duration::from_millis(100)));
+        assert!(parse_timeout("0").is_err());
+        assert_eq!(parse_timeout("5"), Ok(Duration::from_secs(5)));
+        assert!(parse_heartbeat("0").is_err());
+        assert!(parse_heartbeat("0.5").is_err());
+        assert!(parse_heartbeat("-60").is_err());
+        assert_eq!(parse_heartbeat("60"), Ok(Duration::from_secs(60)));
+    }
+
+    proptest! {
+        #[test]
+        fn formatted_time_fits_column(format in format_string(), date_time in date_time()) {
+            let parsed_format = ParsedFormat::new(&format);
+            let formatter = Formatter::from_parsed(parsed_format.clone(), None);
+            let width = format::str_width(&parsed_format.format(date_time).to_string());
+            prop_assert!(width <= parsed_format.max_output_width());
+            prop_assert!(width <= formatter.output_width());
+        }
+    }
+}
@@ -0,0 +1,134 @@
+//! Totals of the bytes transferred by a set of devices over a monitoring run
+//!
+//! Network interfaces and block devices expose monotonic counters of the bytes
+//! that they transferred in each direction. The totals over a run are computed
+//! as the difference between the last and initial counters of each device,
+//! rather than as a sum of per-sample rates, so that they do not accumulate
+//! rounding errors. But counters start over from zero when a device is reset,
+//! and devices may come and go during the run, so the traffic that was counted
+//! before such events must be carried over.
+
+use std::collections::HashMap;
+
+/// Pair of monotonic byte counters of a device (e.g. bytes received and sent)
+pub type ByteCounters = (u64, u64);
+
+/// Bytes transferred by a set of devices since monitoring started
+pub struct ByteTotals {
+    /// Last measured counters of each device which is currently present
+    last: HashMap<String, ByteCounters>,
+
+    /// Counters of each device when it was first measured (or when it last
+    /// reappeared), or zero if its counters were reset since then
+    baselines: HashMap<String, ByteCounters>,
+
+    /// Bytes transferred by each device before its counters were last reset,
+    /// or before it last disappeared
+    carried_over: HashMap<String, ByteCounters>,
+}
+
+impl ByteTotals {
+    /// Start counting from the current counters of every device
+    pub fn new(counters: HashMap<String, ByteCounters>) -> Self {
+        Self {
+            baselines: counters.clone(),
+            last: counters,
+            carried_over: HashMap::new(),
+        }
+    }
+
+    /// Account for a new measurement of the counters of every device
+    ///
+    /// Devices which appeared since the last measurement are counted from this
+    /// one. Devices whose counters went down were reset, and are counted from
+    /// zero. The traffic of devices which disappeared is kept.
+    ///
+    pub fn update(&mut self, counters: HashMap<String, ByteCounters>) {
+        for (device, &new) in &counters {
+            match self.last.get(device) {
+                None => {
+                    self.baselines.insert(device.clone(), new);
+                }
+                Some(&last) if new.0 < last.0 || new.1 < last.1 => {
+                    self.carry_over(device, last);
+                    self.baselines.insert(device.clone(), (0, 0));
+                }
+                Some(_) => {}
+            }
+        }
+        let vanished = self
+            .last
+            .iter()
+            .filter(|(device, _last)| !counters.contains_key(*device))
+            .map(|(device, &last)| (device.clone(), last))
+            .collect::<Vec<_>>();
+        for (device, last) in vanished {
+            self.carry_over(&device, last);
+            self.baselines.remove(&device);
+        }
+        self.last = counters;
+    }
+
+    /// Bytes transferred in each direction since monitoring started, summed
+    /// over devices, as of the last measurement
+    pub fn totals(&self) -> ByteCounters {
+        let mut totals = (0, 0);
+        for (device, last) in &self.last {
+            let baseline = self.baselines.get(device).unwrap_or(last);
+            totals.0 += last.0.saturating_sub(baseline.0);
+            totals.1 += last.1.saturating_sub(baseline.1);
+        }
+        for carried_over in self.carried_over.values() {
+            totals.0 += carried_over.0;
+            totals.1 += carried_over.1;
+        }
+        totals
+    }
+
+    /// Keep the bytes that a device transferred up to its last counters, which
+    /// are about to be discarded
+    fn carry_over(&mut self, device: &str, last: ByteCounters) {
+        let baseline = self.baselines.get(device).copied().unwrap_or_default();
+        let carried_over = self.carried_over.entry(device.to_owned()).or_default();
+        carried_over.0 += last.0.saturating_sub(baseline.0);
+        carried_over.1 += last.1.saturating_sub(baseline.1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn counters(devices: &[(&str, ByteCounters)]) -> HashMap<String, ByteCounters> {
+        devices
+            .iter()
+            .map(|&(device, counters)| (device.to_owned(), counters))
+            .collect()
+    }
+
+    #[test]
+    fn resets_and_vanished_devices() {
+        let mut totals = ByteTotals::new(counters(&[("eth0", (100, 10))]));
+        assert_eq!(totals.totals(), (0, 0));
+
+        // Traffic is counted from the initial counters, new devices from their
+        // first measurement
+        totals.update(counters(&[("eth0", (150, 20)), ("wg0", (5, 5))]));
+        assert_eq!(totals.totals(), (50, 10));
+        totals.update(counters(&[("eth0", (160, 20)), ("wg0", (25, 15))]));
+        assert_eq!(totals.totals(), (80, 20));
+
+        // Traffic that was counted before a reset is kept
+        totals.update(counters(&[("eth0", (3, 2)), ("wg0", (25, 15))]));
+        assert_eq!(totals.totals(), (83, 22));
+
+        // So is the traffic of devices which disappear...
+        totals.update(counters(&[("eth0", (3, 2))]));
+        assert_eq!(totals.totals(), (83, 22));
+
+        // ...and later reappear
+        totals.update(counters(&[("eth0", (3, 2)), ("wg0", (1, 1))]));
+        totals.update(counters(&[("eth0", (3, 2)), ("wg0", (2, 4))]));
+        assert_eq!(totals.totals(), (84, 25));
+    }
+}
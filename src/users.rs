@@ -1,3 +1,5 @@
+use crate::warning::BenchmonWarning;
+
 use heim::host::{Pid, User};
 
 use slog::{debug, info, o, warn, Logger};
@@ -19,7 +21,11 @@ struct UserStats {
 }
 
 /// Report on the host's open user sessions
-pub fn startup_report(log: &Logger, user_connections: Vec<User>) {
+///
+/// Users whose name is listed in `ignored_users` (e.g. service accounts) are
+/// reported, but do not count as other users of the system.
+///
+pub fn startup_report(log: &Logger, user_connections: Vec<User>, ignored_users: &[String]) {
     // The OS APIs give us a list of active user connections, when what we
     // actually want is a breakdown of these connections by user, and by user
     // session on OSes that have that concept. Let's build that.
@@ -70,11 +76,16 @@ pub fn startup_report(log: &Logger, user_connections: Vec<User>) {
     }
 
     // Warn if other users are active on this system
-    if usernames_to_stats.len() > 1 {
+    let num_users = usernames_to_stats
+        .keys()
+        .filter(|username| !ignored_users.contains(username))
+        .count();
+    if num_users > 1 {
         warn!(
             log,
             "Detected multiple logged-in users, make sure others keep the \
-             system quiet while your benchmarks are running!"
+             system quiet while your benchmarks are running!";
+            "warning" => BenchmonWarning::MultipleUsers
         );
     }
 }
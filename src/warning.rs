@@ -0,0 +1,328 @@
+//! Machine-readable identification of benchmon's warnings
+
+use chrono::{DateTime, Local};
+
+use slog::{Drain, Key, OwnedKVList, Record, Serializer, Value, KV};
+
+use std::{
+    fmt, mem,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+};
+
+/// Exit code of benchmon when a warning was emitted and --fail-on-warning is
+/// set, so that CI scripts can tell an unclean environment apart from errors
+///
+/// The full exit code scheme is 0 on success, 1 on errors, 2 on invalid
+/// command-line options, and this code when warnings are treated as failures.
+///
+pub const WARNING_EXIT_CODE: i32 = 3;
+
+/// Condition which benchmon warns about
+///
+/// Every warning emitted by benchmon carries a "warning" key whose value is
+/// the stable code of one of these conditions (given in parentheses below), so
+/// that log processors can act on specific warnings without parsing
+/// human-readable messages.
+///
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum BenchmonWarning {
+    /// benchmon is running with superuser privileges (`SUPERUSER`)
+    Superuser,
+
+    /// Many processes could not be fully queried (`PROCESS_ACCESS_DENIED`)
+    ProcessAccessDenied,
+
+    /// A zombie process was found (`ZOMBIE_PROCESS`)
+    ZombieProcess,
+
+    /// A known source of background activity is running (`NOISY_PROCESS`)
+    NoisyProcess,
+
+    /// Other users are logged into the system (`MULTIPLE_USERS`)
+    MultipleUsers,
+
+    /// A non-negligible amount of swap is in use (`SWAP_IN_USE`)
+    SwapInUse,
+
+    /// The system runs on top of a virtualization layer (`VIRTUALIZATION`)
+    Virtualization,
+
+    /// The system has multiple CPU sockets (`MULTIPLE_SOCKETS`)
+    MultipleSockets,
+
+    /// Deep CPU idle states are enabled (`DEEP_IDLE_STATES`)
+    DeepIdleStates,
+
+    /// Some CPU frequency range data is missing (`CPU_FREQUENCY_UNKNOWN`)
+    CpuFrequencyUnknown,
+
+    /// Some system characteristics could not be queried (`PROBE_FAILED`)
+    ProbeFailed,
+
+    /// The wall clock was adjusted during monitoring (`CLOCK_JUMP`)
+    ClockJump,
+
+    /// A sample could not be collected in time (`SAMPLING_STALLED`)
+    SamplingStalled,
+
+    /// Resource pressure rose sharply during monitoring (`PRESSURE_RISE`)
+    PressureRise,
+
+    /// User-provided markers could not be read (`MARKER_READ_FAILED`)
+    MarkerReadFailed,
+
+    /// Memory allocations were skewed towards one NUMA node
+    /// (`NUMA_IMBALANCE`)
+    NumaImbalance,
+
+    /// A filesystem is stored on a rotational device (`ROTATIONAL_STORAGE`)
+    RotationalStorage,
+
+    /// Uncorrectable memory errors occured since boot (`MEMORY_ERRORS`)
+    MemoryErrors,
+
+    /// Reading files on a watched path updates their access time
+    /// (`ATIME_UPDATES`)
+    AtimeUpdates,
+
+    /// The system was already heavily loaded before monitoring started
+    /// (`HIGH_LOAD`)
+    HighLoad,
+}
+
+impl BenchmonWarning {
+    /// Stable machine-readable code of this warning
+    pub fn code(self) -> &'static str {
+        match self {
+            BenchmonWarning::Superuser => "SUPERUSER",
+            BenchmonWarning::ProcessAccessDenied => "PROCESS_ACCESS_DENIED",
+            BenchmonWarning::ZombieProcess => "ZOMBIE_PROCESS",
+            BenchmonWarning::NoisyProcess => "NOISY_PROCESS",
+            BenchmonWarning::MultipleUsers => "MULTIPLE_USERS",
+            BenchmonWarning::SwapInUse => "SWAP_IN_USE",
+            BenchmonWarning::Virtualization => "VIRTUALIZATION",
+            BenchmonWarning::MultipleSockets => "MULTIPLE_SOCKETS",
+            BenchmonWarning::DeepIdleStates => "DEEP_IDLE_STATES",
+            BenchmonWarning::CpuFrequencyUnknown => "CPU_FREQUENCY_UNKNOWN",
+            BenchmonWarning::ProbeFailed => "PROBE_FAILED",
+            BenchmonWarning::ClockJump => "CLOCK_JUMP",
+            BenchmonWarning::SamplingStalled => "SAMPLING_STALLED",
+            BenchmonWarning::PressureRise => "PRESSURE_RISE",
+            BenchmonWarning::MarkerReadFailed => "MARKER_READ_FAILED",
+            BenchmonWarning::NumaImbalance => "NUMA_IMBALANCE",
+            BenchmonWarning::RotationalStorage => "ROTATIONAL_STORAGE",
+            BenchmonWarning::MemoryErrors => "MEMORY_ERRORS",
+            BenchmonWarning::AtimeUpdates => "ATIME_UPDATES",
+            BenchmonWarning::HighLoad => "HIGH_LOAD",
+        }
+    }
+}
+
+/// Benchmon warning which was logged
+#[derive(Clone, Debug, PartialEq)]
+pub struct LoggedWarning {
+    /// Local date and time at which the warning was logged
+    pub time: DateTime<Local>,
+
+    /// Stable code of the warning (see `BenchmonWarning::code()`)
+    pub code: String,
+
+    /// Human-readable message of the warning
+    pub message: String,
+}
+
+/// Queue of the benchmon warnings which were logged, for outputs which record
+/// them alongside measurements (see the --csv-comments option)
+#[derive(Clone, Default)]
+pub struct WarningQueue(Arc<Mutex<Vec<LoggedWarning>>>);
+
+impl WarningQueue {
+    /// Record a warning
+    fn push(&self, warning: LoggedWarning) {
+        self.0.lock().unwrap().push(warning);
+    }
+
+    /// Extract the warnings which were logged since the last call, oldest first
+    pub fn take(&self) -> Vec<LoggedWarning> {
+        mem::take(&mut *self.0.lock().unwrap())
+    }
+}
+
+/// Logging drain which counts the records that carry a benchmon warning
+/// before forwarding them to another drain
+pub struct WarningCounter<D: Drain> {
+    /// Drain which records are forwarded to
+    drain: D,
+
+    /// Number of benchmon warnings which were logged so far
+    count: Arc<AtomicUsize>,
+
+    /// Queue where benchmon warnings are also recorded, if requested
+    queue: Option<WarningQueue>,
+}
+
+impl<D: Drain> WarningCounter<D> {
+    /// Wrap a drain, returning the wrapper and a handle to the warning count
+    ///
+    /// If a `queue` is specified, benchmon warnings are also recorded there.
+    ///
+    pub fn new(drain: D, queue: Option<WarningQueue>) -> (Self, Arc<AtomicUsize>) {
+        let count = Arc::new(AtomicUsize::new(0));
+        (
+            Self {
+                drain,
+                count: count.clone(),
+                queue,
+            },
+            count,
+        )
+    }
+}
+
+impl<D: Drain> Drain for WarningCounter<D> {
+    type Ok = D::Ok;
+    type Err = D::Err;
+
+    fn log(&self, record: &Record, values: &OwnedKVList) -> Result<Self::Ok, Self::Err> {
+        /// Serializer which looks for the "warning" key, and extracts its code
+        struct WarningFinder(Option<String>);
+        impl Serializer for WarningFinder {
+            fn emit_arguments(&mut self, key: Key, val: &fmt::Arguments) -> slog::Result {
+                if key == "warning" {
+                    self.0 = Some(val.to_string());
+                }
+                Ok(())
+            }
+        }
+
+        let mut finder = WarningFinder(None);
+        if record.kv().serialize(record, &mut finder).is_ok() {
+            if let Some(code) = finder.0 {
+                self.count.fetch_add(1, Ordering::Relaxed);
+                if let Some(queue) = &self.queue {
+                    queue.push(LoggedWarning {
+                        time: Local::now(),
+                        code,
+                        message: record.msg().to_string(),
+                    });
+                }
+            }
+        }
+        self.drain.log(record, values)
+    }
+}
+
+impl Value for BenchmonWarning {
+    fn serialize(
+        &self,
+        _record: &Record,
+        key: Key,
+        serializer: &mut dyn Serializer,
+    ) -> slog::Result {
+        serializer.emit_str(key, self.code())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use slog::{info, o, warn, Discard, Logger};
+
+    use std::{
+        fs,
+        path::{Path, PathBuf},
+    };
+
+    #[test]
+    fn warning_queue() {
+        let queue = WarningQueue::default();
+        let (drain, count) = WarningCounter::new(Discard, Some(queue.clone()));
+        let log = Logger::root(drain, o!());
+        info!(log, "Not a warning");
+        warn!(log, "Swap is in use"; "warning" => BenchmonWarning::SwapInUse);
+        warn!(log, "Clock jumped"; "warning" => BenchmonWarning::ClockJump);
+        assert_eq!(count.load(Ordering::Relaxed), 2);
+
+        let warnings = queue.take();
+        let codes = warnings.iter().map(|w| &w.code[..]).collect::<Vec<_>>();
+        assert_eq!(codes, ["SWAP_IN_USE", "CLOCK_JUMP"]);
+        assert_eq!(warnings[0].message, "Swap is in use");
+        assert!(warnings[0].time <= warnings[1].time);
+        assert!(queue.take().is_empty());
+    }
+
+    /// Recursively list the Rust source files of a directory
+    fn source_files(dir: &Path) -> Vec<PathBuf> {
+        let mut files = Vec::new();
+        for entry in fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                files.extend(source_files(&path));
+            } else if path.extension().map_or(false, |ext| ext == "rs") {
+                files.push(path);
+            }
+        }
+        files
+    }
+
+    /// Extract the message of every warning that is emitted by a source file,
+    /// i.e. the first string literal of each warn!() invocation, as it will
+    /// be displayed (with escape sequences and line continuations resolved)
+    fn warning_messages(source: &str) -> Vec<String> {
+        // Spelled this way so that this test does not find itself
+        const WARN_INVOCATION: &str = concat!("warn", "!(");
+        let mut messages = Vec::new();
+        for (start, _) in source.match_indices(WARN_INVOCATION) {
+            let invocation = &source[start..];
+            let literal_start = match invocation.find('"') {
+                Some(quote_idx) => quote_idx + 1,
+                None => continue,
+            };
+            let mut message = String::new();
+            let mut chars = invocation[literal_start..].chars().peekable();
+            while let Some(c) = chars.next() {
+                match c {
+                    '"' => break,
+                    '\\' => match chars.next() {
+                        // A line continuation skips the newline and the next
+                        // line's indentation
+                        Some('\n') => {
+                            while chars.peek().map_or(false, |c| c.is_whitespace()) {
+                                chars.next();
+                            }
+                        }
+                        Some('n') => message.push('\n'),
+                        Some(other) => message.push(other),
+                        None => break,
+                    },
+                    other => message.push(other),
+                }
+            }
+            messages.push(message);
+        }
+        messages
+    }
+
+    #[test]
+    fn warnings_are_single_line() {
+        let src_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("src");
+        let mut checked_messages = 0;
+        for path in source_files(&src_dir) {
+            let source = fs::read_to_string(&path).unwrap();
+            for message in warning_messages(&source) {
+                assert!(
+                    !message.contains('\n'),
+                    "Warning message in {} spans multiple lines: {:?}",
+                    path.display(),
+                    message
+                );
+                checked_messages += 1;
+            }
+        }
+        assert!(checked_messages > 0, "No warning message was found");
+    }
+}